@@ -0,0 +1,581 @@
+// 归档导入：本应用导出的 JSON、ChatGPT conversations.json、OpenAI 消息格式 JSONL。
+
+const ARCHIVE_IMPORT_REASON: &str = "imported";
+const ARCHIVE_EXPORT_VERSION: u32 = 1;
+
+/// 导入时的中间消息，统一三种来源后再转成 ChatMessage。
+#[derive(Debug, Clone, Default)]
+struct ImportedMessage {
+    role: String,
+    text: String,
+    images: Vec<(String, String)>,
+    created_at: Option<String>,
+    tool_calls: Option<Vec<Value>>,
+    tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ImportedConversation {
+    source_id: Option<String>,
+    title: String,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    messages: Vec<ImportedMessage>,
+}
+
+#[derive(Debug, Clone)]
+struct ParsedArchiveImport {
+    /// easyCallAi | chatgpt | openaiJsonl
+    format: String,
+    archives: Vec<ConversationArchive>,
+}
+
+fn unix_seconds_to_iso(value: &Value) -> Option<String> {
+    let seconds = value.as_f64()?;
+    OffsetDateTime::from_unix_timestamp_nanos((seconds * 1_000_000_000.0) as i128)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+fn parse_image_data_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("data:")?;
+    let (mime, data) = rest.split_once(";base64,")?;
+    if mime.trim().is_empty() || data.trim().is_empty() {
+        return None;
+    }
+    Some((mime.trim().to_string(), data.trim().to_string()))
+}
+
+/// OpenAI content 既可能是字符串，也可能是 text / image_url 分段数组。
+fn openai_content_parts(content: Option<&Value>) -> (String, Vec<(String, String)>) {
+    let mut texts = Vec::<String>::new();
+    let mut images = Vec::new();
+    match content {
+        Some(Value::String(text)) => texts.push(text.clone()),
+        Some(Value::Array(parts)) => {
+            for part in parts {
+                match part.get("type").and_then(Value::as_str).unwrap_or("text") {
+                    "text" | "input_text" | "output_text" => {
+                        if let Some(text) = part.get("text").and_then(Value::as_str) {
+                            texts.push(text.to_string());
+                        }
+                    }
+                    "image_url" => {
+                        let url = part
+                            .get("image_url")
+                            .and_then(|v| v.get("url").or(Some(v)))
+                            .and_then(Value::as_str)
+                            .unwrap_or_default();
+                        if let Some(image) = parse_image_data_url(url) {
+                            images.push(image);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    (texts.join("\n"), images)
+}
+
+/// 统一 tool_calls 结构：补齐 id / type，arguments 必须是字符串。
+fn normalize_imported_tool_calls(raw: &Value) -> Option<Vec<Value>> {
+    let calls = raw
+        .as_array()?
+        .iter()
+        .filter_map(|call| {
+            let function = call.get("function")?;
+            let name = function.get("name").and_then(Value::as_str)?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let arguments = match function.get("arguments") {
+                Some(Value::String(args)) => args.clone(),
+                Some(Value::Null) | None => "{}".to_string(),
+                Some(other) => other.to_string(),
+            };
+            let id = call
+                .get("id")
+                .and_then(Value::as_str)
+                .filter(|id| !id.trim().is_empty())
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(|| format!("call_{}", Uuid::new_v4().simple()));
+            Some(serde_json::json!({
+                "id": id,
+                "type": "function",
+                "function": { "name": name, "arguments": arguments }
+            }))
+        })
+        .collect::<Vec<_>>();
+    if calls.is_empty() {
+        None
+    } else {
+        Some(calls)
+    }
+}
+
+fn openai_message_to_imported(raw: &Value) -> Option<ImportedMessage> {
+    let role = raw
+        .get("role")
+        .and_then(Value::as_str)?
+        .trim()
+        .to_lowercase();
+    let (text, images) = openai_content_parts(raw.get("content"));
+    Some(ImportedMessage {
+        role,
+        text,
+        images,
+        created_at: raw
+            .get("created_at")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+        tool_calls: raw
+            .get("tool_calls")
+            .and_then(normalize_imported_tool_calls),
+        tool_call_id: raw
+            .get("tool_call_id")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+    })
+}
+
+/// 按本应用的存储方式组装消息：工具调用过程挂在其后的助手消息 tool_call 上，
+/// 与 build_prompt 的展开顺序一致；没有后续助手回复时补一条空助手消息承载。
+fn imported_messages_to_chat(items: Vec<ImportedMessage>, fallback_time: &str) -> Vec<ChatMessage> {
+    fn chat_message(role: &str, item: &ImportedMessage, created_at: &str) -> ChatMessage {
+        let mut parts = Vec::new();
+        if !item.text.trim().is_empty() {
+            parts.push(MessagePart::Text {
+                text: item.text.clone(),
+            });
+        }
+        for (mime, bytes_base64) in &item.images {
+            parts.push(MessagePart::Image {
+                mime: mime.clone(),
+                bytes_base64: bytes_base64.clone(),
+                blob_hash: None,
+                name: None,
+                compressed: false,
+            });
+        }
+        ChatMessage {
+            id: Uuid::new_v4().to_string(),
+            role: role.to_string(),
+            created_at: created_at.to_string(),
+            parts,
+            extra_text_blocks: Vec::new(),
+            provider_meta: None,
+            tool_call: None,
+            mcp_call: None,
+            parent_id: None,
+            sibling_index: 0,
+        }
+    }
+
+    let mut out = Vec::<ChatMessage>::new();
+    let mut pending_events = Vec::<Value>::new();
+    let mut last_time = fallback_time.to_string();
+    for item in items {
+        if let Some(created_at) = item
+            .created_at
+            .as_deref()
+            .filter(|t| parse_iso(t).is_some())
+        {
+            last_time = created_at.to_string();
+        }
+        match item.role.as_str() {
+            "assistant" if item.tool_calls.is_some() => {
+                pending_events.push(serde_json::json!({
+                    "role": "assistant",
+                    "content": if item.text.trim().is_empty() { Value::Null } else { Value::String(item.text.clone()) },
+                    "tool_calls": item.tool_calls
+                }));
+            }
+            "tool" | "function" => {
+                pending_events.push(serde_json::json!({
+                    "role": "tool",
+                    "tool_call_id": item.tool_call_id,
+                    "content": item.text
+                }));
+            }
+            "assistant" => {
+                let mut message = chat_message("assistant", &item, &last_time);
+                if !pending_events.is_empty() {
+                    message.tool_call = Some(std::mem::take(&mut pending_events));
+                }
+                out.push(message);
+            }
+            "user" => {
+                if !pending_events.is_empty() {
+                    let mut carrier =
+                        chat_message("assistant", &ImportedMessage::default(), &last_time);
+                    carrier.tool_call = Some(std::mem::take(&mut pending_events));
+                    out.push(carrier);
+                }
+                if !item.text.trim().is_empty() || !item.images.is_empty() {
+                    out.push(chat_message("user", &item, &last_time));
+                }
+            }
+            _ => {}
+        }
+    }
+    if !pending_events.is_empty() {
+        let mut carrier = chat_message("assistant", &ImportedMessage::default(), &last_time);
+        carrier.tool_call = Some(pending_events);
+        out.push(carrier);
+    }
+    out
+}
+
+fn chatgpt_node_to_imported(message: &Value) -> Option<ImportedMessage> {
+    let role = message
+        .get("author")
+        .and_then(|a| a.get("role"))
+        .and_then(Value::as_str)?
+        .to_string();
+    let content = message.get("content")?;
+    let mut texts = Vec::<String>::new();
+    match content
+        .get("content_type")
+        .and_then(Value::as_str)
+        .unwrap_or("text")
+    {
+        "text" | "multimodal_text" => {
+            for part in content
+                .get("parts")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(text) = part.as_str() {
+                    texts.push(text.to_string());
+                } else if part.get("content_type").and_then(Value::as_str)
+                    == Some("image_asset_pointer")
+                {
+                    // 导出包里图片只是引用，无法还原内容。
+                    texts.push("[image]".to_string());
+                }
+            }
+        }
+        "code" | "execution_output" | "tether_quote" | "tether_browsing_display" => {
+            for key in ["text", "result"] {
+                if let Some(text) = content.get(key).and_then(Value::as_str) {
+                    texts.push(text.to_string());
+                }
+            }
+        }
+        _ => {}
+    }
+    let text = texts.join("\n");
+    let created_at = message.get("create_time").and_then(unix_seconds_to_iso);
+    let recipient = message
+        .get("recipient")
+        .and_then(Value::as_str)
+        .unwrap_or("all");
+    let tool_calls = if role == "assistant" && recipient != "all" {
+        Some(vec![serde_json::json!({
+            "id": message.get("id").and_then(Value::as_str).unwrap_or_default(),
+            "type": "function",
+            "function": { "name": recipient, "arguments": text }
+        })])
+    } else {
+        None
+    };
+    Some(ImportedMessage {
+        role,
+        text: if tool_calls.is_some() {
+            String::new()
+        } else {
+            text
+        },
+        images: Vec::new(),
+        created_at,
+        tool_calls,
+        tool_call_id: None,
+    })
+}
+
+/// ChatGPT 导出是消息树，沿 current_node 往上回溯得到当前显示的分支。
+fn parse_chatgpt_conversation(raw: &Value) -> Option<ImportedConversation> {
+    let mapping = raw.get("mapping")?.as_object()?;
+    let mut node_id = raw
+        .get("current_node")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+        .or_else(|| {
+            mapping
+                .iter()
+                .find(|(_, node)| {
+                    node.get("children")
+                        .and_then(Value::as_array)
+                        .is_none_or(|c| c.is_empty())
+                })
+                .map(|(id, _)| id.clone())
+        });
+    let mut chain = Vec::<ImportedMessage>::new();
+    let mut pending_call_id = None::<String>;
+    let mut visited = std::collections::HashSet::<String>::new();
+    while let Some(id) = node_id.take() {
+        if !visited.insert(id.clone()) {
+            break;
+        }
+        let Some(node) = mapping.get(&id) else {
+            break;
+        };
+        if let Some(message) = node.get("message").and_then(chatgpt_node_to_imported) {
+            chain.push(message);
+        }
+        node_id = node
+            .get("parent")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+    }
+    chain.reverse();
+    // 工具结果紧跟在调用之后，用调用的 id 关联。
+    for message in &mut chain {
+        if let Some(call_id) = message
+            .tool_calls
+            .as_ref()
+            .and_then(|calls| calls.first())
+            .and_then(|call| call.get("id"))
+            .and_then(Value::as_str)
+        {
+            pending_call_id = Some(call_id.to_string());
+        } else if message.role == "tool" {
+            message.tool_call_id = pending_call_id.take();
+        }
+    }
+    Some(ImportedConversation {
+        source_id: raw
+            .get("conversation_id")
+            .or_else(|| raw.get("id"))
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+        title: raw
+            .get("title")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        created_at: raw.get("create_time").and_then(unix_seconds_to_iso),
+        updated_at: raw.get("update_time").and_then(unix_seconds_to_iso),
+        messages: chain,
+    })
+}
+
+fn imported_conversation_from_openai_messages(
+    messages: &[Value],
+    title: String,
+    source_id: Option<String>,
+) -> ImportedConversation {
+    ImportedConversation {
+        source_id,
+        title,
+        created_at: None,
+        updated_at: None,
+        messages: messages
+            .iter()
+            .filter_map(openai_message_to_imported)
+            .collect(),
+    }
+}
+
+/// JSONL 两种写法：每行一个 {"messages": [...]} 对话（微调数据格式），或每行一条消息组成一个对话。
+fn parse_openai_jsonl(raw: &str) -> Result<Vec<ImportedConversation>, String> {
+    let mut conversations = Vec::new();
+    let mut loose_messages = Vec::<Value>::new();
+    for (idx, line) in raw.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line)
+            .map_err(|err| format!("Parse JSONL line {} failed: {err}", idx + 1))?;
+        if let Some(messages) = value.get("messages").and_then(Value::as_array) {
+            let title = value
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let source_id = value
+                .get("id")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned);
+            conversations.push(imported_conversation_from_openai_messages(
+                messages, title, source_id,
+            ));
+        } else if value.get("role").is_some() {
+            loose_messages.push(value);
+        } else {
+            return Err(format!(
+                "JSONL line {} is neither a message nor a {{\"messages\": [...]}} object.",
+                idx + 1
+            ));
+        }
+    }
+    if !loose_messages.is_empty() {
+        conversations.push(imported_conversation_from_openai_messages(
+            &loose_messages,
+            String::new(),
+            None,
+        ));
+    }
+    Ok(conversations)
+}
+
+fn imported_conversation_to_archive(
+    imported: ImportedConversation,
+    agent_id: &str,
+    api_config_id: &str,
+) -> Option<ConversationArchive> {
+    let now = now_iso();
+    let created_at = imported
+        .created_at
+        .clone()
+        .or_else(|| imported.messages.iter().find_map(|m| m.created_at.clone()))
+        .unwrap_or_else(|| now.clone());
+    let messages = imported_messages_to_chat(imported.messages, &created_at);
+    if !messages.iter().any(|m| m.role == "user") {
+        return None;
+    }
+    let updated_at = imported
+        .updated_at
+        .or_else(|| messages.last().map(|m| m.created_at.clone()))
+        .unwrap_or_else(|| now.clone());
+    let title_settled = !imported.title.trim().is_empty();
+    let title = if !title_settled {
+        format!(
+            "Imported {}",
+            created_at.chars().take(16).collect::<String>()
+        )
+    } else {
+        imported.title.trim().to_string()
+    };
+    Some(ConversationArchive {
+        archive_id: Uuid::new_v4().to_string(),
+        // 沿用原对话的最后时间，让导入的历史按原时间线排序。
+        archived_at: updated_at.clone(),
+        reason: ARCHIVE_IMPORT_REASON.to_string(),
+        summary: String::new(),
+        source_conversation: Conversation {
+            id: imported
+                .source_id
+                .filter(|id| !id.trim().is_empty())
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            title,
+            api_config_id: api_config_id.to_string(),
+            agent_id: agent_id.to_string(),
+            created_at,
+            updated_at,
+            last_user_at: messages
+                .iter()
+                .rfind(|m| m.role == "user")
+                .map(|m| m.created_at.clone()),
+            last_assistant_at: messages
+                .iter()
+                .rfind(|m| m.role == "assistant")
+                .map(|m| m.created_at.clone()),
+            last_context_usage_ratio: 0.0,
+            status: "archived".to_string(),
+            messages,
+            alternate_messages: Vec::new(),
+            title_settled,
+            forked_from_archive_id: None,
+            compaction: None,
+            messages_deferred: false,
+        },
+    })
+}
+
+/// 识别文件格式并转换为归档。agent_id / api_config_id 用于没有这些信息的外部格式。
+fn parse_archive_import(
+    raw: &str,
+    agent_id: &str,
+    api_config_id: &str,
+) -> Result<ParsedArchiveImport, String> {
+    let raw = raw.trim_start_matches('\u{feff}').trim();
+    if raw.is_empty() {
+        return Err("Import file is empty.".to_string());
+    }
+    let convert = |items: Vec<ImportedConversation>| {
+        items
+            .into_iter()
+            .filter_map(|c| imported_conversation_to_archive(c, agent_id, api_config_id))
+            .collect::<Vec<_>>()
+    };
+    let Ok(value) = serde_json::from_str::<Value>(raw) else {
+        return Ok(ParsedArchiveImport {
+            format: "openaiJsonl".to_string(),
+            archives: convert(parse_openai_jsonl(raw)?),
+        });
+    };
+
+    if value.get("archive").is_some() {
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+        if version > u64::from(ARCHIVE_EXPORT_VERSION) {
+            return Err(format!(
+                "Archive export version {version} is newer than supported version {ARCHIVE_EXPORT_VERSION}."
+            ));
+        }
+        let payload: ArchiveExportPayload = serde_json::from_value(value)
+            .map_err(|err| format!("Parse archive export failed: {err}"))?;
+        return Ok(ParsedArchiveImport {
+            format: "easyCallAi".to_string(),
+            archives: vec![payload.archive],
+        });
+    }
+
+    let items = match &value {
+        Value::Array(items) => items.clone(),
+        Value::Object(_) => vec![value.clone()],
+        _ => return Err("Unsupported archive import file.".to_string()),
+    };
+    if items.iter().any(|item| item.get("mapping").is_some()) {
+        return Ok(ParsedArchiveImport {
+            format: "chatgpt".to_string(),
+            archives: convert(
+                items
+                    .iter()
+                    .filter_map(parse_chatgpt_conversation)
+                    .collect(),
+            ),
+        });
+    }
+    if items.iter().all(|item| item.get("role").is_some()) {
+        return Ok(ParsedArchiveImport {
+            format: "openaiJsonl".to_string(),
+            archives: convert(vec![imported_conversation_from_openai_messages(
+                &items,
+                String::new(),
+                None,
+            )]),
+        });
+    }
+    if items.iter().all(|item| item.get("messages").is_some()) {
+        return Ok(ParsedArchiveImport {
+            format: "openaiJsonl".to_string(),
+            archives: convert(
+                items
+                    .iter()
+                    .map(|item| {
+                        imported_conversation_from_openai_messages(
+                            item.get("messages")
+                                .and_then(Value::as_array)
+                                .map(Vec::as_slice)
+                                .unwrap_or_default(),
+                            item.get("title")
+                                .and_then(Value::as_str)
+                                .unwrap_or_default()
+                                .to_string(),
+                            item.get("id")
+                                .and_then(Value::as_str)
+                                .map(ToOwned::to_owned),
+                        )
+                    })
+                    .collect(),
+            ),
+        });
+    }
+    Err("Unsupported archive import file.".to_string())
+}
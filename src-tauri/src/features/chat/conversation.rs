@@ -1,3 +1,4 @@
+/// 同一 (API 配置, 智能体) 下可有多条活动会话，列表中最靠后的一条即当前会话。
 fn latest_active_conversation_index(
     data: &AppData,
     api_config_id: &str,
@@ -18,11 +19,24 @@ fn ensure_active_conversation_index(
     if let Some(idx) = latest_active_conversation_index(data, api_config_id, agent_id) {
         return idx;
     }
+    push_active_conversation(data, api_config_id, agent_id, None)
+}
 
+/// 新建一条活动会话并放到列表末尾，使其成为当前会话。
+fn push_active_conversation(
+    data: &mut AppData,
+    api_config_id: &str,
+    agent_id: &str,
+    title: Option<&str>,
+) -> usize {
     let now = now_iso();
+    let title = title.map(str::trim).filter(|t| !t.is_empty());
     let conversation = Conversation {
         id: Uuid::new_v4().to_string(),
-        title: format!("Chat {}", &now.chars().take(16).collect::<String>()),
+        title: title
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| format!("Chat {}", &now.chars().take(16).collect::<String>())),
+        title_settled: title.is_some(),
         api_config_id: api_config_id.to_string(),
         agent_id: agent_id.to_string(),
         created_at: now.clone(),
@@ -32,16 +46,87 @@ fn ensure_active_conversation_index(
         last_context_usage_ratio: 0.0,
         status: "active".to_string(),
         messages: Vec::new(),
+        alternate_messages: Vec::new(),
+        forked_from_archive_id: None,
+        compaction: None,
+        messages_deferred: false,
     };
 
     data.conversations.push(conversation);
     data.conversations.len() - 1
 }
 
+/// 按 id 查找活动会话，并校验它属于当前的 API 配置与智能体。
+fn active_conversation_index_by_id(
+    data: &AppData,
+    conversation_id: &str,
+    api_config_id: &str,
+    agent_id: &str,
+) -> Result<usize, String> {
+    let idx = data
+        .conversations
+        .iter()
+        .position(|c| c.id == conversation_id && c.status == "active")
+        .ok_or_else(|| format!("Conversation '{conversation_id}' not found."))?;
+    let conversation = &data.conversations[idx];
+    if conversation.agent_id != agent_id || conversation.api_config_id != api_config_id {
+        return Err(format!(
+            "Conversation '{conversation_id}' does not belong to the selected agent and API config."
+        ));
+    }
+    Ok(idx)
+}
+
+/// 请求的智能体无效时依次回退到当前选中的智能体、第一个助手智能体。
+fn resolve_session_agent_id(data: &AppData, requested_agent_id: &str) -> Result<String, String> {
+    let requested_agent_id = requested_agent_id.trim();
+    [requested_agent_id, data.selected_agent_id.as_str()]
+        .into_iter()
+        .find(|id| data.agents.iter().any(|a| a.id == *id && !a.is_built_in_user))
+        .map(ToOwned::to_owned)
+        .or_else(|| {
+            data.agents
+                .iter()
+                .find(|a| !a.is_built_in_user)
+                .map(|a| a.id.clone())
+        })
+        .ok_or_else(|| "Selected agent not found.".to_string())
+}
+
+/// 会话使用的对话 API：会话显式指定的优先，其次智能体默认，再次全局对话 API，最后第一个文本 API。
+fn resolve_session_api_config(
+    app_config: &AppConfig,
+    agent: Option<&AgentProfile>,
+    requested_id: Option<&str>,
+) -> Result<ApiConfig, String> {
+    if let Some(api_id) = requested_id.map(str::trim).filter(|v| !v.is_empty()) {
+        return app_config
+            .api_configs
+            .iter()
+            .find(|a| a.id == api_id)
+            .cloned()
+            .ok_or_else(|| format!("Selected API config '{api_id}' not found."));
+    }
+    if let Some(api) = agent.and_then(|agent| agent_default_api_config(app_config, agent)) {
+        return Ok(api.clone());
+    }
+    resolve_selected_api_config(app_config, None)
+        .ok_or_else(|| "No API config configured. Please add one.".to_string())
+}
+
+/// 把会话移到列表末尾，使其成为所属 (API 配置, 智能体) 的当前会话。
+fn select_conversation_index(data: &mut AppData, idx: usize) -> usize {
+    let conversation = data.conversations.remove(idx);
+    data.conversations.push(conversation);
+    data.conversations.len() - 1
+}
+
 #[derive(Debug, Clone)]
 struct ArchiveDecision {
     should_archive: bool,
     forced: bool,
+    /// 以原地压缩代替归档。
+    compact: bool,
     reason: String,
     usage_ratio: f64,
 }
@@ -84,8 +169,12 @@ fn estimated_tokens_for_message(message: &ChatMessage) -> f64 {
 }
 
 fn estimate_conversation_tokens(conversation: &Conversation) -> u32 {
-    let mut sum = 0.0f64;
-    for msg in &conversation.messages {
+    let mut sum = conversation
+        .compaction
+        .as_ref()
+        .map(|c| estimated_tokens_for_text(&c.summary))
+        .unwrap_or(0.0);
+    for msg in &conversation.messages[compacted_start(conversation)..] {
         sum += estimated_tokens_for_message(msg);
     }
     sum.ceil().max(0.0) as u32
@@ -99,51 +188,65 @@ fn compute_context_usage_ratio(conversation: &Conversation, context_window_token
 fn decide_archive_before_user_message(
     conversation: &Conversation,
     context_window_tokens: u32,
+    policy: &ArchivePolicy,
+    now: OffsetDateTime,
 ) -> ArchiveDecision {
     let usage_ratio = compute_context_usage_ratio(conversation, context_window_tokens);
-    if usage_ratio >= 0.82 {
+    let decision = |should_archive: bool, forced: bool, reason: &str| ArchiveDecision {
+        should_archive,
+        forced,
+        compact: false,
+        reason: reason.to_string(),
+        usage_ratio,
+    };
+    if policy.overflow_action == ContextOverflowAction::Compact
+        && usage_ratio >= policy.force_usage_ratio
+    {
         return ArchiveDecision {
-            should_archive: true,
-            forced: true,
-            reason: "force_context_usage_82".to_string(),
-            usage_ratio,
+            compact: true,
+            ..decision(false, true, "compact_context_usage")
         };
     }
+    if policy.mode == ArchiveMode::Never {
+        return decision(false, false, "policy_never");
+    }
+    if usage_ratio >= policy.force_usage_ratio {
+        return decision(true, true, "force_context_usage");
+    }
 
     let Some(last_user_at) = conversation.last_user_at.as_deref().and_then(parse_iso) else {
-        return ArchiveDecision {
-            should_archive: false,
-            forced: false,
-            reason: "no_last_user_timestamp".to_string(),
-            usage_ratio,
-        };
+        return decision(false, false, "no_last_user_timestamp");
     };
 
-    let now = now_utc();
-    let idle_seconds = now.unix_timestamp() - last_user_at.unix_timestamp();
-    if idle_seconds < ARCHIVE_IDLE_SECONDS {
-        return ArchiveDecision {
-            should_archive: false,
-            forced: false,
-            reason: "idle_not_reached_30m".to_string(),
-            usage_ratio,
+    if policy.mode == ArchiveMode::Daily {
+        let Some(rollover) = latest_daily_rollover(&policy.daily_time, now) else {
+            return decision(false, false, "invalid_daily_time");
         };
+        if last_user_at < rollover {
+            return decision(true, false, "daily_rollover");
+        }
+        return decision(false, false, "daily_rollover_not_reached");
     }
 
-    if usage_ratio >= 0.30 {
-        return ArchiveDecision {
-            should_archive: true,
-            forced: false,
-            reason: "idle_30m_and_usage_30pct".to_string(),
-            usage_ratio,
-        };
+    let idle_seconds = now.unix_timestamp() - last_user_at.unix_timestamp();
+    if idle_seconds < i64::from(policy.idle_minutes) * 60 {
+        return decision(false, false, "idle_not_reached");
+    }
+    if usage_ratio >= policy.idle_usage_ratio {
+        return decision(true, false, "idle_and_usage_reached");
     }
+    decision(false, false, "usage_below_idle_threshold")
+}
 
-    ArchiveDecision {
-        should_archive: false,
-        forced: false,
-        reason: "usage_below_30pct".to_string(),
-        usage_ratio,
+/// `now` 所在时区里最近一次（不晚于 now）的换日时刻。
+fn latest_daily_rollover(daily_time: &str, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    let (hour, minute) = parse_daily_time(daily_time)?;
+    let time = time::Time::from_hms(hour, minute, 0).ok()?;
+    let today = now.replace_time(time);
+    if today <= now {
+        Some(today)
+    } else {
+        Some(today - time::Duration::days(1))
     }
 }
 
@@ -172,6 +275,255 @@ fn archive_conversation_now(
     Some(archive_id)
 }
 
+/// 消息被截断或删除后，重新计算会话的时间戳与上下文占用。
+fn refresh_conversation_after_edit(conversation: &mut Conversation, context_window_tokens: u32) {
+    conversation.last_user_at = conversation
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .map(|m| m.created_at.clone());
+    conversation.last_assistant_at = conversation
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == "assistant")
+        .map(|m| m.created_at.clone());
+    conversation.updated_at = now_iso();
+    conversation.last_context_usage_ratio = if conversation.messages.is_empty() {
+        0.0
+    } else {
+        compute_context_usage_ratio(conversation, context_window_tokens)
+    };
+}
+
+/// 重新生成只针对最后一条用户消息；会话已变化时返回错误。
+fn regenerate_user_message_position(
+    conversation: &Conversation,
+    message_id: &str,
+) -> Result<usize, String> {
+    conversation
+        .messages
+        .iter()
+        .rposition(|m| m.role == "user")
+        .filter(|pos| conversation.messages[*pos].id == message_id)
+        .ok_or_else(|| "Conversation changed, please retry regenerating.".to_string())
+}
+
+/// 用已保存的用户消息还原发送载荷，供重新生成时走与发送相同的预处理。
+fn chat_payload_from_user_message(message: &ChatMessage) -> ChatInputPayload {
+    let mut texts = Vec::<String>::new();
+    let mut images = Vec::<BinaryPart>::new();
+    for part in &message.parts {
+        match part {
+            MessagePart::Text { text } => texts.push(text.clone()),
+            MessagePart::Image {
+                mime, bytes_base64, ..
+            } if !bytes_base64.is_empty() => images.push(BinaryPart {
+                mime: mime.clone(),
+                bytes_base64: bytes_base64.clone(),
+            }),
+            _ => {}
+        }
+    }
+    ChatInputPayload {
+        text: Some(texts.join("\n")).filter(|t| !t.trim().is_empty()),
+        images: Some(images).filter(|v| !v.is_empty()),
+        audios: None,
+        model: None,
+    }
+}
+
+/// 删除一条消息，或删除它所在的整轮（用户消息及其后直到下一条用户消息前的回复）。
+/// 工具调用事件保存在助手消息上，随助手消息一起删除，剩余历史仍能被 build_prompt 正确展开。
+fn remove_conversation_messages(
+    conversation: &mut Conversation,
+    message_id: &str,
+    whole_turn: bool,
+) -> Result<usize, String> {
+    let pos = conversation
+        .messages
+        .iter()
+        .position(|m| m.id == message_id)
+        .ok_or_else(|| "Message not found.".to_string())?;
+    let (start, end) = if whole_turn {
+        let start = conversation.messages[..=pos]
+            .iter()
+            .rposition(|m| m.role == "user")
+            .unwrap_or(pos);
+        let end = conversation.messages[pos + 1..]
+            .iter()
+            .position(|m| m.role == "user")
+            .map(|offset| pos + 1 + offset)
+            .unwrap_or(conversation.messages.len());
+        (start, end)
+    } else {
+        (pos, pos + 1)
+    };
+    conversation.messages.drain(start..end);
+    prune_orphan_branches(conversation);
+    drop_stale_compaction(conversation);
+    Ok(end - start)
+}
+
+/// 当前分支第 position 条消息在消息树上的父消息：以分支上的前一条为父，首条挂在根上。
+fn branch_parent_id(messages: &[ChatMessage], position: usize) -> Option<&str> {
+    position
+        .checked_sub(1)
+        .and_then(|p| messages.get(p))
+        .map(|m| m.id.as_str())
+}
+
+/// 按当前分支的顺序重写 parent_id，保证存入备选分支时父子关系正确。
+fn link_branch_parents(messages: &mut [ChatMessage]) {
+    for position in 0..messages.len() {
+        messages[position].parent_id = branch_parent_id(messages, position).map(str::to_string);
+    }
+}
+
+/// 当前分支第 position 条消息及其全部兄弟的 id，按 sibling_index 排序。
+fn branch_sibling_ids(conversation: &Conversation, position: usize) -> Vec<String> {
+    let parent_id = branch_parent_id(&conversation.messages, position);
+    let mut siblings = conversation
+        .messages
+        .get(position)
+        .into_iter()
+        .chain(
+            conversation
+                .alternate_messages
+                .iter()
+                .filter(|m| m.parent_id.as_deref() == parent_id),
+        )
+        .collect::<Vec<_>>();
+    siblings.sort_by(|a, b| {
+        a.sibling_index
+            .cmp(&b.sibling_index)
+            .then_with(|| a.created_at.cmp(&b.created_at))
+    });
+    siblings.into_iter().map(|m| m.id.clone()).collect()
+}
+
+/// 在当前分支末尾追加消息；同一父消息下已有其他分支时给出下一个兄弟序号。
+fn push_branch_message(conversation: &mut Conversation, mut message: ChatMessage) {
+    let position = conversation.messages.len();
+    let parent_id = branch_parent_id(&conversation.messages, position).map(str::to_string);
+    message.sibling_index = conversation
+        .alternate_messages
+        .iter()
+        .filter(|m| m.parent_id == parent_id)
+        .map(|m| m.sibling_index + 1)
+        .max()
+        .unwrap_or(0);
+    message.parent_id = parent_id;
+    conversation.messages.push(message);
+}
+
+/// 把当前分支从 position 起的消息移入备选分支，之后可通过切换分支找回。
+fn stash_branch_tail(conversation: &mut Conversation, position: usize) {
+    link_branch_parents(&mut conversation.messages);
+    let position = position.min(conversation.messages.len());
+    let tail = conversation.messages.split_off(position);
+    conversation.alternate_messages.extend(tail);
+    drop_stale_compaction(conversation);
+}
+
+/// 切换到 message_id 的前一个（offset < 0）或后一个兄弟分支，
+/// 并沿每层最近离开的子消息展开到叶子。
+fn switch_conversation_branch(
+    conversation: &mut Conversation,
+    message_id: &str,
+    offset: i32,
+) -> Result<(), String> {
+    let position = conversation
+        .messages
+        .iter()
+        .position(|m| m.id == message_id)
+        .ok_or_else(|| "Message not found.".to_string())?;
+    let siblings = branch_sibling_ids(conversation, position);
+    let current = siblings
+        .iter()
+        .position(|id| id == message_id)
+        .unwrap_or(0);
+    let target = current
+        .checked_add_signed(offset as isize)
+        .and_then(|idx| siblings.get(idx))
+        .ok_or_else(|| "No alternate branch in that direction.".to_string())?
+        .clone();
+    if target == message_id {
+        return Ok(());
+    }
+
+    stash_branch_tail(conversation, position);
+    let mut next = Some(target);
+    while let Some(id) = next {
+        let Some(idx) = conversation
+            .alternate_messages
+            .iter()
+            .position(|m| m.id == id)
+        else {
+            break;
+        };
+        let message = conversation.alternate_messages.remove(idx);
+        conversation.messages.push(message);
+        next = conversation
+            .alternate_messages
+            .iter()
+            .rfind(|m| m.parent_id.as_deref() == Some(id.as_str()))
+            .map(|m| m.id.clone());
+    }
+    Ok(())
+}
+
+/// 删除或截断消息后，丢弃父消息已不在树上的备选分支。
+fn prune_orphan_branches(conversation: &mut Conversation) {
+    loop {
+        let known = conversation
+            .messages
+            .iter()
+            .chain(conversation.alternate_messages.iter())
+            .map(|m| m.id.clone())
+            .collect::<std::collections::HashSet<_>>();
+        let before = conversation.alternate_messages.len();
+        conversation
+            .alternate_messages
+            .retain(|m| m.parent_id.as_ref().is_none_or(|p| known.contains(p)));
+        if conversation.alternate_messages.len() == before {
+            break;
+        }
+    }
+}
+
+/// 当前分支上存在多个兄弟的位置，供前端显示“2/3”并切换。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageBranchInfo {
+    message_id: String,
+    sibling_position: usize,
+    sibling_count: usize,
+}
+
+fn conversation_branch_infos(conversation: &Conversation) -> Vec<MessageBranchInfo> {
+    if conversation.alternate_messages.is_empty() {
+        return Vec::new();
+    }
+    conversation
+        .messages
+        .iter()
+        .enumerate()
+        .filter_map(|(position, message)| {
+            let siblings = branch_sibling_ids(conversation, position);
+            if siblings.len() < 2 {
+                return None;
+            }
+            Some(MessageBranchInfo {
+                message_id: message.id.clone(),
+                sibling_position: siblings.iter().position(|id| *id == message.id)?,
+                sibling_count: siblings.len(),
+            })
+        })
+        .collect()
+}
+
 fn keep_recent_turns(messages: &[ChatMessage], turn_count: usize) -> Vec<ChatMessage> {
     let mut turns: Vec<Vec<ChatMessage>> = Vec::new();
     let mut i = 0usize;
@@ -194,6 +546,234 @@ fn keep_recent_turns(messages: &[ChatMessage], turn_count: usize) -> Vec<ChatMes
     turns[start..].iter().flat_map(|t| t.clone()).collect::<Vec<_>>()
 }
 
+/// 压缩后第一条仍以原文发给模型的消息位置；未压缩时为 0。
+fn compacted_start(conversation: &Conversation) -> usize {
+    conversation
+        .compaction
+        .as_ref()
+        .and_then(|c| {
+            conversation
+                .messages
+                .iter()
+                .position(|m| m.id == c.until_message_id)
+        })
+        .map(|pos| pos + 1)
+        .unwrap_or(0)
+}
+
+/// 摘要覆盖的消息已不在当前分支上（被删除、编辑或切到其他分支）时，摘要随之失效。
+fn drop_stale_compaction(conversation: &mut Conversation) {
+    let stale = conversation.compaction.as_ref().is_some_and(|c| {
+        !conversation
+            .messages
+            .iter()
+            .any(|m| m.id == c.until_message_id)
+    });
+    if stale {
+        conversation.compaction = None;
+    }
+}
+
+/// 本次压缩覆盖到的位置：尽量保留最近 recent_turns 轮原文，不够时逐轮减少；
+/// 未压缩部分只剩一轮时返回 None。
+fn compaction_boundary(conversation: &Conversation, recent_turns: usize) -> Option<usize> {
+    let start = compacted_start(conversation);
+    let user_positions = conversation.messages[start..]
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.role == "user")
+        .map(|(idx, _)| start + idx)
+        .collect::<Vec<_>>();
+    (1..=recent_turns.max(1)).rev().find_map(|turns| {
+        let keep_from = *user_positions.iter().rev().nth(turns - 1)?;
+        (keep_from > start).then_some(keep_from - 1)
+    })
+}
+
+/// 压缩任务：把上次摘要与本次新覆盖的消息合并成一份新的累计摘要。
+fn conversation_compaction_prompt(
+    conversation: &Conversation,
+    until: usize,
+    assistant_name: &str,
+    user_name: &str,
+) -> PreparedPrompt {
+    let start = compacted_start(conversation);
+    let mut transcript = String::new();
+    for msg in &conversation.messages[start..=until] {
+        transcript.push_str(&render_message_for_context(msg));
+        transcript.push('\n');
+    }
+    let previous = conversation
+        .compaction
+        .as_ref()
+        .map(|c| c.summary.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("[PREVIOUS SUMMARY]\n{s}\n\n"))
+        .unwrap_or_default();
+    PreparedPrompt {
+        preamble: format!(
+            "[COMPACT TASK]\n把下面较早的对话压缩成一份摘要，供后续对话继续使用。\n\
+             要求：\n\
+             1) 只输出摘要正文，不要 JSON、代码块或额外说明。\n\
+             2) 若有上次摘要，合并进来，输出覆盖全部内容的新摘要。\n\
+             3) 保留用户的目标与偏好、已达成的结论、关键事实与数据、未完成的事项。\n\
+             4) 你是 {assistant_name}，用户称谓是 {user_name}。"
+        ),
+        history_messages: Vec::new(),
+        latest_user_text: format!("{previous}[CONVERSATION]\n{}", transcript.trim()),
+        latest_user_time_text: String::new(),
+        latest_user_system_text: String::new(),
+        latest_images: Vec::new(),
+        latest_audios: Vec::new(),
+    }
+}
+
+/// 摘要覆盖到 until（含）。摘要为空时保持原有压缩状态不变并返回 false，
+/// 避免未被总结的消息被移出上下文。
+fn apply_conversation_compaction(
+    conversation: &mut Conversation,
+    until: usize,
+    summary: &str,
+) -> bool {
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return false;
+    }
+    conversation.compaction = Some(ConversationCompaction {
+        summary: summary.to_string(),
+        until_message_id: conversation.messages[until].id.clone(),
+        created_at: now_iso(),
+    });
+    true
+}
+
+/// 把归档转回活动对话。完整历史超出上下文上限时改为分叉：新对话只带最近几轮，
+/// 并记录来源归档以便发送时注入其摘要；返回分叉实际保留的轮数。
+fn conversation_from_archive(
+    archive: &ConversationArchive,
+    api_config_id: &str,
+    context_window_tokens: u32,
+    force_usage_ratio: f64,
+    recent_turns: usize,
+) -> (Conversation, Option<usize>) {
+    let now = now_iso();
+    let mut conversation = archive.source_conversation.clone();
+    conversation.api_config_id = api_config_id.to_string();
+    conversation.status = "active".to_string();
+    conversation.updated_at = now.clone();
+    // 以恢复时间作为最近活动时间，避免下一条消息因闲置超时立即被再次归档。
+    conversation.last_user_at = Some(now.clone());
+    let usage_ratio = compute_context_usage_ratio(&conversation, context_window_tokens);
+    if usage_ratio < force_usage_ratio {
+        conversation.last_context_usage_ratio = usage_ratio;
+        return (conversation, None);
+    }
+
+    let source_messages = std::mem::take(&mut conversation.messages);
+    let mut turns = recent_turns.max(1);
+    loop {
+        conversation.messages = keep_recent_turns(&source_messages, turns);
+        conversation.last_context_usage_ratio =
+            compute_context_usage_ratio(&conversation, context_window_tokens);
+        if turns <= 1 || conversation.last_context_usage_ratio < force_usage_ratio {
+            break;
+        }
+        turns -= 1;
+    }
+    prune_orphan_branches(&mut conversation);
+    drop_stale_compaction(&mut conversation);
+    conversation.id = Uuid::new_v4().to_string();
+    conversation.created_at = now;
+    conversation.last_assistant_at = conversation
+        .messages
+        .iter()
+        .rfind(|m| m.role == "assistant")
+        .map(|m| m.created_at.clone());
+    conversation.forked_from_archive_id = Some(archive.archive_id.clone());
+    (conversation, Some(turns))
+}
+
+/// 发送时注入的上次对话回顾：分叉对话取来源归档的摘要，否则取该智能体最近一次归档摘要。
+fn archive_recap_summary(data: &AppData, conversation: &Conversation) -> Option<String> {
+    let has_summary = |a: &&ConversationArchive| !a.summary.trim().is_empty();
+    conversation
+        .forked_from_archive_id
+        .as_deref()
+        .and_then(|archive_id| {
+            data.archived_conversations
+                .iter()
+                .filter(has_summary)
+                .find(|a| a.archive_id == archive_id)
+        })
+        .or_else(|| {
+            data.archived_conversations
+                .iter()
+                .filter(has_summary)
+                .rfind(|a| a.source_conversation.agent_id == conversation.agent_id)
+        })
+        .map(|a| a.summary.clone())
+}
+
+const CONVERSATION_TITLE_MAX_CHARS: usize = 40;
+const CONVERSATION_TITLE_SOURCE_MAX_CHARS: usize = 1200;
+
+/// 自动标题只看首轮问答；还没有完整一轮时返回 None。
+fn conversation_title_prompt(conversation: &Conversation, ui_language: &str) -> Option<PreparedPrompt> {
+    let user_pos = conversation.messages.iter().position(|m| m.role == "user")?;
+    let assistant = conversation.messages[user_pos + 1..]
+        .iter()
+        .find(|m| m.role == "assistant")?;
+    let clip = |message: &ChatMessage| {
+        render_message_content_for_model(message)
+            .chars()
+            .take(CONVERSATION_TITLE_SOURCE_MAX_CHARS)
+            .collect::<String>()
+    };
+    let language = match ui_language.trim() {
+        "en-US" => "English, at most 8 words",
+        "ja-JP" => "日本語、20文字以内",
+        "ko-KR" => "한국어, 20자 이내",
+        _ => "简体中文，不超过 16 个字",
+    };
+    Some(PreparedPrompt {
+        preamble: format!(
+            "[TITLE TASK]\n为下面这段对话起一个简短的标题，概括用户的主要意图。\n\
+             要求：使用{language}；只输出标题本身，不要引号、标点结尾或任何解释。"
+        ),
+        history_messages: Vec::new(),
+        latest_user_text: format!(
+            "[USER]\n{}\n\n[ASSISTANT]\n{}",
+            clip(&conversation.messages[user_pos]).trim(),
+            clip(assistant).trim()
+        ),
+        latest_user_time_text: String::new(),
+        latest_user_system_text: String::new(),
+        latest_images: Vec::new(),
+        latest_audios: Vec::new(),
+    })
+}
+
+/// 模型返回的标题去掉思考内容、引号与多余行，过长时截断。
+fn clean_generated_title(raw: &str) -> Option<String> {
+    let visible = match raw.rfind("</think>") {
+        Some(pos) => &raw[pos + "</think>".len()..],
+        None => raw,
+    };
+    let line = visible.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let is_wrapper = |c: char| "\"'“”‘’「」『』《》*`".contains(c);
+    let line = line
+        .trim_start_matches(['#', '*', ' '])
+        .trim_start_matches("标题：")
+        .trim_start_matches("Title:")
+        .trim_start_matches(is_wrapper)
+        .trim_end_matches(|c: char| is_wrapper(c) || "。.!！".contains(c))
+        .trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.chars().take(CONVERSATION_TITLE_MAX_CHARS).collect())
+}
+
 fn compress_image_to_webp(bytes: &[u8]) -> Result<Vec<u8>, String> {
     let image =
         image::load_from_memory(bytes).map_err(|err| format!("Decode image failed: {err}"))?;
@@ -219,12 +799,33 @@ fn is_supported_image_upload_mime(mime: &str) -> bool {
     )
 }
 
+fn extract_pdf_text_locally(raw: &[u8]) -> Result<String, String> {
+    // pdf-extract 在遇到异常结构时可能 panic，这里兜底成普通错误。
+    let extracted = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(raw))
+        .map_err(|_| "Extract PDF text failed: parser panicked.".to_string())?
+        .map_err(|err| format!("Extract PDF text failed: {err}"))?;
+    let text = extracted
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if text.trim().is_empty() {
+        return Err(
+            "PDF has no extractable text (scanned document?). Use a request format with native PDF support."
+                .to_string(),
+        );
+    }
+    Ok(truncate_by_chars(&text, MAX_PDF_EXTRACTED_CHARS))
+}
+
 fn build_user_parts(
     payload: &ChatInputPayload,
     api_config: &ApiConfig,
 ) -> Result<Vec<MessagePart>, String> {
     let mut parts = Vec::<MessagePart>::new();
     let mut total_binary = 0usize;
+    let mut pdf_count = 0usize;
 
     if let Some(text) = payload
         .text
@@ -257,16 +858,20 @@ fn build_user_parts(
                 .decode(image.bytes_base64.trim())
                 .map_err(|err| format!("Decode image base64 failed: {err}"))?;
             if mime == "application/pdf" {
-                if !api_config.request_format.is_gemini() {
-                    return Err(
-                        "PDF attachment is only supported when request format is 'gemini'."
-                            .to_string(),
-                    );
+                if !api_config.request_format.supports_document_input() {
+                    // 不支持文档输入的格式（如 deepseek/kimi）回退为本地提取的 PDF 文本。
+                    pdf_count += 1;
+                    let text = extract_pdf_text_locally(&raw)?;
+                    parts.push(MessagePart::Text {
+                        text: format!("[PDF{}]\n{}", pdf_count, text),
+                    });
+                    continue;
                 }
                 total_binary += raw.len();
                 parts.push(MessagePart::Image {
                     mime: "application/pdf".to_string(),
                     bytes_base64: B64.encode(raw),
+                    blob_hash: None,
                     name: None,
                     compressed: false,
                 });
@@ -276,6 +881,7 @@ fn build_user_parts(
                 parts.push(MessagePart::Image {
                     mime: "image/webp".to_string(),
                     bytes_base64: B64.encode(webp),
+                    blob_hash: None,
                     name: None,
                     compressed: true,
                 });
@@ -296,6 +902,7 @@ fn build_user_parts(
             parts.push(MessagePart::Audio {
                 mime: audio.mime.trim().to_string(),
                 bytes_base64: B64.encode(raw),
+                blob_hash: None,
                 name: None,
                 compressed: false,
             });
@@ -382,12 +989,14 @@ fn build_prompt(
     agent: &AgentProfile,
     user_name: &str,
     user_intro: &str,
-    response_style_id: &str,
-    ui_language: &str,
+    response_style: &ResponseStylePreset,
+    app_config: &AppConfig,
 ) -> PreparedPrompt {
+    let time_zone = configured_time_zone(app_config);
     let latest_user_index = conversation.messages.iter().rposition(|m| m.role == "user");
+    let history_start = compacted_start(conversation);
     let mut history_messages = Vec::<PreparedHistoryMessage>::new();
-    for (idx, message) in conversation.messages.iter().enumerate() {
+    for (idx, message) in conversation.messages.iter().enumerate().skip(history_start) {
         if Some(idx) == latest_user_index {
             continue;
         }
@@ -456,7 +1065,7 @@ fn build_prompt(
             role: role.clone(),
             text,
             user_time_text: if role == "user" {
-                let t = format_message_time_text(&message.created_at);
+                let t = format_message_time_text(&message.created_at, &time_zone);
                 if t.is_empty() { None } else { Some(t) }
             } else {
                 None
@@ -471,16 +1080,16 @@ fn build_prompt(
     } else {
         user_intro.trim().to_string()
     };
-    let response_style = response_style_preset(response_style_id);
-    let highest_instruction_md = highest_instruction_markdown();
-    let language_instruction = match ui_language.trim() {
+    let highest_instruction_md =
+        highest_instruction_markdown(effective_highest_instruction(app_config, Some(agent)).as_ref());
+    let language_instruction = match app_config.ui_language.trim() {
         "en-US" => "Please respond in English by default.",
         "ja-JP" => "通常は日本語で回答してください。",
         "ko-KR" => "기본적으로 한국어로 답변해 주세요.",
         _ => "默认使用中文回答。",
     };
 
-    let preamble = format!(
+    let mut preamble = format!(
         "{}\n\
 ## 助理设定\n\
 {}\n\
@@ -500,17 +1109,31 @@ fn build_prompt(
 ## 语言设定\n\
 - {}\n\
 - 若用户明确指定回答语言，以用户指定为准。\n\
+\n\
+## 时间\n\
+- 用户时区：{}，对话中标注的时间均为用户本地时间。\n\
+- 最新一条用户消息附带的时间即为当前时间。\n\
 \n",
         highest_instruction_md,
-        agent.system_prompt,
+        render_agent_system_prompt(&agent.system_prompt, user_name, &time_zone),
         xml_escape_prompt(user_name),
         xml_escape_prompt(&user_intro_display),
         agent.name,
         user_name,
         response_style.name,
         response_style.prompt,
-        language_instruction
+        language_instruction,
+        time_zone.label()
     );
+    if let Some(compaction) = conversation
+        .compaction
+        .as_ref()
+        .filter(|c| history_start > 0 && !c.summary.trim().is_empty())
+    {
+        preamble.push_str("\n[EARLIER CONVERSATION SUMMARY]\n");
+        preamble.push_str(compaction.summary.trim());
+        preamble.push('\n');
+    }
 
     let latest_user = conversation
         .messages
@@ -532,7 +1155,7 @@ fn build_prompt(
             extra_text_blocks,
             ..
         } = msg;
-        latest_user_time_text = format_message_time_text(&created_at);
+        latest_user_time_text = format_message_time_text(&created_at, &time_zone);
         for part in parts {
             match part {
                 MessagePart::Text { text } => {
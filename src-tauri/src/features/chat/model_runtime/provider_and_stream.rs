@@ -145,7 +145,7 @@ async fn openai_stream_request(
     url: &str,
     body: Value,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
-) -> Result<(String, String, String, Vec<OpenAIToolCall>), String> {
+) -> Result<(String, String, String, Vec<OpenAIToolCall>), ApiCallError> {
     openai_stream_request_with_sink(client, url, body, |kind, delta| {
         let _ = on_delta.send(AssistantDeltaEvent {
             delta: delta.to_string(),
@@ -167,7 +167,7 @@ async fn openai_stream_request_with_sink<F>(
     url: &str,
     body: Value,
     mut on_event: F,
-) -> Result<(String, String, String, Vec<OpenAIToolCall>), String>
+) -> Result<(String, String, String, Vec<OpenAIToolCall>), ApiCallError>
 where
     F: FnMut(&str, &str),
 {
@@ -180,9 +180,12 @@ where
     if !resp.status().is_success() {
         let status = resp.status();
         let raw = resp.text().await.unwrap_or_default();
-        return Err(format!(
-            "OpenAI stream failed with status {status}: {}",
-            raw.chars().take(300).collect::<String>()
+        return Err(ApiCallError::new(
+            Some(status.as_u16()),
+            format!(
+                "OpenAI stream failed with status {status}: {}",
+                raw.chars().take(300).collect::<String>()
+            ),
         ));
     }
 
@@ -292,17 +295,14 @@ where
     ))
 }
 
-async fn call_model_openai_stream_text(
-    api_config: &ResolvedApiConfig,
-    model_name: &str,
-    prepared: &PreparedPrompt,
-    on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
-) -> Result<ModelReply, String> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(120))
-        .default_headers(openai_headers(&api_config.api_key)?)
-        .build()
-        .map_err(|err| format!("Build HTTP client failed: {err}"))?;
+fn prepared_has_pdf(prepared: &PreparedPrompt) -> bool {
+    prepared
+        .latest_images
+        .iter()
+        .any(|(mime, _)| mime.trim().eq_ignore_ascii_case("application/pdf"))
+}
+
+fn openai_latest_user_content(prepared: &PreparedPrompt) -> Vec<Value> {
     let mut user_content = vec![serde_json::json!({
       "type": "text",
       "text": prepared.latest_user_text
@@ -319,6 +319,44 @@ async fn call_model_openai_stream_text(
           "text": prepared.latest_user_system_text
         }));
     }
+    let mut pdf_index = 0usize;
+    for (mime, bytes_base64) in &prepared.latest_images {
+        let mime = mime.trim().to_ascii_lowercase();
+        if mime == "application/pdf" {
+            // Chat Completions 的 file 内容块，文件以 data URL 内联。
+            pdf_index += 1;
+            user_content.push(serde_json::json!({
+              "type": "file",
+              "file": {
+                "filename": format!("attachment-{pdf_index}.pdf"),
+                "file_data": format!("data:application/pdf;base64,{bytes_base64}")
+              }
+            }));
+        } else {
+            user_content.push(serde_json::json!({
+              "type": "image_url",
+              "image_url": {
+                "url": format!("data:{mime};base64,{bytes_base64}"),
+                "detail": "auto"
+              }
+            }));
+        }
+    }
+    user_content
+}
+
+async fn call_model_openai_stream_text(
+    api_config: &ResolvedApiConfig,
+    model_name: &str,
+    prepared: &PreparedPrompt,
+    on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
+) -> Result<ModelReply, ApiCallError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(120))
+        .default_headers(openai_headers(&api_config.api_key)?)
+        .build()
+        .map_err(|err| format!("Build HTTP client failed: {err}"))?;
+    let user_content = openai_latest_user_content(prepared);
     let mut messages = Vec::<Value>::new();
     messages.push(serde_json::json!({
       "role": "system",
@@ -387,10 +425,11 @@ async fn call_model_openai_stream_text(
 
     let urls = candidate_openai_chat_urls(&api_config.base_url);
     if urls.is_empty() {
-        return Err("Base URL is empty.".to_string());
+        return Err("Base URL is empty.".to_string().into());
     }
 
     let mut errors = Vec::new();
+    let mut status = None;
     for url in urls {
         match openai_stream_request(&client, &url, body.clone(), on_delta).await {
             Ok((text, reasoning_standard, reasoning_inline, _)) => {
@@ -401,13 +440,19 @@ async fn call_model_openai_stream_text(
                     tool_history_events: Vec::new(),
                 });
             }
-            Err(err) => errors.push(format!("{url} -> {err}")),
+            Err(err) => {
+                status = err.key_failure_status().or(status).or(err.status);
+                errors.push(format!("{url} -> {err}"));
+            }
         }
     }
 
-    Err(format!(
-        "OpenAI stream request failed for all candidate URLs: {}",
-        errors.join(" || ")
+    Err(ApiCallError::new(
+        status,
+        format!(
+            "OpenAI stream request failed for all candidate URLs: {}",
+            errors.join(" || ")
+        ),
     ))
 }
 
@@ -823,6 +868,8 @@ async fn try_attach_desktop_screenshot_mcp_tool(
         .map_err(|err| format!("Resolve current executable for MCP screenshot failed: {err}"))?;
     let mut cmd = tokio::process::Command::new(exe);
     cmd.arg(MCP_SCREENSHOT_SERVER_FLAG);
+    // 子进程收不到 --data-dir 等启动参数，直接告诉它已解析出的数据目录。
+    cmd.env(DATA_DIR_ENV, &app_data_dir()?.dir);
     let transport = rmcp::transport::TokioChildProcess::new(cmd)
         .map_err(|err| format!("Start MCP screenshot child process failed: {err}"))?;
 
@@ -853,6 +900,8 @@ async fn try_attach_desktop_screenshot_mcp_tool(
     Ok(client)
 }
 
+/// 手写的 Chat Completions 工具循环：DeepSeek/Kimi 需要回传 reasoning_content，
+/// OpenAI 带 PDF 附件时也走这里以保留原生 file 内容块。
 async fn call_model_deepseek_with_tools_http(
     api_config: &ResolvedApiConfig,
     selected_api: &ApiConfig,
@@ -861,7 +910,7 @@ async fn call_model_deepseek_with_tools_http(
     app_state: Option<&AppState>,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
     max_tool_iterations: usize,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let tools = deepseek_tool_schemas(selected_api);
     if tools.is_empty() {
         return call_model_openai_stream_text(api_config, model_name, &prepared, on_delta).await;
@@ -874,7 +923,7 @@ async fn call_model_deepseek_with_tools_http(
         .map_err(|err| format!("Build HTTP client failed: {err}"))?;
     let urls = candidate_openai_chat_urls(&api_config.base_url);
     if urls.is_empty() {
-        return Err("Base URL is empty.".to_string());
+        return Err("Base URL is empty.".to_string().into());
     }
 
     let mut full_assistant_text = String::new();
@@ -882,22 +931,8 @@ async fn call_model_deepseek_with_tools_http(
     let mut tool_history_events = Vec::<Value>::new();
     let mut full_reasoning_inline = String::new();
 
-    let mut first_user_content = vec![serde_json::json!({
-      "type": "text",
-      "text": prepared.latest_user_text
-    })];
-    if !prepared.latest_user_time_text.trim().is_empty() {
-        first_user_content.push(serde_json::json!({
-          "type": "text",
-          "text": prepared.latest_user_time_text
-        }));
-    }
-    if !prepared.latest_user_system_text.trim().is_empty() {
-        first_user_content.push(serde_json::json!({
-          "type": "text",
-          "text": prepared.latest_user_system_text
-        }));
-    }
+    // 最新一条用户消息带上原生附件（PDF 的 file 内容块等）。
+    let first_user_content = openai_latest_user_content(&prepared);
     let mut messages = Vec::<Value>::new();
     messages.push(serde_json::json!({ "role": "system", "content": prepared.preamble }));
     for hm in &prepared.history_messages {
@@ -963,6 +998,7 @@ async fn call_model_deepseek_with_tools_http(
         });
 
         let mut errors = Vec::new();
+        let mut status = None;
         let mut turn_result: Option<(String, String, String, Vec<OpenAIToolCall>)> = None;
         for url in &urls {
             match openai_stream_request(&client, url, body.clone(), on_delta).await {
@@ -970,13 +1006,19 @@ async fn call_model_deepseek_with_tools_http(
                     turn_result = Some(v);
                     break;
                 }
-                Err(err) => errors.push(format!("{url} -> {err}")),
+                Err(err) => {
+                    status = err.key_failure_status().or(status).or(err.status);
+                    errors.push(format!("{url} -> {err}"));
+                }
             }
         }
         let (turn_text, reasoning_standard, reasoning_inline, tool_calls) = turn_result.ok_or_else(|| {
-            format!(
-                "DeepSeek stream request failed for all candidate URLs: {}",
-                errors.join(" || ")
+            ApiCallError::new(
+                status,
+                format!(
+                    "Tool-calling stream request failed for all candidate URLs: {}",
+                    errors.join(" || ")
+                ),
             )
         })?;
 
@@ -1128,7 +1170,7 @@ async fn call_model_openai_with_tools(
     app_state: Option<&AppState>,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
     max_tool_iterations: usize,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     if selected_api.request_format.is_deepseek_kimi() {
         return call_model_deepseek_with_tools_http(
             api_config,
@@ -1240,10 +1282,12 @@ async fn call_model_openai_with_tools(
         let mut stream = agent
             .stream_completion(current_prompt.clone(), chat_history.clone())
             .await
-            .map_err(|err| format!("rig stream completion build failed: {err}"))?
+            .map_err(|err| {
+                ApiCallError::from_rig_completion(&err, "rig stream completion build failed")
+            })?
             .stream()
             .await
-            .map_err(|err| format!("rig stream start failed: {err}"))?;
+            .map_err(|err| ApiCallError::from_rig_completion(&err, "rig stream start failed"))?;
 
         chat_history.push(current_prompt.clone());
 
@@ -1374,7 +1418,9 @@ async fn call_model_openai_with_tools(
                     }
                 }
                 Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
-                Err(err) => return Err(format!("rig streaming failed: {err}")),
+                Err(err) => {
+                    return Err(ApiCallError::from_rig_completion(&err, "rig streaming failed"));
+                }
             }
         }
 
@@ -1474,10 +1520,10 @@ async fn call_model_openai_with_tools(
     let mut final_stream = final_agent
         .stream_completion(current_prompt.clone(), chat_history.clone())
         .await
-        .map_err(|err| format!("rig final stream build failed: {err}"))?
+        .map_err(|err| ApiCallError::from_rig_completion(&err, "rig final stream build failed"))?
         .stream()
         .await
-        .map_err(|err| format!("rig final stream start failed: {err}"))?;
+        .map_err(|err| ApiCallError::from_rig_completion(&err, "rig final stream start failed"))?;
     let mut final_text = String::new();
     while let Some(chunk) = final_stream.next().await {
         match chunk {
@@ -1517,7 +1563,9 @@ async fn call_model_openai_with_tools(
             }
             Ok(StreamedAssistantContent::ToolCall { .. }) => {}
             Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
-            Err(err) => return Err(format!("rig final streaming failed: {err}")),
+            Err(err) => {
+                return Err(ApiCallError::from_rig_completion(&err, "rig final streaming failed"));
+            }
         }
     }
     if !final_text.trim().is_empty() {
@@ -1542,7 +1590,7 @@ async fn call_model_gemini_with_tools(
     app_state: Option<&AppState>,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
     max_tool_iterations: usize,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let has_fetch = tool_enabled(selected_api, "fetch");
     let has_bing = tool_enabled(selected_api, "bing-search");
     let has_memory = tool_enabled(selected_api, "memory-save");
@@ -1648,10 +1696,12 @@ async fn call_model_gemini_with_tools(
         let mut stream = agent
             .stream_completion(current_prompt.clone(), chat_history.clone())
             .await
-            .map_err(|err| format!("rig stream completion build failed: {err}"))?
+            .map_err(|err| {
+                ApiCallError::from_rig_completion(&err, "rig stream completion build failed")
+            })?
             .stream()
             .await
-            .map_err(|err| format!("rig stream start failed: {err}"))?;
+            .map_err(|err| ApiCallError::from_rig_completion(&err, "rig stream start failed"))?;
 
         chat_history.push(current_prompt.clone());
 
@@ -1762,7 +1812,9 @@ async fn call_model_gemini_with_tools(
                     }
                 }
                 Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
-                Err(err) => return Err(format!("rig streaming failed: {err}")),
+                Err(err) => {
+                    return Err(ApiCallError::from_rig_completion(&err, "rig streaming failed"));
+                }
             }
         }
 
@@ -1861,7 +1913,7 @@ async fn call_model_anthropic_with_tools(
     app_state: Option<&AppState>,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
     max_tool_iterations: usize,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let has_fetch = tool_enabled(selected_api, "fetch");
     let has_bing = tool_enabled(selected_api, "bing-search");
     let has_memory = tool_enabled(selected_api, "memory-save");
@@ -1958,10 +2010,12 @@ async fn call_model_anthropic_with_tools(
         let mut stream = agent
             .stream_completion(current_prompt.clone(), chat_history.clone())
             .await
-            .map_err(|err| format!("rig stream completion build failed: {err}"))?
+            .map_err(|err| {
+                ApiCallError::from_rig_completion(&err, "rig stream completion build failed")
+            })?
             .stream()
             .await
-            .map_err(|err| format!("rig stream start failed: {err}"))?;
+            .map_err(|err| ApiCallError::from_rig_completion(&err, "rig stream start failed"))?;
 
         chat_history.push(current_prompt.clone());
 
@@ -2072,7 +2126,9 @@ async fn call_model_anthropic_with_tools(
                     }
                 }
                 Ok(StreamedAssistantContent::ToolCallDelta { .. }) => {}
-                Err(err) => return Err(format!("rig streaming failed: {err}")),
+                Err(err) => {
+                    return Err(ApiCallError::from_rig_completion(&err, "rig streaming failed"));
+                }
             }
         }
 
@@ -2171,7 +2227,7 @@ async fn call_model_openai_style(
     app_state: Option<&AppState>,
     on_delta: &tauri::ipc::Channel<AssistantDeltaEvent>,
     max_tool_iterations: usize,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     if selected_api.request_format.is_gemini() {
         if selected_api.enable_tools
             && prepared.latest_images.is_empty()
@@ -2209,6 +2265,24 @@ async fn call_model_openai_style(
         return call_model_anthropic_rig_style(api_config, model_name, prepared).await;
     }
 
+    // rig 的 completions 接口会把文档降级为纯文本，PDF 改走原生 file 内容块；
+    // 启用工具时用手写的 HTTP 工具循环，保证带 PDF 的轮次仍可调用工具。
+    if matches!(selected_api.request_format, RequestFormat::OpenAI) && prepared_has_pdf(&prepared) {
+        if selected_api.enable_tools && prepared.latest_audios.is_empty() {
+            return call_model_deepseek_with_tools_http(
+                api_config,
+                selected_api,
+                model_name,
+                prepared,
+                app_state,
+                on_delta,
+                max_tool_iterations,
+            )
+            .await;
+        }
+        return call_model_openai_stream_text(api_config, model_name, &prepared, on_delta).await;
+    }
+
     // 优先使用工具调用（如果启用）
     if selected_api.enable_tools
         && is_openai_style_request_format(selected_api.request_format)
@@ -2241,7 +2315,7 @@ async fn call_model_openai_style(
     match rig_result {
         Ok(reply) => Ok(reply),
         Err(err)
-            if !original.latest_images.is_empty() && is_image_unsupported_error(&err) =>
+            if !original.latest_images.is_empty() && is_image_unsupported_error(&err.message) =>
         {
             eprintln!(
                 "[CHAT] Model rejected image input, fallback to text-only request. error={}",
@@ -2260,7 +2334,7 @@ async fn describe_image_with_vision_api(
     vision_resolved: &ResolvedApiConfig,
     vision_api: &ApiConfig,
     image: &BinaryPart,
-) -> Result<String, String> {
+) -> Result<String, ApiCallError> {
     let mime = image.mime.trim();
     let prepared = PreparedPrompt {
         preamble: "[SYSTEM PROMPT]\n你是图像理解助手。请读取图片中的关键信息并输出简洁中文描述，保留有价值的文本、数字、UI元素与上下文。".to_string(),
@@ -2290,7 +2364,7 @@ async fn describe_image_with_vision_api(
             call_model_anthropic_rig_style(vision_resolved, &vision_api.model, prepared).await?
         }
         RequestFormat::OpenAITts => {
-            return Err("Vision request format 'openai_tts' is not supported.".to_string())
+            return Err("Vision request format 'openai_tts' is not supported.".to_string().into())
         }
     };
     Ok(reply.assistant_text)
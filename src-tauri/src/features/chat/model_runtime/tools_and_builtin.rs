@@ -10,7 +10,7 @@ async fn call_model_openai_rig_style(
     api_config: &ResolvedApiConfig,
     model_name: &str,
     prepared: PreparedPrompt,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let mut content_items: Vec<UserContent> = Vec::new();
     if !prepared.latest_user_text.trim().is_empty() {
         content_items.push(UserContent::text(prepared.latest_user_text));
@@ -59,7 +59,7 @@ async fn call_model_openai_rig_style(
     let assistant_text = agent
         .prompt(prompt_message)
         .await
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| ApiCallError::from_rig_prompt(&err))?;
     Ok(ModelReply {
         assistant_text,
         reasoning_standard: String::new(),
@@ -83,7 +83,7 @@ async fn call_model_gemini_rig_style(
     api_config: &ResolvedApiConfig,
     model_name: &str,
     prepared: PreparedPrompt,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let mut client_builder = gemini::Client::builder().api_key(&api_config.api_key);
     let normalized_base = normalize_gemini_rig_base_url(&api_config.base_url);
     if !normalized_base.is_empty() {
@@ -152,7 +152,7 @@ async fn call_model_gemini_rig_style(
             content: prompt_content,
         })
         .await
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| ApiCallError::from_rig_prompt(&err))?;
     Ok(ModelReply {
         assistant_text,
         reasoning_standard: String::new(),
@@ -165,7 +165,7 @@ async fn call_model_anthropic_rig_style(
     api_config: &ResolvedApiConfig,
     model_name: &str,
     prepared: PreparedPrompt,
-) -> Result<ModelReply, String> {
+) -> Result<ModelReply, ApiCallError> {
     let mut content_items: Vec<UserContent> = Vec::new();
     if !prepared.latest_user_text.trim().is_empty() {
         content_items.push(UserContent::text(prepared.latest_user_text));
@@ -178,11 +178,15 @@ async fn call_model_anthropic_rig_style(
     }
 
     for (mime, bytes) in prepared.latest_images {
-        content_items.push(UserContent::image_base64(
-            bytes,
-            image_media_type_from_mime(&mime),
-            Some(ImageDetail::Auto),
-        ));
+        if mime.trim().eq_ignore_ascii_case("application/pdf") {
+            content_items.push(UserContent::document(bytes, Some(DocumentMediaType::PDF)));
+        } else {
+            content_items.push(UserContent::image_base64(
+                bytes,
+                image_media_type_from_mime(&mime),
+                Some(ImageDetail::Auto),
+            ));
+        }
     }
 
     for (mime, bytes) in prepared.latest_audios {
@@ -213,7 +217,7 @@ async fn call_model_anthropic_rig_style(
     let assistant_text = agent
         .prompt(prompt_message)
         .await
-        .map_err(|err| err.to_string())?;
+        .map_err(|err| ApiCallError::from_rig_prompt(&err))?;
     Ok(ModelReply {
         assistant_text,
         reasoning_standard: String::new(),
@@ -0,0 +1,139 @@
+// 提示词模板：智能体设定中的 {{变量}} 与 {{#if 条件}}...{{else}}...{{/if}} 在发送时展开。
+
+const PROMPT_TEMPLATE_CLIPBOARD_MAX_CHARS: usize = 2000;
+
+type PromptTemplateVars = std::collections::HashMap<&'static str, String>;
+
+/// 条件写法：`name`（非空为真）、`!name`、`name == value`、`name != value`（忽略大小写）。
+fn prompt_template_condition(expr: &str, vars: &PromptTemplateVars) -> bool {
+    for (op, negate) in [("!=", true), ("==", false)] {
+        if let Some((left, right)) = expr.split_once(op) {
+            let left = vars.get(left.trim()).map(|v| v.trim()).unwrap_or_default();
+            let right = right.trim().trim_matches(|c| c == '"' || c == '\'');
+            return left.eq_ignore_ascii_case(right) != negate;
+        }
+    }
+    let (negate, name) = match expr.trim().strip_prefix('!') {
+        Some(name) => (true, name.trim()),
+        None => (false, expr.trim()),
+    };
+    vars.get(name).is_some_and(|v| !v.trim().is_empty()) != negate
+}
+
+/// 未知变量原样保留；条件块可以嵌套，缺少 {{/if}} 时一直生效到结尾。
+fn render_prompt_template(template: &str, vars: &PromptTemplateVars) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    let mut out = String::with_capacity(template.len());
+    // (外层是否输出, 本层条件)
+    let mut stack = Vec::<(bool, bool)>::new();
+    let mut emitting = true;
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        if emitting {
+            out.push_str(&rest[..start]);
+        }
+        let whole = &rest[start..start + len + 4];
+        let tag = rest[start + 2..start + 2 + len].trim();
+        rest = &rest[start + len + 4..];
+        if let Some(expr) = tag.strip_prefix("#if ") {
+            let truthy = prompt_template_condition(expr, vars);
+            stack.push((emitting, truthy));
+            emitting = emitting && truthy;
+        } else if tag == "else" && !stack.is_empty() {
+            let (parent, truthy) = stack[stack.len() - 1];
+            emitting = parent && !truthy;
+        } else if tag == "/if" && !stack.is_empty() {
+            let (parent, _) = stack.pop().unwrap_or((true, true));
+            emitting = parent;
+        } else if emitting {
+            match vars.get(tag) {
+                Some(value) => out.push_str(value),
+                None => out.push_str(whole),
+            }
+        }
+    }
+    if emitting {
+        out.push_str(rest);
+    }
+    out
+}
+
+fn prompt_template_os_name() -> String {
+    match std::env::consts::OS {
+        "windows" => "Windows",
+        "macos" => "macOS",
+        "linux" => "Linux",
+        other => other,
+    }
+    .to_string()
+}
+
+fn prompt_template_clipboard_text() -> String {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .unwrap_or_default();
+    text.trim()
+        .chars()
+        .take(PROMPT_TEMPLATE_CLIPBOARD_MAX_CHARS)
+        .collect()
+}
+
+/// 当前前台窗口标题，跳过本应用自己的窗口。
+fn prompt_template_active_window_title() -> String {
+    let own_pid = std::process::id();
+    let Ok(windows) = xcap::Window::all() else {
+        return String::new();
+    };
+    let candidates = windows.iter().filter(|w| {
+        w.pid().map(|pid| pid != own_pid).unwrap_or(false)
+            && !w.is_minimized().unwrap_or(true)
+            && w.title().map(|t| !t.trim().is_empty()).unwrap_or(false)
+    });
+    let mut first = None;
+    for window in candidates {
+        if window.is_focused().unwrap_or(false) {
+            return window.title().unwrap_or_default();
+        }
+        first.get_or_insert(window);
+    }
+    first.and_then(|w| w.title().ok()).unwrap_or_default()
+}
+
+/// 剪贴板与前台窗口只在模板用到时读取。
+fn collect_prompt_template_vars(
+    template: &str,
+    user_name: &str,
+    time_zone: &UserTimeZone,
+) -> PromptTemplateVars {
+    let now = now_utc().to_offset(time_zone.offset);
+    let mut vars = PromptTemplateVars::new();
+    vars.insert(
+        "date",
+        format!("{:04}-{:02}-{:02}", now.year(), now.month() as u8, now.day()),
+    );
+    vars.insert("time", format!("{:02}:{:02}", now.hour(), now.minute()));
+    vars.insert("weekday", now.weekday().to_string());
+    vars.insert("timezone", time_zone.label());
+    vars.insert("user_name", user_name.trim().to_string());
+    vars.insert("os", prompt_template_os_name());
+    if template.contains("clipboard") {
+        vars.insert("clipboard", prompt_template_clipboard_text());
+    }
+    if template.contains("active_window_title") {
+        vars.insert("active_window_title", prompt_template_active_window_title());
+    }
+    vars
+}
+
+fn render_agent_system_prompt(prompt: &str, user_name: &str, time_zone: &UserTimeZone) -> String {
+    if !prompt.contains("{{") {
+        return prompt.to_string();
+    }
+    let vars = collect_prompt_template_vars(prompt, user_name, time_zone);
+    render_prompt_template(prompt, &vars)
+}
@@ -35,8 +35,8 @@
             &agent,
             "用户",
             "我是...",
-            DEFAULT_RESPONSE_STYLE_ID,
-            "zh-CN",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
         );
 
         assert!(
@@ -67,6 +67,8 @@
             tools: default_api_tools(),
             base_url: "https://example.com/v1".to_string(),
             api_key: "k".to_string(),
+            extra_api_keys: Vec::new(),
+            key_rotation: ApiKeyRotation::RoundRobin,
             model: "gpt-x".to_string(),
             temperature: 0.7,
             context_window_tokens: 128_000,
@@ -131,7 +133,7 @@
             vec![test_text_message("user", &huge, &now)],
             Some(now),
         );
-        let d = decide_archive_before_user_message(&conv, 1000);
+        let d = decide_archive_before_user_message(&conv, 1000, &ArchivePolicy::default(), now_utc());
         assert!(d.should_archive);
         assert!(d.forced);
         assert!(d.usage_ratio >= 0.82);
@@ -148,7 +150,7 @@
             vec![test_text_message("user", &text, &old)],
             Some(old),
         );
-        let d = decide_archive_before_user_message(&conv, 1000);
+        let d = decide_archive_before_user_message(&conv, 1000, &ArchivePolicy::default(), now_utc());
         assert!(d.should_archive);
         assert!(!d.forced);
         assert!(d.usage_ratio >= 0.30);
@@ -164,9 +166,703 @@
             vec![test_text_message("user", "hello", &old)],
             Some(old),
         );
-        let d = decide_archive_before_user_message(&conv, 1000);
+        let d = decide_archive_before_user_message(&conv, 1000, &ArchivePolicy::default(), now_utc());
         assert!(!d.should_archive);
         assert!(!d.forced);
         assert!(d.usage_ratio < 0.30);
     }
 
+    #[test]
+    fn archive_decision_should_follow_daily_and_never_policies() {
+        let offset = UtcOffset::from_hms(8, 0, 0).expect("offset");
+        let at = |raw: &str| {
+            OffsetDateTime::parse(raw, &Rfc3339)
+                .expect("parse time")
+                .to_offset(offset)
+        };
+        let last_user = "2026-03-01T19:30:00Z".to_string(); // 当地 03-02 03:30
+        let conv = test_active_conversation_with_messages(
+            vec![test_text_message("user", "hello", &last_user)],
+            Some(last_user),
+        );
+        let daily = ArchivePolicy {
+            mode: ArchiveMode::Daily,
+            daily_time: "04:00".to_string(),
+            ..ArchivePolicy::default()
+        };
+
+        // 当地 03:50，还没到换日时间。
+        let d = decide_archive_before_user_message(&conv, 1000, &daily, at("2026-03-01T19:50:00Z"));
+        assert!(!d.should_archive);
+        // 当地 04:10，上一条消息早于换日时间。
+        let d = decide_archive_before_user_message(&conv, 1000, &daily, at("2026-03-01T20:10:00Z"));
+        assert!(d.should_archive);
+        assert!(!d.forced);
+        assert_eq!(d.reason, "daily_rollover");
+
+        let huge = "中".repeat(2000);
+        let full = test_active_conversation_with_messages(
+            vec![test_text_message("user", &huge, &now_iso())],
+            Some(now_iso()),
+        );
+        let never = ArchivePolicy {
+            mode: ArchiveMode::Never,
+            ..ArchivePolicy::default()
+        };
+        let d = decide_archive_before_user_message(&full, 1000, &never, now_utc());
+        assert!(!d.should_archive);
+        let d = decide_archive_before_user_message(&full, 1000, &daily, now_utc());
+        assert!(d.forced);
+    }
+
+
+    const TEST_HELLO_PDF_BASE64: &str = "JVBERi0xLjQKMSAwIG9iago8PCAvVHlwZSAvQ2F0YWxvZyAvUGFnZXMgMiAwIFIgPj4KZW5kb2JqCjIgMCBvYmoKPDwgL1R5cGUgL1BhZ2VzIC9LaWRzIFszIDAgUl0gL0NvdW50IDEgPj4KZW5kb2JqCjMgMCBvYmoKPDwgL1R5cGUgL1BhZ2UgL1BhcmVudCAyIDAgUiAvTWVkaWFCb3ggWzAgMCAyMDAgNTBdIC9Db250ZW50cyA0IDAgUiAvUmVzb3VyY2VzIDw8IC9Gb250IDw8IC9GMSA1IDAgUiA+PiA+PiA+PgplbmRvYmoKNCAwIG9iago8PCAvTGVuZ3RoIDM5ID4+CnN0cmVhbQpCVCAvRjEgMTIgVGYgMTAgMjAgVGQgKEhlbGxvIFBERikgVGogRVQKZW5kc3RyZWFtCmVuZG9iago1IDAgb2JqCjw8IC9UeXBlIC9Gb250IC9TdWJ0eXBlIC9UeXBlMSAvQmFzZUZvbnQgL0hlbHZldGljYSA+PgplbmRvYmoKeHJlZgowIDYKMDAwMDAwMDAwMCA2NTUzNSBmIAowMDAwMDAwMDA5IDAwMDAwIG4gCjAwMDAwMDAwNTggMDAwMDAgbiAKMDAwMDAwMDExNSAwMDAwMCBuIAowMDAwMDAwMjQwIDAwMDAwIG4gCjAwMDAwMDAzMjkgMDAwMDAgbiAKdHJhaWxlcgo8PCAvU2l6ZSA2IC9Sb290IDEgMCBSID4+CnN0YXJ0eHJlZgozOTkKJSVFT0YK";
+
+    fn test_pdf_payload() -> ChatInputPayload {
+        ChatInputPayload {
+            text: Some("看看这个文件".to_string()),
+            images: Some(vec![BinaryPart {
+                mime: "application/pdf".to_string(),
+                bytes_base64: TEST_HELLO_PDF_BASE64.to_string(),
+            }]),
+            audios: None,
+            model: None,
+        }
+    }
+
+    #[test]
+    fn build_user_parts_should_keep_pdf_for_document_capable_formats() {
+        for request_format in [
+            RequestFormat::OpenAI,
+            RequestFormat::Gemini,
+            RequestFormat::Anthropic,
+        ] {
+            let api = ApiConfig {
+                request_format,
+                enable_image: true,
+                ..ApiConfig::default()
+            };
+            let parts = build_user_parts(&test_pdf_payload(), &api).expect("build parts");
+            assert!(parts.iter().any(|p| matches!(
+                p,
+                MessagePart::Image { mime, .. } if mime == "application/pdf"
+            )));
+        }
+    }
+
+    #[test]
+    fn build_user_parts_should_extract_pdf_text_for_deepseek() {
+        let api = ApiConfig {
+            request_format: RequestFormat::DeepSeekKimi,
+            enable_image: true,
+            ..ApiConfig::default()
+        };
+        let parts = build_user_parts(&test_pdf_payload(), &api).expect("build parts");
+        assert!(!parts.iter().any(|p| matches!(p, MessagePart::Image { .. })));
+        assert!(parts.iter().any(|p| matches!(
+            p,
+            MessagePart::Text { text } if text.starts_with("[PDF1]") && text.contains("Hello PDF")
+        )));
+    }
+
+    #[test]
+    fn openai_latest_user_content_should_send_pdf_as_file_block() {
+        let prepared = PreparedPrompt {
+            preamble: "sys".to_string(),
+            history_messages: Vec::new(),
+            latest_user_text: "看看这个文件".to_string(),
+            latest_user_time_text: String::new(),
+            latest_user_system_text: String::new(),
+            latest_images: vec![("application/pdf".to_string(), "QUJD".to_string())],
+            latest_audios: Vec::new(),
+        };
+        assert!(prepared_has_pdf(&prepared));
+        let content = openai_latest_user_content(&prepared);
+        let file = content
+            .iter()
+            .find(|c| c.get("type").and_then(Value::as_str) == Some("file"))
+            .expect("file block");
+        assert_eq!(
+            file.pointer("/file/file_data").and_then(Value::as_str),
+            Some("data:application/pdf;base64,QUJD")
+        );
+    }
+
+    #[test]
+    fn parse_archive_import_should_attach_jsonl_tool_calls_to_assistant_reply() {
+        let raw = [
+            r#"{"role":"system","content":"be brief"}"#,
+            r#"{"role":"user","content":"weather?"}"#,
+            r#"{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"weather","arguments":{"city":"Paris"}}}]}"#,
+            r#"{"role":"tool","tool_call_id":"call_1","content":"sunny"}"#,
+            r#"{"role":"assistant","content":[{"type":"text","text":"It is sunny."}]}"#,
+        ]
+        .join("\n");
+        let parsed = parse_archive_import(&raw, "agent-a", "api-a").expect("parse jsonl");
+        assert_eq!(parsed.format, "openaiJsonl");
+        assert_eq!(parsed.archives.len(), 1);
+        let conversation = &parsed.archives[0].source_conversation;
+        assert_eq!(conversation.agent_id, "agent-a");
+        assert_eq!(
+            conversation.messages.iter().map(|m| m.role.as_str()).collect::<Vec<_>>(),
+            vec!["user", "assistant"]
+        );
+        let events = conversation.messages[1].tool_call.clone().expect("tool events");
+        assert_eq!(
+            events[0].pointer("/tool_calls/0/function/arguments").and_then(Value::as_str),
+            Some("{\"city\":\"Paris\"}")
+        );
+
+        let prepared = build_prompt(
+            conversation,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig {
+                ui_language: "en-US".to_string(),
+                ..AppConfig::default()
+            },
+        );
+        assert!(prepared
+            .history_messages
+            .iter()
+            .any(|m| m.role == "tool" && m.tool_call_id.as_deref() == Some("call_1")));
+    }
+
+    #[test]
+    fn parse_archive_import_should_follow_chatgpt_current_branch() {
+        let raw = serde_json::json!([{
+            "id": "conv-1",
+            "title": "Trip",
+            "create_time": 1700000000.5,
+            "update_time": 1700000100.0,
+            "current_node": "a2",
+            "mapping": {
+                "root": { "message": null, "parent": null, "children": ["u1"] },
+                "u1": {
+                    "parent": "root", "children": ["a1", "a2"],
+                    "message": { "author": { "role": "user" }, "create_time": 1700000001.0,
+                        "content": { "content_type": "text", "parts": ["Plan a trip"] } }
+                },
+                "a1": {
+                    "parent": "u1", "children": [],
+                    "message": { "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": ["old draft"] } }
+                },
+                "a2": {
+                    "parent": "u1", "children": [],
+                    "message": { "author": { "role": "assistant" }, "recipient": "all",
+                        "content": { "content_type": "text", "parts": ["Go to Kyoto"] } }
+                }
+            }
+        }])
+        .to_string();
+        let parsed = parse_archive_import(&raw, "agent-a", "api-a").expect("parse chatgpt");
+        assert_eq!(parsed.format, "chatgpt");
+        let conversation = &parsed.archives[0].source_conversation;
+        assert_eq!(conversation.id, "conv-1");
+        assert_eq!(conversation.title, "Trip");
+        assert_eq!(conversation.messages.len(), 2);
+        assert_eq!(
+            archive_message_plain_text(&conversation.messages[1]),
+            "Go to Kyoto"
+        );
+        assert!(conversation.messages[0].created_at.starts_with("2023-11-14T22:13:21"));
+    }
+
+    #[test]
+    fn conversation_from_archive_should_fork_recent_turns_when_history_is_too_long() {
+        let now = now_iso();
+        let mut messages = Vec::new();
+        for idx in 0..6 {
+            messages.push(test_text_message("user", &format!("问题{idx} {}", "长".repeat(200)), &now));
+            messages.push(test_text_message("assistant", &format!("回答{idx}"), &now));
+        }
+        let mut source = test_active_conversation_with_messages(messages, None);
+        source.status = "archived".to_string();
+        let archive = ConversationArchive {
+            archive_id: "archive-1".to_string(),
+            archived_at: now.clone(),
+            reason: "manual_force_archive".to_string(),
+            summary: "讨论了长文本".to_string(),
+            source_conversation: source.clone(),
+        };
+
+        let (restored, kept) = conversation_from_archive(&archive, "api-b", 100_000, 0.82, 3);
+        assert_eq!(kept, None);
+        assert_eq!(restored.id, source.id);
+        assert_eq!(restored.status, "active");
+        assert_eq!(restored.api_config_id, "api-b");
+        assert_eq!(restored.messages.len(), 12);
+
+        let (forked, kept) = conversation_from_archive(&archive, "api-b", 600, 0.82, 3);
+        assert_eq!(kept, Some(3));
+        assert_ne!(forked.id, source.id);
+        assert_eq!(forked.messages.len(), 6);
+        assert_eq!(archive_message_plain_text(&forked.messages[0]).chars().take(3).collect::<String>(), "问题3");
+        assert_eq!(forked.forked_from_archive_id.as_deref(), Some("archive-1"));
+
+        let mut data = AppData::default();
+        data.archived_conversations.push(archive);
+        let mut later = data.archived_conversations[0].clone();
+        later.archive_id = "archive-2".to_string();
+        later.summary = "另一次对话".to_string();
+        data.archived_conversations.push(later);
+        assert_eq!(archive_recap_summary(&data, &forked).as_deref(), Some("讨论了长文本"));
+        assert_eq!(archive_recap_summary(&data, &restored).as_deref(), Some("另一次对话"));
+    }
+
+    #[test]
+    fn conversation_selection_should_track_current_per_agent() {
+        let mut data = AppData::default();
+        let first = test_active_conversation_with_messages(Vec::new(), None);
+        let second = test_active_conversation_with_messages(Vec::new(), None);
+        let mut other_agent = test_active_conversation_with_messages(Vec::new(), None);
+        other_agent.agent_id = "other".to_string();
+        data.conversations = vec![first.clone(), second.clone(), other_agent.clone()];
+
+        let current = latest_active_conversation_index(&data, "api", "agent").expect("current");
+        assert_eq!(data.conversations[current].id, second.id);
+
+        let idx = active_conversation_index_by_id(&data, &first.id, "api", "agent").expect("first");
+        let idx = select_conversation_index(&mut data, idx);
+        assert_eq!(latest_active_conversation_index(&data, "api", "agent"), Some(idx));
+        assert_eq!(data.conversations[idx].id, first.id);
+        assert!(active_conversation_index_by_id(&data, &other_agent.id, "api", "agent").is_err());
+
+        let idx = push_active_conversation(&mut data, "api", "agent", Some("  Side topic "));
+        assert_eq!(data.conversations[idx].title, "Side topic");
+        assert_eq!(latest_active_conversation_index(&data, "api", "agent"), Some(idx));
+        assert_eq!(ensure_active_conversation_index(&mut data, "api", "agent"), idx);
+        assert_eq!(data.conversations.len(), 4);
+    }
+
+    #[test]
+    fn remove_conversation_messages_should_drop_whole_turn_with_tool_history() {
+        let now = now_iso();
+        let mut reply_with_tool = test_text_message("assistant", "查到了", &now);
+        reply_with_tool.tool_call = Some(vec![
+            serde_json::json!({
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "bing_search", "arguments": "{}" }
+                }]
+            }),
+            serde_json::json!({ "role": "tool", "tool_call_id": "call_1", "content": "ok" }),
+        ]);
+        let first_user = test_text_message("user", "帮我查 Rust", &now);
+        let second_user = test_text_message("user", "再写个例子", &now);
+        let second_reply = test_text_message("assistant", "fn main() {}", &now);
+        let mut conv = test_active_conversation_with_messages(
+            vec![
+                first_user.clone(),
+                reply_with_tool.clone(),
+                second_user.clone(),
+                second_reply.clone(),
+            ],
+            Some(now.clone()),
+        );
+
+        assert_eq!(
+            regenerate_user_message_position(&conv, &second_user.id).expect("last user"),
+            2
+        );
+        assert!(regenerate_user_message_position(&conv, &first_user.id).is_err());
+        let payload = chat_payload_from_user_message(&second_user);
+        assert_eq!(payload.text.as_deref(), Some("再写个例子"));
+        assert!(payload.images.is_none());
+
+        let removed =
+            remove_conversation_messages(&mut conv, &reply_with_tool.id, true).expect("remove");
+        assert_eq!(removed, 2);
+        assert_eq!(conv.messages.len(), 2);
+        assert_eq!(conv.messages[0].id, second_user.id);
+        refresh_conversation_after_edit(&mut conv, 128_000);
+        assert_eq!(conv.last_user_at.as_deref(), Some(now.as_str()));
+
+        let prepared = build_prompt(
+            &conv,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
+        );
+        assert!(prepared.history_messages.iter().all(|m| m.tool_calls.is_none() && m.role != "tool"));
+
+        let removed =
+            remove_conversation_messages(&mut conv, &second_reply.id, false).expect("remove");
+        assert_eq!(removed, 1);
+        assert_eq!(conv.messages.len(), 1);
+    }
+
+    #[test]
+    fn conversation_branches_should_keep_alternate_replies_and_edits() {
+        let now = now_iso();
+        let question = test_text_message("user", "讲个笑话", &now);
+        let first_reply = test_text_message("assistant", "第一个笑话", &now);
+        let mut conv = test_active_conversation_with_messages(Vec::new(), Some(now.clone()));
+        push_branch_message(&mut conv, question.clone());
+        push_branch_message(&mut conv, first_reply.clone());
+
+        // 重新生成：旧回复转为备选分支，新回复是它的兄弟。
+        stash_branch_tail(&mut conv, 1);
+        let second_reply = test_text_message("assistant", "第二个笑话", &now);
+        push_branch_message(&mut conv, second_reply.clone());
+        assert_eq!(conv.messages.len(), 2);
+        assert_eq!(conv.messages[1].sibling_index, 1);
+        assert_eq!(conv.messages[1].parent_id.as_deref(), Some(question.id.as_str()));
+        let follow_up = test_text_message("user", "再来一个", &now);
+        push_branch_message(&mut conv, follow_up.clone());
+
+        let branches = conversation_branch_infos(&conv);
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].message_id, second_reply.id);
+        assert_eq!((branches[0].sibling_position, branches[0].sibling_count), (1, 2));
+
+        // 切回旧回复，追问随第二个回复留在备选分支里；再切回来时沿最近的子消息展开。
+        switch_conversation_branch(&mut conv, &second_reply.id, -1).expect("switch back");
+        assert_eq!(conv.messages.len(), 2);
+        assert_eq!(conv.messages[1].id, first_reply.id);
+        assert!(switch_conversation_branch(&mut conv, &first_reply.id, -1).is_err());
+        let tokens_on_first = estimate_conversation_tokens(&conv);
+        switch_conversation_branch(&mut conv, &first_reply.id, 1).expect("switch forward");
+        let ids = conv.messages.iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![question.id.clone(), second_reply.id.clone(), follow_up.id.clone()]);
+        assert!(estimate_conversation_tokens(&conv) > tokens_on_first);
+
+        let prepared = build_prompt(
+            &conv,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
+        );
+        assert!(prepared
+            .history_messages
+            .iter()
+            .all(|m| !m.text.contains("第一个笑话")));
+
+        // 编辑首条提问：原提问及其后的对话整体成为根上的备选分支。
+        stash_branch_tail(&mut conv, 0);
+        let edited = test_text_message("user", "讲个冷笑话", &now);
+        push_branch_message(&mut conv, edited.clone());
+        assert_eq!(conv.messages.len(), 1);
+        assert_eq!(conv.messages[0].sibling_index, 1);
+        switch_conversation_branch(&mut conv, &edited.id, -1).expect("switch to original");
+        assert_eq!(conv.messages.len(), 3);
+        assert_eq!(conv.messages[2].id, follow_up.id);
+
+        // 删除整轮后，挂在被删消息下的备选分支一并清理。
+        remove_conversation_messages(&mut conv, &question.id, true).expect("remove");
+        assert!(conv
+            .alternate_messages
+            .iter()
+            .all(|m| m.id != first_reply.id));
+    }
+
+    #[test]
+    fn conversation_title_should_wait_for_first_exchange_and_clean_reply() {
+        let now = now_iso();
+        let mut conv = test_active_conversation_with_messages(
+            vec![test_text_message("user", "怎么给 Rust 项目配置 clippy", &now)],
+            Some(now.clone()),
+        );
+        assert!(conversation_title_prompt(&conv, "zh-CN").is_none());
+
+        conv.messages
+            .push(test_text_message("assistant", "在 CI 里运行 cargo clippy", &now));
+        let prepared = conversation_title_prompt(&conv, "en-US").expect("title prompt");
+        assert!(prepared.preamble.contains("English"));
+        assert!(prepared.latest_user_text.contains("配置 clippy"));
+        assert!(prepared.latest_user_text.contains("cargo clippy"));
+
+        assert_eq!(
+            clean_generated_title("<think>想一想</think>\n标题：“配置 Rust Clippy”。").as_deref(),
+            Some("配置 Rust Clippy")
+        );
+        assert_eq!(
+            clean_generated_title("\"Setting up Clippy.\"\nextra").as_deref(),
+            Some("Setting up Clippy")
+        );
+        assert!(clean_generated_title("  \n ").is_none());
+    }
+
+    #[test]
+    fn compaction_should_keep_previous_state_when_summary_is_empty() {
+        let now = now_iso();
+        let mut messages = Vec::new();
+        for i in 1..=4 {
+            messages.push(test_text_message("user", &format!("问题{i}"), &now));
+            messages.push(test_text_message("assistant", &format!("回答{i}"), &now));
+        }
+        let mut conv = test_active_conversation_with_messages(messages, Some(now));
+
+        let until = compaction_boundary(&conv, 2).expect("boundary");
+        assert!(!apply_conversation_compaction(&mut conv, until, "  "));
+        assert!(conv.compaction.is_none());
+        assert_eq!(compacted_start(&conv), 0);
+
+        assert!(apply_conversation_compaction(&mut conv, 1, "聊了问题1"));
+        let until = compaction_boundary(&conv, 1).expect("second boundary");
+        assert!(!apply_conversation_compaction(&mut conv, until, ""));
+        assert_eq!(compacted_start(&conv), 2);
+        assert_eq!(conv.compaction.as_ref().map(|c| c.summary.as_str()), Some("聊了问题1"));
+    }
+
+    #[test]
+    fn compaction_should_summarize_old_turns_and_keep_originals() {
+        let now = now_iso();
+        let mut messages = Vec::new();
+        for i in 1..=4 {
+            messages.push(test_text_message("user", &format!("问题{i}"), &now));
+            messages.push(test_text_message("assistant", &format!("回答{i}"), &now));
+        }
+        let mut conv = test_active_conversation_with_messages(messages, Some(now.clone()));
+        let compact = ArchivePolicy {
+            overflow_action: ContextOverflowAction::Compact,
+            mode: ArchiveMode::Never,
+            ..ArchivePolicy::default()
+        };
+        let d = decide_archive_before_user_message(&conv, 10, &compact, now_utc());
+        assert!(d.compact && !d.should_archive);
+
+        let until = compaction_boundary(&conv, 2).expect("boundary");
+        assert_eq!(until, 3);
+        let prepared = conversation_compaction_prompt(&conv, until, "助理", "用户");
+        assert!(prepared.latest_user_text.contains("问题2"));
+        assert!(!prepared.latest_user_text.contains("问题3"));
+        let before = estimate_conversation_tokens(&conv);
+        assert!(apply_conversation_compaction(&mut conv, until, "聊了问题1和2"));
+        assert_eq!(conv.messages.len(), 8);
+        assert_eq!(compacted_start(&conv), 4);
+        assert!(estimate_conversation_tokens(&conv) < before);
+
+        let prompt = build_prompt(
+            &conv,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
+        );
+        assert!(prompt.preamble.contains("聊了问题1和2"));
+        assert!(prompt.history_messages.iter().all(|m| !m.text.contains("问题1")));
+        assert!(prompt.history_messages.iter().any(|m| m.text.contains("问题3")));
+
+        // 再次压缩时合并上次摘要，只剩一轮时不再压缩。
+        let until = compaction_boundary(&conv, 1).expect("second boundary");
+        let prepared = conversation_compaction_prompt(&conv, until, "助理", "用户");
+        assert!(prepared.latest_user_text.contains("[PREVIOUS SUMMARY]\n聊了问题1和2"));
+        assert!(!prepared.latest_user_text.contains("问题2"));
+        assert!(apply_conversation_compaction(&mut conv, until, "聊了问题1到3"));
+        assert_eq!(conv.compaction.as_ref().map(|c| c.summary.as_str()), Some("聊了问题1到3"));
+        assert!(compaction_boundary(&conv, 3).is_none());
+
+        // 编辑被覆盖的消息后摘要失效，提示词回到完整历史。
+        stash_branch_tail(&mut conv, 2);
+        assert!(conv.compaction.is_none());
+        assert_eq!(compacted_start(&conv), 0);
+    }
+
+    #[test]
+    fn message_time_should_render_local_time_with_weekday_and_zone() {
+        let offset = parse_utc_offset("UTC+8").expect("offset");
+        assert_eq!(format_utc_offset_label(offset), "UTC+08:00");
+        assert_eq!(
+            format_message_time_text("2026-02-11T17:30:45Z", &UserTimeZone::fixed(offset)),
+            "2026-02-12 01:30:45 Thursday (UTC+08:00)"
+        );
+        let named = UserTimeZone {
+            name: Some("Asia/Shanghai".to_string()),
+            offset,
+        };
+        assert_eq!(
+            format_message_time_text("2026-02-11T17:30:45Z", &named),
+            "2026-02-12 01:30:45 Thursday (Asia/Shanghai UTC+08:00)"
+        );
+        let west = parse_utc_offset("-05:30").expect("negative offset");
+        assert_eq!(format_utc_offset_label(west), "UTC-05:30");
+        assert_eq!(parse_utc_offset("+0930"), UtcOffset::from_hms(9, 30, 0).ok());
+        assert!(parse_utc_offset("Asia/Shanghai").is_none());
+        assert!(parse_utc_offset("+25:00").is_none());
+        assert!(parse_utc_offset("+1é1").is_none());
+        assert!(parse_utc_offset("+1é12").is_none());
+
+        let conv = test_active_conversation_with_messages(
+            vec![test_text_message("user", "明早提醒我", "2026-02-11T17:30:45Z")],
+            Some("2026-02-11T17:30:45Z".to_string()),
+        );
+        let prepared = build_prompt(
+            &conv,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig {
+                timezone: "UTC+8".to_string(),
+                ..AppConfig::default()
+            },
+        );
+        assert_eq!(prepared.latest_user_time_text, "2026-02-12 01:30:45 Thursday (UTC+08:00)");
+        assert!(prepared.preamble.contains("- 用户时区：UTC+08:00"));
+        // 前言不含随时间变化的内容，便于命中提示词缓存。
+        assert!(!prepared.preamble.contains("当前时间："));
+
+        // 跟随系统时显示系统时区名称。
+        let prepared = build_prompt(
+            &conv,
+            &default_agent(),
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
+        );
+        let system_label = system_time_zone().label();
+        assert!(prepared.preamble.contains(&format!("- 用户时区：{system_label}")));
+    }
+
+    #[test]
+    fn highest_instruction_should_merge_config_override_and_agent_adjustments() {
+        let mut app_config = AppConfig {
+            highest_instruction: Some(HighestInstruction {
+                title: " 公司准则 ".to_string(),
+                rules: vec!["不得泄露客户数据。".to_string(), "  ".to_string()],
+            }),
+            ..AppConfig::default()
+        };
+        normalize_highest_instruction(&mut app_config.highest_instruction);
+        let mut agent = default_agent();
+        agent.highest_instruction = Some(AgentInstructionOverride {
+            disabled: false,
+            extra_rules: vec!["回答控制在三句话以内。".to_string()],
+        });
+        let merged = effective_highest_instruction(&app_config, Some(&agent)).expect("merged");
+        assert_eq!(merged.title, "公司准则");
+        assert_eq!(merged.rules, vec!["不得泄露客户数据。", "回答控制在三句话以内。"]);
+
+        let conv = test_active_conversation_with_messages(Vec::new(), None);
+        let prepared = build_prompt(
+            &conv,
+            &agent,
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &app_config,
+        );
+        assert!(prepared
+            .preamble
+            .starts_with("# 公司准则\n- 不得泄露客户数据。\n- 回答控制在三句话以内。\n"));
+
+        agent.highest_instruction = Some(AgentInstructionOverride {
+            disabled: true,
+            extra_rules: Vec::new(),
+        });
+        assert!(effective_highest_instruction(&app_config, Some(&agent)).is_none());
+        let prepared = build_prompt(
+            &conv,
+            &agent,
+            "用户",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &app_config,
+        );
+        assert!(!prepared.preamble.contains("公司准则"));
+
+        let mut empty = Some(AgentInstructionOverride::default());
+        normalize_agent_instruction_override(&mut empty);
+        assert!(empty.is_none());
+        let built_in = effective_highest_instruction(&AppConfig::default(), None).expect("built-in");
+        assert_eq!(&built_in, highest_instruction());
+    }
+
+    #[test]
+    fn prompt_template_should_render_variables_and_conditionals() {
+        let mut vars = PromptTemplateVars::new();
+        vars.insert("user_name", "小明".to_string());
+        vars.insert("os", "Windows".to_string());
+        vars.insert("clipboard", String::new());
+        let template = "你好 {{ user_name }}，{{#if os == windows}}用 Win 快捷键{{else}}用系统快捷键{{/if}}。\
+{{#if clipboard}}剪贴板：{{clipboard}}{{else}}剪贴板为空{{/if}}{{#if !missing}}！{{/if}}{{unknown}}";
+        assert_eq!(
+            render_prompt_template(template, &vars),
+            "你好 小明，用 Win 快捷键。剪贴板为空！{{unknown}}"
+        );
+        assert_eq!(
+            render_prompt_template("{{#if os != \"Windows\"}}a{{#if user_name}}b{{/if}}{{else}}c{{/if}}d", &vars),
+            "cd"
+        );
+        assert_eq!(render_prompt_template("x {{user_name", &vars), "x {{user_name");
+
+        let mut agent = default_agent();
+        agent.system_prompt = "称呼用户为{{user_name}}，今天是{{weekday}}。".to_string();
+        let conv = test_active_conversation_with_messages(Vec::new(), None);
+        let prepared = build_prompt(
+            &conv,
+            &agent,
+            "阿青",
+            "",
+            &response_style_preset(DEFAULT_RESPONSE_STYLE_ID, &[]),
+            &AppConfig::default(),
+        );
+        assert!(prepared.preamble.contains("称呼用户为阿青，今天是"));
+        assert!(!prepared.preamble.contains("{{weekday}}"));
+    }
+
+    #[test]
+    fn agent_overrides_should_pick_default_api_and_filter_tools() {
+        let tts = ApiConfig {
+            id: "tts".to_string(),
+            request_format: RequestFormat::OpenAITts,
+            ..ApiConfig::default()
+        };
+        let chat = ApiConfig {
+            id: "chat-b".to_string(),
+            ..ApiConfig::default()
+        };
+        let app_config = AppConfig {
+            api_configs: vec![
+                ApiConfig {
+                    api_key: "sk-test".to_string(),
+                    ..ApiConfig::default()
+                },
+                tts,
+                chat,
+            ],
+            ..AppConfig::default()
+        };
+        let mut agent = AgentProfile {
+            api_config_id: Some(" chat-b ".to_string()),
+            temperature: Some(3.5),
+            allowed_tools: vec!["fetch".to_string(), " memory-save".to_string(), "fetch".to_string()],
+            denied_tools: vec!["memory-save".to_string(), "".to_string()],
+            ..default_agent()
+        };
+        normalize_agent_model_overrides(&mut agent);
+        assert_eq!(agent.api_config_id.as_deref(), Some("chat-b"));
+        assert_eq!(agent.temperature, Some(2.0));
+        assert_eq!(agent.allowed_tools, vec!["fetch", "memory-save"]);
+        assert_eq!(agent.denied_tools, vec!["memory-save"]);
+        assert_eq!(
+            agent_default_api_config(&app_config, &agent).map(|a| a.id.as_str()),
+            Some("chat-b")
+        );
+        let tts_agent = AgentProfile {
+            api_config_id: Some("tts".to_string()),
+            ..default_agent()
+        };
+        assert!(agent_default_api_config(&app_config, &tts_agent).is_none());
+
+        let mut api = app_config.api_configs[0].clone();
+        let mut resolved = resolve_api_config(&app_config, Some(api.id.as_str())).expect("resolve");
+        apply_agent_overrides(&mut api, &mut resolved, &agent);
+        assert_eq!(resolved.temperature, 2.0);
+        assert!(tool_enabled(&api, "fetch"));
+        assert!(!tool_enabled(&api, "memory-save"));
+        assert!(!tool_enabled(&api, "bing-search"));
+    }
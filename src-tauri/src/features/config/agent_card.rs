@@ -0,0 +1,327 @@
+// 单个智能体的导入导出：自有 JSON 格式（含头像与设置），以及写在 PNG tEXt 块里的角色卡 V2。
+
+const AGENT_EXPORT_FORMAT: &str = "easy-call-ai-agent";
+const AGENT_EXPORT_VERSION: u32 = 1;
+const CHARACTER_CARD_SPEC: &str = "chara_card_v2";
+const CHARACTER_CARD_TEXT_KEY: &str = "chara";
+/// 角色卡 extensions 中保存本应用完整设定的键，导回时优先使用。
+const CHARACTER_CARD_EXTENSION_KEY: &str = "easy_call_ai";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentExportAvatar {
+    mime: String,
+    bytes_base64: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentExportFile {
+    format: String,
+    version: u32,
+    exported_at: String,
+    agent: AgentProfile,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avatar: Option<AgentExportAvatar>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CharacterCardData {
+    name: String,
+    description: String,
+    personality: String,
+    scenario: String,
+    first_mes: String,
+    mes_example: String,
+    creator_notes: String,
+    system_prompt: String,
+    post_history_instructions: String,
+    alternate_greetings: Vec<String>,
+    tags: Vec<String>,
+    creator: String,
+    character_version: String,
+    extensions: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CharacterCardV2 {
+    spec: String,
+    spec_version: String,
+    data: CharacterCardData,
+}
+
+/// 导入结果：智能体（id 由调用方重新分配）与原始头像字节。
+#[derive(Debug, Clone)]
+struct ParsedAgentImport {
+    agent: AgentProfile,
+    avatar: Option<Vec<u8>>,
+}
+
+/// 导出时去掉本机相关的字段。
+fn agent_for_export(agent: &AgentProfile) -> AgentProfile {
+    let mut agent = agent.clone();
+    agent.avatar_path = None;
+    agent.avatar_updated_at = None;
+    agent.is_built_in_user = false;
+    agent
+}
+
+/// 导入时同样清掉本机相关字段；最高准则的调整不随文件导入，需在本机重新设置。
+fn agent_for_import(agent: AgentProfile) -> AgentProfile {
+    AgentProfile {
+        highest_instruction: None,
+        ..agent_for_export(&agent)
+    }
+}
+
+/// 导出文件的默认文件名：保留各语言的文字与数字，其余字符替换为下划线。
+fn agent_export_file_name(name: &str, extension: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|ch| if ch.is_alphanumeric() || ch == '-' || ch == '_' { ch } else { '_' })
+        .collect();
+    let stem = stem.trim_matches('_');
+    let stem = if stem.is_empty() { "agent" } else { stem };
+    format!("{stem}.{extension}")
+}
+
+fn build_agent_export_json(agent: &AgentProfile, avatar: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let file = AgentExportFile {
+        format: AGENT_EXPORT_FORMAT.to_string(),
+        version: AGENT_EXPORT_VERSION,
+        exported_at: now_iso(),
+        agent: agent_for_export(agent),
+        avatar: avatar.map(|bytes| AgentExportAvatar {
+            mime: "image/webp".to_string(),
+            bytes_base64: B64.encode(bytes),
+        }),
+    };
+    serde_json::to_vec_pretty(&file).map_err(|err| format!("Serialize agent export failed: {err}"))
+}
+
+/// 生成角色卡 PNG：头像转为 PNG（没有头像时用纯色底图），卡片 JSON 以 base64 写入 `chara` 文本块。
+fn build_agent_card_png(agent: &AgentProfile, avatar: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    let image = match avatar {
+        Some(bytes) => image::load_from_memory(bytes)
+            .map_err(|err| format!("Decode avatar image failed: {err}"))?,
+        None => image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            256,
+            256,
+            image::Rgba([200, 200, 200, 255]),
+        )),
+    };
+    let mut png = Vec::<u8>::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| format!("Encode character card image failed: {err}"))?;
+
+    let exported = agent_for_export(agent);
+    let mut extensions = serde_json::Map::new();
+    extensions.insert(
+        CHARACTER_CARD_EXTENSION_KEY.to_string(),
+        serde_json::to_value(&exported)
+            .map_err(|err| format!("Serialize agent export failed: {err}"))?,
+    );
+    let card = CharacterCardV2 {
+        spec: CHARACTER_CARD_SPEC.to_string(),
+        spec_version: "2.0".to_string(),
+        data: CharacterCardData {
+            name: exported.name.clone(),
+            // 与导入时的替换相反，让其他前端也能识别用户占位符。
+            description: exported.system_prompt.replace("{{user_name}}", "{{user}}"),
+            extensions,
+            ..CharacterCardData::default()
+        },
+    };
+    let json = serde_json::to_string(&card)
+        .map_err(|err| format!("Serialize character card failed: {err}"))?;
+    png_with_text_chunk(&png, CHARACTER_CARD_TEXT_KEY, &B64.encode(json))
+}
+
+/// 按文件头识别 PNG 角色卡或 JSON（本应用格式或裸角色卡 JSON）。
+fn parse_agent_import(raw: &[u8]) -> Result<ParsedAgentImport, String> {
+    if raw.starts_with(PNG_SIGNATURE) {
+        let text = png_text_chunks(raw)?
+            .into_iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(CHARACTER_CARD_TEXT_KEY))
+            .map(|(_, text)| text)
+            .ok_or_else(|| "PNG does not contain character card data.".to_string())?;
+        let decoded = B64
+            .decode(text.trim())
+            .map_err(|err| format!("Decode character card base64 failed: {err}"))?;
+        let value: Value = serde_json::from_slice(&decoded)
+            .map_err(|err| format!("Parse character card failed: {err}"))?;
+        return Ok(ParsedAgentImport {
+            agent: agent_from_character_card(&value)?,
+            avatar: Some(raw.to_vec()),
+        });
+    }
+
+    let value: Value = serde_json::from_slice(raw)
+        .map_err(|err| format!("Parse agent file failed: {err}"))?;
+    if value.get("format").and_then(Value::as_str) == Some(AGENT_EXPORT_FORMAT) {
+        let file: AgentExportFile = serde_json::from_value(value)
+            .map_err(|err| format!("Parse agent file failed: {err}"))?;
+        if file.version > AGENT_EXPORT_VERSION {
+            return Err(format!(
+                "Agent file version {} is newer than supported version {AGENT_EXPORT_VERSION}.",
+                file.version
+            ));
+        }
+        let avatar = file
+            .avatar
+            .map(|a| B64.decode(a.bytes_base64.trim()))
+            .transpose()
+            .map_err(|err| format!("Decode avatar base64 failed: {err}"))?;
+        return Ok(ParsedAgentImport {
+            agent: agent_for_import(file.agent),
+            avatar,
+        });
+    }
+    Ok(ParsedAgentImport {
+        agent: agent_from_character_card(&value)?,
+        avatar: None,
+    })
+}
+
+/// V2 卡片读取 data，V1 卡片字段在顶层；带本应用扩展时直接还原完整设定。
+fn agent_from_character_card(value: &Value) -> Result<AgentProfile, String> {
+    let data_value = match value.get("spec").and_then(Value::as_str) {
+        Some(CHARACTER_CARD_SPEC) => value.get("data").cloned().unwrap_or(Value::Null),
+        Some(other) => return Err(format!("Unsupported character card spec '{other}'.")),
+        None => value.clone(),
+    };
+    let data: CharacterCardData = serde_json::from_value(data_value)
+        .map_err(|err| format!("Parse character card failed: {err}"))?;
+    let now = now_iso();
+    if let Some(agent) = data
+        .extensions
+        .get(CHARACTER_CARD_EXTENSION_KEY)
+        .and_then(|v| serde_json::from_value::<AgentProfile>(v.clone()).ok())
+    {
+        return Ok(AgentProfile {
+            updated_at: now,
+            ..agent_for_import(agent)
+        });
+    }
+
+    let name = data.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Character card has no name.".to_string());
+    }
+    // 角色卡中的 {{char}} / {{user}} 换成角色名与本应用的提示词模板变量。
+    let convert = |text: &str| {
+        text.trim()
+            .replace("{{char}}", &name)
+            .replace("{{user}}", "{{user_name}}")
+    };
+    let mut sections = Vec::<String>::new();
+    for (title, text) in [
+        ("", &data.system_prompt),
+        ("", &data.description),
+        ("## 性格\n", &data.personality),
+        ("## 场景\n", &data.scenario),
+        ("## 开场白\n", &data.first_mes),
+        ("## 对话示例\n", &data.mes_example),
+        ("", &data.post_history_instructions),
+    ] {
+        let text = convert(text);
+        if !text.is_empty() {
+            sections.push(format!("{title}{text}"));
+        }
+    }
+    Ok(AgentProfile {
+        id: String::new(),
+        name,
+        system_prompt: sections.join("\n\n"),
+        created_at: now.clone(),
+        updated_at: now,
+        ..default_agent()
+    })
+}
+
+fn png_crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// (块类型, 块数据)
+type PngChunk<'a> = ([u8; 4], &'a [u8]);
+
+/// 遍历 PNG 块，读到 IEND 为止。
+fn png_chunks(raw: &[u8]) -> Result<Vec<PngChunk<'_>>, String> {
+    if !raw.starts_with(PNG_SIGNATURE) {
+        return Err("Not a PNG file.".to_string());
+    }
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= raw.len() {
+        let len = u32::from_be_bytes([raw[pos], raw[pos + 1], raw[pos + 2], raw[pos + 3]]) as usize;
+        let kind = [raw[pos + 4], raw[pos + 5], raw[pos + 6], raw[pos + 7]];
+        let data_end = pos + 8 + len;
+        if data_end + 4 > raw.len() {
+            return Err("PNG chunk is truncated.".to_string());
+        }
+        chunks.push((kind, &raw[pos + 8..data_end]));
+        pos = data_end + 4;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+/// tEXt 块内容为 `关键字\0文本`，文本按 Latin-1 解码（角色卡正文为 base64，不受影响）。
+fn png_text_chunks(raw: &[u8]) -> Result<Vec<(String, String)>, String> {
+    Ok(png_chunks(raw)?
+        .into_iter()
+        .filter(|(kind, _)| kind == b"tEXt")
+        .filter_map(|(_, data)| {
+            let split = data.iter().position(|b| *b == 0)?;
+            let key = data[..split].iter().map(|b| char::from(*b)).collect();
+            let text = data[split + 1..].iter().map(|b| char::from(*b)).collect();
+            Some((key, text))
+        })
+        .collect())
+}
+
+/// 重新组装 PNG：去掉同名 tEXt 块，在 IEND 前写入新的文本块。
+fn png_with_text_chunk(raw: &[u8], key: &str, text: &str) -> Result<Vec<u8>, String> {
+    let mut payload = Vec::with_capacity(key.len() + 1 + text.len());
+    payload.extend_from_slice(key.as_bytes());
+    payload.push(0);
+    payload.extend_from_slice(text.as_bytes());
+
+    let write_chunk = |out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]| {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let crc_start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = png_crc32(&out[crc_start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    };
+
+    let mut out = Vec::with_capacity(raw.len() + payload.len() + 12);
+    out.extend_from_slice(PNG_SIGNATURE);
+    for (kind, data) in png_chunks(raw)? {
+        let same_key = &kind == b"tEXt"
+            && data.iter().position(|b| *b == 0).map(|i| &data[..i]) == Some(key.as_bytes());
+        if same_key {
+            continue;
+        }
+        if &kind == b"IEND" {
+            write_chunk(&mut out, b"tEXt", &payload);
+        }
+        write_chunk(&mut out, &kind, data);
+    }
+    Ok(out)
+}
@@ -0,0 +1,352 @@
+// API key 池：同一配置下多个 key 轮换，429/401 时冷却并切换到下一个 key。
+
+/// 配置中的全部 key 位置（api_key 在前，其后为 extra_api_keys），可能含空值。
+fn api_key_slots(api: &ApiConfig) -> impl Iterator<Item = &String> {
+    std::iter::once(&api.api_key).chain(api.extra_api_keys.iter())
+}
+
+/// 去空、去重后的 key 引用，保持配置顺序。
+fn api_key_pool(api: &ApiConfig) -> Vec<String> {
+    let mut out = Vec::<String>::new();
+    for key in api_key_slots(api) {
+        let key = key.trim();
+        if !key.is_empty() && !out.iter().any(|k| k == key) {
+            out.push(key.to_string());
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Default)]
+struct ApiKeyHealthState {
+    last_used_seq: u64,
+    last_used_at: Option<String>,
+    cooldown_until: Option<std::time::Instant>,
+    last_rate_limited: Option<std::time::Instant>,
+    last_rate_limited_at: Option<String>,
+    last_status_code: Option<u16>,
+    last_error: Option<String>,
+    success_count: u64,
+    failure_count: u64,
+}
+
+#[derive(Debug, Default)]
+struct ApiKeyHealthRegistry {
+    seq: u64,
+    keys: std::collections::HashMap<(String, String), ApiKeyHealthState>,
+}
+
+fn api_key_health_registry() -> &'static Mutex<ApiKeyHealthRegistry> {
+    static REGISTRY: OnceLock<Mutex<ApiKeyHealthRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(ApiKeyHealthRegistry::default()))
+}
+
+fn api_key_health_state(api_config_id: &str, key_ref: &str) -> ApiKeyHealthState {
+    api_key_health_registry()
+        .lock()
+        .ok()
+        .and_then(|r| {
+            r.keys
+                .get(&(api_config_id.to_string(), key_ref.to_string()))
+                .cloned()
+        })
+        .unwrap_or_default()
+}
+
+fn update_api_key_health(
+    api_config_id: &str,
+    key_ref: &str,
+    update: impl FnOnce(&mut ApiKeyHealthState, u64),
+) {
+    let Ok(mut registry) = api_key_health_registry().lock() else {
+        return;
+    };
+    registry.seq += 1;
+    let seq = registry.seq;
+    let entry = registry
+        .keys
+        .entry((api_config_id.to_string(), key_ref.to_string()))
+        .or_default();
+    update(entry, seq);
+}
+
+/// 按轮换策略给出本次尝试顺序：未冷却的 key 在前，冷却中的按解除时间排在最后。
+fn order_api_key_refs(
+    api_config_id: &str,
+    refs: &[String],
+    rotation: ApiKeyRotation,
+) -> Vec<String> {
+    let now = std::time::Instant::now();
+    let mut available = Vec::new();
+    let mut cooling = Vec::new();
+    for (idx, key_ref) in refs.iter().enumerate() {
+        let health = api_key_health_state(api_config_id, key_ref);
+        match health.cooldown_until.filter(|until| *until > now) {
+            Some(until) => cooling.push((until, idx, key_ref.clone())),
+            None => available.push((health, idx, key_ref.clone())),
+        }
+    }
+    match rotation {
+        ApiKeyRotation::RoundRobin => {
+            available.sort_by_key(|(health, idx, _)| (health.last_used_seq, *idx));
+        }
+        ApiKeyRotation::LeastRateLimited => {
+            available.sort_by_key(|(health, idx, _)| (health.last_rate_limited, *idx));
+        }
+    }
+    cooling.sort_by_key(|(until, idx, _)| (*until, *idx));
+    available
+        .into_iter()
+        .map(|(_, _, key_ref)| key_ref)
+        .chain(cooling.into_iter().map(|(_, _, key_ref)| key_ref))
+        .collect()
+}
+
+/// 模型/STT 请求失败：status 为服务端返回的 HTTP 状态码，网络错误等拿不到时为 None。
+#[derive(Debug, Clone)]
+struct ApiCallError {
+    status: Option<u16>,
+    message: String,
+}
+
+impl ApiCallError {
+    fn new(status: Option<u16>, message: String) -> Self {
+        Self { status, message }
+    }
+
+    fn from_rig_completion(err: &CompletionError, context: &str) -> Self {
+        Self::new(rig_completion_error_status(err), format!("{context}: {err}"))
+    }
+
+    fn from_rig_prompt(err: &PromptError) -> Self {
+        let status = match err {
+            PromptError::CompletionError(inner) => rig_completion_error_status(inner),
+            _ => None,
+        };
+        Self::new(status, err.to_string())
+    }
+
+    /// 只有限流（429）和鉴权失败（401）算 key 级别的失败，需要冷却并换 key。
+    fn key_failure_status(&self) -> Option<u16> {
+        self.status.filter(|status| matches!(status, 429 | 401))
+    }
+}
+
+impl std::fmt::Display for ApiCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<String> for ApiCallError {
+    fn from(message: String) -> Self {
+        Self::new(None, message)
+    }
+}
+
+impl From<ApiCallError> for String {
+    fn from(err: ApiCallError) -> Self {
+        err.message
+    }
+}
+
+fn rig_completion_error_status(err: &CompletionError) -> Option<u16> {
+    match err {
+        CompletionError::HttpError(
+            rig::http_client::Error::InvalidStatusCode(status)
+            | rig::http_client::Error::InvalidStatusCodeWithMessage(status, _),
+        ) => Some(status.as_u16()),
+        CompletionError::ProviderError(body) => provider_error_body_status(body),
+        _ => None,
+    }
+}
+
+/// rig 的非流式接口只把错误响应体交回来：Gemini 的 error.code 是数字状态码，
+/// OpenAI / Anthropic 则给出错误类型。
+fn provider_error_body_status(body: &str) -> Option<u16> {
+    let value = serde_json::from_str::<Value>(body.trim()).ok()?;
+    let error = value.get("error")?;
+    if let Some(code) = error.get("code").and_then(Value::as_u64) {
+        return u16::try_from(code).ok();
+    }
+    ["type", "code", "status"]
+        .iter()
+        .filter_map(|field| error.get(*field).and_then(Value::as_str))
+        .find_map(|kind| match kind {
+            "rate_limit_error" | "rate_limit_exceeded" | "RESOURCE_EXHAUSTED" => Some(429),
+            "authentication_error" | "invalid_api_key" | "UNAUTHENTICATED" => Some(401),
+            _ => None,
+        })
+}
+
+fn mark_api_key_attempt(api_config_id: &str, key_ref: &str) {
+    update_api_key_health(api_config_id, key_ref, |health, seq| {
+        health.last_used_seq = seq;
+        health.last_used_at = Some(now_iso());
+    });
+}
+
+fn mark_api_key_success(api_config_id: &str, key_ref: &str) {
+    update_api_key_health(api_config_id, key_ref, |health, _| {
+        health.success_count += 1;
+        health.cooldown_until = None;
+        health.last_status_code = None;
+        health.last_error = None;
+    });
+}
+
+fn mark_api_key_failure(api_config_id: &str, key_ref: &str, status: Option<u16>, err: &str) {
+    let now = std::time::Instant::now();
+    update_api_key_health(api_config_id, key_ref, |health, _| {
+        health.failure_count += 1;
+        health.last_status_code = status;
+        health.last_error = Some(err.chars().take(300).collect());
+        let cooldown = match status {
+            Some(429) => {
+                health.last_rate_limited = Some(now);
+                health.last_rate_limited_at = Some(now_iso());
+                Some(API_KEY_RATE_LIMIT_COOLDOWN_SECONDS)
+            }
+            Some(401) => Some(API_KEY_AUTH_FAILURE_COOLDOWN_SECONDS),
+            _ => None,
+        };
+        if let Some(seconds) = cooldown {
+            health.cooldown_until = Some(now + std::time::Duration::from_secs(seconds));
+        }
+    });
+}
+
+/// 选出本次请求的 key：按轮换顺序取第一个能解析的，并把它放到 api_key_refs 最前。
+fn select_api_key(api: &ApiConfig) -> Result<(String, Vec<String>), String> {
+    let mut refs = order_api_key_refs(&api.id, &api_key_pool(api), api.key_rotation);
+    if refs.is_empty() {
+        return Err(
+            "Selected API config API key is empty. Please fill it in settings.".to_string(),
+        );
+    }
+    let mut first_err = None;
+    let mut chosen = None;
+    for (idx, key_ref) in refs.iter().enumerate() {
+        match reveal_api_key(key_ref) {
+            Ok(api_key) => {
+                chosen = Some((idx, api_key));
+                break;
+            }
+            Err(err) => {
+                mark_api_key_failure(&api.id, key_ref, None, &err);
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    let Some((idx, api_key)) = chosen else {
+        return Err(first_err.unwrap_or_default());
+    };
+    let key_ref = refs.remove(idx);
+    refs.insert(0, key_ref);
+    Ok((api_key, refs))
+}
+
+/// 依次用 key 池中的 key 发起请求；遇到 429/401 把当前 key 冷却并换下一个，其余错误直接返回。
+async fn call_with_api_key_rotation<T, F, Fut>(
+    api_config: &ResolvedApiConfig,
+    mut call: F,
+) -> Result<T, String>
+where
+    F: FnMut(ResolvedApiConfig) -> Fut,
+    Fut: std::future::Future<Output = Result<T, ApiCallError>>,
+{
+    let api_id = api_config.api_config_id.as_str();
+    let mut last_err = None;
+    for (idx, key_ref) in api_config.api_key_refs.iter().enumerate() {
+        let api_key = if idx == 0 {
+            api_config.api_key.clone()
+        } else {
+            match reveal_api_key(key_ref) {
+                Ok(api_key) => api_key,
+                Err(err) => {
+                    mark_api_key_failure(api_id, key_ref, None, &err);
+                    last_err = Some(err);
+                    continue;
+                }
+            }
+        };
+        let attempt = ResolvedApiConfig {
+            api_key,
+            ..api_config.clone()
+        };
+        mark_api_key_attempt(api_id, key_ref);
+        match call(attempt).await {
+            Ok(value) => {
+                mark_api_key_success(api_id, key_ref);
+                return Ok(value);
+            }
+            Err(err) => {
+                let Some(status) = err.key_failure_status() else {
+                    return Err(err.message);
+                };
+                mark_api_key_failure(api_id, key_ref, Some(status), &err.message);
+                last_err = Some(err.message);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| "No usable API key in selected API config.".to_string()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiKeyHealth {
+    api_config_id: String,
+    key_index: usize,
+    /// env:/file: 引用原样显示，其余只显示序号。
+    key_label: String,
+    /// unused | ok | coolingDown | failing
+    status: String,
+    cooldown_remaining_seconds: u64,
+    last_status_code: Option<u16>,
+    last_error: Option<String>,
+    last_used_at: Option<String>,
+    last_rate_limited_at: Option<String>,
+    success_count: u64,
+    failure_count: u64,
+}
+
+fn api_key_health_report(api: &ApiConfig) -> Vec<ApiKeyHealth> {
+    let now = std::time::Instant::now();
+    api_key_pool(api)
+        .iter()
+        .enumerate()
+        .map(|(idx, key_ref)| {
+            let health = api_key_health_state(&api.id, key_ref);
+            let cooldown_remaining_seconds = health
+                .cooldown_until
+                .map(|until| until.saturating_duration_since(now).as_secs())
+                .unwrap_or(0);
+            let status = if cooldown_remaining_seconds > 0 {
+                "coolingDown"
+            } else if health.last_error.is_some() {
+                "failing"
+            } else if health.last_used_at.is_some() {
+                "ok"
+            } else {
+                "unused"
+            };
+            ApiKeyHealth {
+                api_config_id: api.id.clone(),
+                key_index: idx,
+                key_label: if is_external_key_ref(key_ref) {
+                    key_ref.clone()
+                } else {
+                    format!("Key #{}", idx + 1)
+                },
+                status: status.to_string(),
+                cooldown_remaining_seconds,
+                last_status_code: health.last_status_code,
+                last_error: health.last_error,
+                last_used_at: health.last_used_at,
+                last_rate_limited_at: health.last_rate_limited_at,
+                success_count: health.success_count,
+                failure_count: health.failure_count,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,242 @@
+// 原子写入与每日滚动快照（config.toml / app_data.db）。
+
+const BACKUP_DIR_NAME: &str = "backups";
+const PRE_RESTORE_BACKUP_SUFFIX: &str = "pre-restore";
+const PRE_IMPORT_BACKUP_SUFFIX: &str = "pre-import";
+
+/// 写入同目录临时文件、fsync 后重命名覆盖目标，避免崩溃或断电留下截断的文件。
+fn write_file_atomically(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("Path has no parent directory: {}", path.display()))?;
+    fs::create_dir_all(parent).map_err(|err| format!("Create directory failed: {err}"))?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "data".to_string());
+    let tmp = parent.join(format!(".{file_name}.{}.tmp", Uuid::new_v4()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, path)?;
+        #[cfg(unix)]
+        fs::File::open(parent)?.sync_all()?;
+        Ok::<(), std::io::Error>(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Write {} failed: {err}", path.display()));
+    }
+    Ok(())
+}
+
+fn backup_root_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(BACKUP_DIR_NAME)
+}
+
+fn today_backup_id() -> String {
+    let now = now_utc();
+    format!(
+        "{:04}-{:02}-{:02}",
+        now.year(),
+        u8::from(now.month()),
+        now.day()
+    )
+}
+
+fn copy_into_backup(source: &Path, backup_dir: &Path) -> Result<(), String> {
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| format!("Path has no file name: {}", source.display()))?;
+    let raw = fs::read(source)
+        .map_err(|err| format!("Read {} for backup failed: {err}", source.display()))?;
+    write_file_atomically(&backup_dir.join(file_name), &raw)
+}
+
+/// 每天首次写入前，把当前文件复制到 `backups/<YYYY-MM-DD>/`，并只保留最近 N 份快照。
+fn ensure_daily_backup(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let root = backup_root_dir(path);
+    let backup_dir = root.join(today_backup_id());
+    let file_name = path.file_name().unwrap_or_default();
+    if backup_dir.join(file_name).exists() {
+        return Ok(());
+    }
+    copy_into_backup(path, &backup_dir)?;
+    prune_backups(&root, is_daily_backup_id, DAILY_BACKUP_KEEP_COUNT)
+}
+
+/// 每日快照的目录名就是日期 `YYYY-MM-DD`，操作前快照另带时间与后缀。
+fn is_daily_backup_id(id: &str) -> bool {
+    id.len() == 10
+        && id
+            .char_indices()
+            .all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+}
+
+fn list_backup_ids(root: &Path) -> Result<Vec<String>, String> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(root).map_err(|err| format!("Read backups failed: {err}"))?;
+    let mut ids = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    ids.sort();
+    Ok(ids)
+}
+
+/// 只在 `kind` 选中的快照里按时间淘汰，其他种类的快照不占用名额。
+fn prune_backups(root: &Path, kind: impl Fn(&str) -> bool, keep: usize) -> Result<(), String> {
+    let ids = list_backup_ids(root)?
+        .into_iter()
+        .filter(|id| kind(id))
+        .collect::<Vec<_>>();
+    if ids.len() <= keep {
+        return Ok(());
+    }
+    for id in &ids[..ids.len() - keep] {
+        fs::remove_dir_all(root.join(id))
+            .map_err(|err| format!("Remove old backup {id} failed: {err}"))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupFileInfo {
+    /// config | appData
+    kind: String,
+    file_name: String,
+    size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupEntry {
+    backup_id: String,
+    files: Vec<BackupFileInfo>,
+}
+
+fn backup_file_kind(file_name: &str, config_path: &Path, data_path: &Path) -> Option<&'static str> {
+    if Some(std::ffi::OsStr::new(file_name)) == config_path.file_name() {
+        Some("config")
+    } else if Some(std::ffi::OsStr::new(file_name)) == data_path.file_name() {
+        Some("appData")
+    } else {
+        None
+    }
+}
+
+/// 列出所有快照，最新的排在前面。
+fn list_backups_in(config_path: &Path, data_path: &Path) -> Result<Vec<BackupEntry>, String> {
+    let root = backup_root_dir(data_path);
+    let mut out = Vec::new();
+    for backup_id in list_backup_ids(&root)?.into_iter().rev() {
+        let dir = root.join(&backup_id);
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir)
+            .map_err(|err| format!("Read backup {backup_id} failed: {err}"))?
+            .flatten()
+        {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(kind) = backup_file_kind(&file_name, config_path, data_path) else {
+                continue;
+            };
+            files.push(BackupFileInfo {
+                kind: kind.to_string(),
+                size_bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                file_name,
+            });
+        }
+        files.sort_by(|a, b| a.kind.cmp(&b.kind));
+        out.push(BackupEntry { backup_id, files });
+    }
+    Ok(out)
+}
+
+fn timestamped_backup_dir(root: &Path, suffix: &str) -> PathBuf {
+    let now = now_utc();
+    root.join(format!(
+        "{}-{:02}{:02}{:02}-{suffix}",
+        today_backup_id(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    ))
+}
+
+/// 在覆盖性操作前把当前 config.toml / app_data.db 另存为 `<时间>-<suffix>` 快照。
+fn snapshot_current_files(config_path: &Path, data_path: &Path, suffix: &str) -> Result<(), String> {
+    let root = backup_root_dir(data_path);
+    let dir = timestamped_backup_dir(&root, suffix);
+    for path in [config_path, data_path] {
+        if path.exists() {
+            copy_into_backup(path, &dir)?;
+        }
+    }
+    prune_operation_backups(&root, suffix)
+}
+
+fn prune_operation_backups(root: &Path, suffix: &str) -> Result<(), String> {
+    let tail = format!("-{suffix}");
+    prune_backups(root, |id| id.ends_with(&tail), OPERATION_BACKUP_KEEP_COUNT)
+}
+
+/// 用快照覆盖当前文件。覆盖前先把当前文件另存为 `<时间>-pre-restore` 快照，便于撤销。
+/// `kinds` 为空时恢复快照中的全部文件。调用方需持有 state_lock。
+fn restore_backup_files(
+    config_path: &Path,
+    data_path: &Path,
+    backup_id: &str,
+    kinds: &[String],
+) -> Result<Vec<String>, String> {
+    let backup_id = backup_id.trim();
+    if backup_id.is_empty() || backup_id.contains(['/', '\\']) || backup_id.contains("..") {
+        return Err("Invalid backupId".to_string());
+    }
+    let root = backup_root_dir(data_path);
+    let dir = root.join(backup_id);
+    if !dir.is_dir() {
+        return Err("Backup not found".to_string());
+    }
+
+    let mut targets = Vec::new();
+    for (kind, path) in [("config", config_path), ("appData", data_path)] {
+        if !kinds.is_empty() && !kinds.iter().any(|k| k == kind) {
+            continue;
+        }
+        let source = dir.join(path.file_name().unwrap_or_default());
+        if source.exists() {
+            targets.push((kind, path, source));
+        }
+    }
+    if targets.is_empty() {
+        return Err("Backup has no matching files".to_string());
+    }
+
+    let pre_restore_dir = timestamped_backup_dir(&root, PRE_RESTORE_BACKUP_SUFFIX);
+    let mut restored = Vec::new();
+    for (kind, path, source) in targets {
+        if path.exists() {
+            copy_into_backup(path, &pre_restore_dir)?;
+        }
+        let raw = fs::read(&source).map_err(|err| format!("Read backup file failed: {err}"))?;
+        write_file_atomically(path, &raw)?;
+        restored.push(kind.to_string());
+    }
+    prune_operation_backups(&root, PRE_RESTORE_BACKUP_SUFFIX)?;
+    Ok(restored)
+}
@@ -0,0 +1,589 @@
+// 完整备份包：单个 zip 内含配置、智能体与头像、对话、归档、记忆和图转文缓存。
+
+const BACKUP_BUNDLE_FORMAT: &str = "easy-call-ai-bundle";
+const BACKUP_BUNDLE_VERSION: u32 = 1;
+const BUNDLE_MANIFEST_ENTRY: &str = "manifest.json";
+const BUNDLE_CONFIG_ENTRY: &str = "config.toml";
+const BUNDLE_SETTINGS_ENTRY: &str = "settings.json";
+const BUNDLE_AGENTS_ENTRY: &str = "agents.json";
+const BUNDLE_CONVERSATIONS_ENTRY: &str = "conversations.json";
+const BUNDLE_ARCHIVES_ENTRY: &str = "archives.json";
+const BUNDLE_MEMORIES_ENTRY: &str = "memories.json";
+const BUNDLE_IMAGE_TEXT_CACHE_ENTRY: &str = "image_text_cache.json";
+const BUNDLE_AVATAR_DIR: &str = "avatars/";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleCounts {
+    api_configs: usize,
+    agents: usize,
+    avatars: usize,
+    conversations: usize,
+    archives: usize,
+    memories: usize,
+    image_text_cache: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupBundleManifest {
+    format: String,
+    version: u32,
+    app_version: String,
+    app_data_schema_version: u32,
+    exported_at: String,
+    /// false 时配置中的 API key 已被清空（env:/file: 引用保留）。
+    includes_secrets: bool,
+    counts: BundleCounts,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupBundleSettings {
+    selected_agent_id: String,
+    user_alias: String,
+    response_style_id: String,
+    #[serde(default)]
+    response_styles: Vec<ResponseStylePreset>,
+}
+
+#[derive(Debug, Clone)]
+struct BackupBundle {
+    manifest: BackupBundleManifest,
+    config: Option<AppConfig>,
+    settings: Option<BackupBundleSettings>,
+    agents: Vec<AgentProfile>,
+    /// (文件名, 内容)，文件名对应 agents 中 avatar_path 的 `avatars/<name>`。
+    avatars: Vec<(String, Vec<u8>)>,
+    conversations: Vec<Conversation>,
+    archives: Vec<ConversationArchive>,
+    memories: Vec<MemoryEntry>,
+    image_text_cache: Vec<ImageTextCacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleConflict {
+    /// apiConfig | agent | conversation | archive
+    kind: String,
+    id: String,
+    label: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportBundleResult {
+    /// merge | replace
+    mode: String,
+    manifest: BackupBundleManifest,
+    imported: BundleCounts,
+    /// 合并时 id 相同但内容不同的条目，保留本地版本。
+    conflicts: Vec<BundleConflict>,
+    config_imported: bool,
+}
+
+fn bundle_avatar_file_name(path: &str) -> Option<String> {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| !n.is_empty() && !n.contains(['/', '\\']) && n != "..")
+}
+
+/// 导出用的配置副本：secret 引用替换为明文（include_secrets）或清空。
+fn bundle_config_for_export(
+    config: &AppConfig,
+    include_secrets: bool,
+    reveal: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<AppConfig, String> {
+    let mut config = config.clone();
+    for api in &mut config.api_configs {
+        let slots = std::iter::once(&mut api.api_key).chain(api.extra_api_keys.iter_mut());
+        for slot in slots {
+            if slot.trim().is_empty() || is_external_key_ref(slot) {
+                continue;
+            }
+            *slot = if include_secrets {
+                reveal(slot)?
+            } else {
+                String::new()
+            };
+        }
+        api.extra_api_keys.retain(|k| !k.trim().is_empty());
+    }
+    Ok(config)
+}
+
+fn write_bundle_entry<W: std::io::Write + std::io::Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    name: &str,
+    bytes: &[u8],
+) -> Result<(), String> {
+    use std::io::Write;
+
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(name, options)
+        .map_err(|err| format!("Write bundle entry {name} failed: {err}"))?;
+    zip.write_all(bytes)
+        .map_err(|err| format!("Write bundle entry {name} failed: {err}"))
+}
+
+fn write_bundle_json<W: std::io::Write + std::io::Seek, T: Serialize>(
+    zip: &mut zip::ZipWriter<W>,
+    name: &str,
+    value: &T,
+) -> Result<(), String> {
+    let body = serde_json::to_vec_pretty(value)
+        .map_err(|err| format!("Serialize bundle entry {name} failed: {err}"))?;
+    write_bundle_entry(zip, name, &body)
+}
+
+/// 生成备份包字节。附件从 blob 还原为 base64，头像改为包内相对路径。
+fn build_backup_bundle(
+    config: &AppConfig,
+    data: &AppData,
+    blob_dir: &Path,
+    include_secrets: bool,
+    reveal: &dyn Fn(&str) -> Result<String, String>,
+) -> Result<(Vec<u8>, BackupBundleManifest), String> {
+    let config = bundle_config_for_export(config, include_secrets, reveal)?;
+
+    let mut avatars = Vec::<(String, Vec<u8>)>::new();
+    let mut agents = data.agents.clone();
+    for agent in &mut agents {
+        let Some(path) = agent.avatar_path.clone() else {
+            continue;
+        };
+        let (Some(name), Ok(bytes)) = (bundle_avatar_file_name(&path), fs::read(&path)) else {
+            eprintln!(
+                "[BUNDLE] skip missing avatar: agent={}, path={path}",
+                agent.id
+            );
+            agent.avatar_path = None;
+            agent.avatar_updated_at = None;
+            continue;
+        };
+        agent.avatar_path = Some(format!("{BUNDLE_AVATAR_DIR}{name}"));
+        avatars.push((name, bytes));
+    }
+
+    let mut conversations = data.conversations.clone();
+    for conversation in &mut conversations {
+        hydrate_message_blobs(blob_dir, &mut conversation.messages);
+        hydrate_message_blobs(blob_dir, &mut conversation.alternate_messages);
+    }
+    let mut archives = data.archived_conversations.clone();
+    for archive in &mut archives {
+        let source = &mut archive.source_conversation;
+        hydrate_message_blobs(blob_dir, &mut source.messages);
+        hydrate_message_blobs(blob_dir, &mut source.alternate_messages);
+    }
+
+    let manifest = BackupBundleManifest {
+        format: BACKUP_BUNDLE_FORMAT.to_string(),
+        version: BACKUP_BUNDLE_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        app_data_schema_version: APP_DATA_SCHEMA_VERSION,
+        exported_at: now_iso(),
+        includes_secrets: include_secrets,
+        counts: BundleCounts {
+            api_configs: config.api_configs.len(),
+            agents: agents.len(),
+            avatars: avatars.len(),
+            conversations: conversations.len(),
+            archives: archives.len(),
+            memories: data.memories.len(),
+            image_text_cache: data.image_text_cache.len(),
+        },
+    };
+    let settings = BackupBundleSettings {
+        selected_agent_id: data.selected_agent_id.clone(),
+        user_alias: data.user_alias.clone(),
+        response_style_id: data.response_style_id.clone(),
+        response_styles: data.response_styles.clone(),
+    };
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::<u8>::new()));
+    write_bundle_json(&mut zip, BUNDLE_MANIFEST_ENTRY, &manifest)?;
+    let config_toml =
+        toml::to_string_pretty(&config).map_err(|err| format!("Serialize config failed: {err}"))?;
+    write_bundle_entry(&mut zip, BUNDLE_CONFIG_ENTRY, config_toml.as_bytes())?;
+    write_bundle_json(&mut zip, BUNDLE_SETTINGS_ENTRY, &settings)?;
+    write_bundle_json(&mut zip, BUNDLE_AGENTS_ENTRY, &agents)?;
+    for (name, bytes) in &avatars {
+        write_bundle_entry(&mut zip, &format!("{BUNDLE_AVATAR_DIR}{name}"), bytes)?;
+    }
+    write_bundle_json(&mut zip, BUNDLE_CONVERSATIONS_ENTRY, &conversations)?;
+    write_bundle_json(&mut zip, BUNDLE_ARCHIVES_ENTRY, &archives)?;
+    write_bundle_json(&mut zip, BUNDLE_MEMORIES_ENTRY, &data.memories)?;
+    write_bundle_json(
+        &mut zip,
+        BUNDLE_IMAGE_TEXT_CACHE_ENTRY,
+        &data.image_text_cache,
+    )?;
+    let bytes = zip
+        .finish()
+        .map_err(|err| format!("Finish bundle failed: {err}"))?
+        .into_inner();
+    Ok((bytes, manifest))
+}
+
+fn read_bundle_entry<R: std::io::Read + std::io::Seek>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Option<Vec<u8>>, String> {
+    use std::io::Read;
+
+    let mut file = match zip.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(format!("Read bundle entry {name} failed: {err}")),
+    };
+    let mut out = Vec::new();
+    file.read_to_end(&mut out)
+        .map_err(|err| format!("Read bundle entry {name} failed: {err}"))?;
+    Ok(Some(out))
+}
+
+fn read_bundle_json<R: std::io::Read + std::io::Seek, T: serde::de::DeserializeOwned + Default>(
+    zip: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<T, String> {
+    match read_bundle_entry(zip, name)? {
+        Some(raw) => serde_json::from_slice(&raw)
+            .map_err(|err| format!("Parse bundle entry {name} failed: {err}")),
+        None => Ok(T::default()),
+    }
+}
+
+fn read_backup_bundle(bytes: &[u8]) -> Result<BackupBundle, String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|err| format!("Open backup bundle failed: {err}"))?;
+    let manifest_raw = read_bundle_entry(&mut zip, BUNDLE_MANIFEST_ENTRY)?
+        .ok_or_else(|| "Backup bundle has no manifest.json".to_string())?;
+    let manifest = serde_json::from_slice::<BackupBundleManifest>(&manifest_raw)
+        .map_err(|err| format!("Parse bundle manifest failed: {err}"))?;
+    if manifest.format != BACKUP_BUNDLE_FORMAT {
+        return Err(format!("Unsupported bundle format: {}", manifest.format));
+    }
+    if manifest.version > BACKUP_BUNDLE_VERSION
+        || manifest.app_data_schema_version > APP_DATA_SCHEMA_VERSION
+    {
+        return Err(format!(
+            "Backup bundle was created by a newer app version ({}); please upgrade first.",
+            manifest.app_version
+        ));
+    }
+
+    let config = match read_bundle_entry(&mut zip, BUNDLE_CONFIG_ENTRY)? {
+        Some(raw) => {
+            let text = String::from_utf8(raw)
+                .map_err(|err| format!("Bundle config is not UTF-8: {err}"))?;
+            let mut config = toml::from_str::<AppConfig>(&text)
+                .map_err(|err| format!("Parse bundle config failed: {err}"))?;
+            normalize_app_config(&mut config);
+            Some(config)
+        }
+        None => None,
+    };
+    let settings = match read_bundle_entry(&mut zip, BUNDLE_SETTINGS_ENTRY)? {
+        Some(raw) => Some(
+            serde_json::from_slice::<BackupBundleSettings>(&raw)
+                .map_err(|err| format!("Parse bundle settings failed: {err}"))?,
+        ),
+        None => None,
+    };
+    let agents = read_bundle_json::<_, Vec<AgentProfile>>(&mut zip, BUNDLE_AGENTS_ENTRY)?;
+    let mut avatars = Vec::new();
+    for agent in &agents {
+        let Some(name) = agent
+            .avatar_path
+            .as_deref()
+            .and_then(|p| p.strip_prefix(BUNDLE_AVATAR_DIR))
+            .and_then(bundle_avatar_file_name)
+        else {
+            continue;
+        };
+        if let Some(bytes) = read_bundle_entry(&mut zip, &format!("{BUNDLE_AVATAR_DIR}{name}"))? {
+            avatars.push((name, bytes));
+        }
+    }
+    Ok(BackupBundle {
+        config,
+        settings,
+        agents,
+        avatars,
+        conversations: read_bundle_json(&mut zip, BUNDLE_CONVERSATIONS_ENTRY)?,
+        archives: read_bundle_json(&mut zip, BUNDLE_ARCHIVES_ENTRY)?,
+        memories: read_bundle_json(&mut zip, BUNDLE_MEMORIES_ENTRY)?,
+        image_text_cache: read_bundle_json(&mut zip, BUNDLE_IMAGE_TEXT_CACHE_ENTRY)?,
+        manifest,
+    })
+}
+
+/// 把包内头像写入本地头像目录，并把 avatar_path 改回绝对路径。
+fn install_bundle_avatars(
+    agents: &mut [AgentProfile],
+    avatars: &[(String, Vec<u8>)],
+    avatar_dir: &Path,
+) -> Result<usize, String> {
+    let mut installed = 0usize;
+    for agent in agents.iter_mut() {
+        let Some(name) = agent
+            .avatar_path
+            .as_deref()
+            .and_then(|p| p.strip_prefix(BUNDLE_AVATAR_DIR))
+            .map(ToOwned::to_owned)
+        else {
+            continue;
+        };
+        let Some((_, bytes)) = avatars.iter().find(|(n, _)| *n == name) else {
+            agent.avatar_path = None;
+            agent.avatar_updated_at = None;
+            continue;
+        };
+        let path = avatar_dir.join(&name);
+        write_file_atomically(&path, bytes)?;
+        agent.avatar_path = Some(path.to_string_lossy().to_string());
+        installed += 1;
+    }
+    Ok(installed)
+}
+
+fn same_json<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// 按 id 合并：新条目加入，内容相同的跳过，id 相同但内容不同的记为冲突并保留本地版本。
+fn merge_by_id<T: Serialize + Clone>(
+    existing: &mut Vec<T>,
+    incoming: Vec<T>,
+    kind: &str,
+    id_of: impl Fn(&T) -> String,
+    label_of: impl Fn(&T) -> String,
+    conflicts: &mut Vec<BundleConflict>,
+) -> Vec<T> {
+    let mut added = Vec::new();
+    for item in incoming {
+        let id = id_of(&item);
+        match existing.iter().find(|e| id_of(e) == id) {
+            Some(current) if same_json(current, &item) => {}
+            Some(_) => conflicts.push(BundleConflict {
+                kind: kind.to_string(),
+                id,
+                label: label_of(&item),
+            }),
+            None => {
+                existing.push(item.clone());
+                added.push(item);
+            }
+        }
+    }
+    added
+}
+
+/// 未含密钥的备包中 key 已被清空（env:/file: 引用除外），用本机同 id 配置的 key 补回。
+fn keep_local_api_keys(config: &mut AppConfig, local: &AppConfig) {
+    for api in &mut config.api_configs {
+        let Some(current) = local.api_configs.iter().find(|a| a.id == api.id) else {
+            continue;
+        };
+        if api.api_key.trim().is_empty() {
+            api.api_key = current.api_key.clone();
+        }
+        for key in &current.extra_api_keys {
+            if !is_external_key_ref(key) && !api.extra_api_keys.contains(key) {
+                api.extra_api_keys.push(key.clone());
+            }
+        }
+    }
+}
+
+fn merge_backup_bundle(
+    config: &mut AppConfig,
+    data: &mut AppData,
+    bundle: BackupBundle,
+    avatar_dir: &Path,
+) -> Result<ImportBundleResult, String> {
+    let manifest = bundle.manifest.clone();
+    let mut conflicts = Vec::new();
+    let mut imported = BundleCounts::default();
+
+    if let Some(incoming) = bundle.config {
+        let added = merge_by_id(
+            &mut config.api_configs,
+            incoming.api_configs,
+            "apiConfig",
+            |a| a.id.clone(),
+            |a| a.name.clone(),
+            &mut conflicts,
+        );
+        imported.api_configs = added.len();
+    }
+
+    let mut incoming_agents = bundle.agents;
+    // 冲突比较前先把头像路径还原为本地路径，避免仅因路径不同被判为冲突。
+    for agent in &mut incoming_agents {
+        let Some(name) = agent
+            .avatar_path
+            .as_deref()
+            .and_then(|p| p.strip_prefix(BUNDLE_AVATAR_DIR))
+        else {
+            continue;
+        };
+        agent.avatar_path = Some(avatar_dir.join(name).to_string_lossy().to_string());
+    }
+    let mut added_agents = merge_by_id(
+        &mut data.agents,
+        incoming_agents,
+        "agent",
+        |a| a.id.clone(),
+        |a| a.name.clone(),
+        &mut conflicts,
+    );
+    imported.agents = added_agents.len();
+    for agent in &mut added_agents {
+        let Some(name) = agent
+            .avatar_path
+            .as_deref()
+            .and_then(bundle_avatar_file_name)
+        else {
+            continue;
+        };
+        agent.avatar_path = Some(format!("{BUNDLE_AVATAR_DIR}{name}"));
+    }
+    imported.avatars = install_bundle_avatars(&mut added_agents, &bundle.avatars, avatar_dir)?;
+    for agent in added_agents {
+        if let Some(slot) = data.agents.iter_mut().find(|a| a.id == agent.id) {
+            *slot = agent;
+        }
+    }
+
+    if let Some(settings) = bundle.settings {
+        merge_by_id(
+            &mut data.response_styles,
+            normalize_custom_response_styles(settings.response_styles),
+            "responseStyle",
+            |s| s.id.clone(),
+            |s| s.name.clone(),
+            &mut conflicts,
+        );
+    }
+
+    imported.conversations = merge_by_id(
+        &mut data.conversations,
+        bundle.conversations,
+        "conversation",
+        |c| c.id.clone(),
+        |c| c.title.clone(),
+        &mut conflicts,
+    )
+    .len();
+    imported.archives = merge_by_id(
+        &mut data.archived_conversations,
+        bundle.archives,
+        "archive",
+        |a| a.archive_id.clone(),
+        |a| a.source_conversation.title.clone(),
+        &mut conflicts,
+    )
+    .len();
+
+    for memory in bundle.memories {
+        let key = memory_content_key(&memory.content);
+        if key.is_empty() {
+            continue;
+        }
+        match data
+            .memories
+            .iter_mut()
+            .find(|m| memory_content_key(&m.content) == key)
+        {
+            Some(existing) => {
+                for kw in memory.keywords {
+                    if !existing.keywords.contains(&kw) {
+                        existing.keywords.push(kw);
+                    }
+                }
+            }
+            None => {
+                data.memories.push(memory);
+                imported.memories += 1;
+            }
+        }
+    }
+
+    for entry in bundle.image_text_cache {
+        if find_image_text_cache(data, &entry.hash, &entry.vision_api_id).is_none() {
+            data.image_text_cache.push(entry);
+            imported.image_text_cache += 1;
+        }
+    }
+
+    Ok(ImportBundleResult {
+        mode: "merge".to_string(),
+        manifest,
+        config_imported: imported.api_configs > 0,
+        imported,
+        conflicts,
+    })
+}
+
+fn replace_with_backup_bundle(
+    config: &mut AppConfig,
+    data: &mut AppData,
+    bundle: BackupBundle,
+    avatar_dir: &Path,
+) -> Result<ImportBundleResult, String> {
+    let manifest = bundle.manifest.clone();
+    let config_imported = bundle.config.is_some();
+    if let Some(mut incoming) = bundle.config {
+        if !manifest.includes_secrets {
+            keep_local_api_keys(&mut incoming, config);
+        }
+        *config = incoming;
+    }
+
+    let mut agents = bundle.agents;
+    let avatars = install_bundle_avatars(&mut agents, &bundle.avatars, avatar_dir)?;
+    let settings = bundle.settings.unwrap_or(BackupBundleSettings {
+        selected_agent_id: default_selected_agent_id(),
+        user_alias: default_user_alias(),
+        response_style_id: default_response_style_id(),
+        response_styles: Vec::new(),
+    });
+    let imported = BundleCounts {
+        api_configs: if config_imported {
+            config.api_configs.len()
+        } else {
+            0
+        },
+        agents: agents.len(),
+        avatars,
+        conversations: bundle.conversations.len(),
+        archives: bundle.archives.len(),
+        memories: bundle.memories.len(),
+        image_text_cache: bundle.image_text_cache.len(),
+    };
+    *data = AppData {
+        version: APP_DATA_SCHEMA_VERSION,
+        agents,
+        selected_agent_id: settings.selected_agent_id,
+        user_alias: settings.user_alias,
+        response_style_id: settings.response_style_id,
+        response_styles: normalize_custom_response_styles(settings.response_styles),
+        conversations: bundle.conversations,
+        archived_conversations: bundle.archives,
+        image_text_cache: bundle.image_text_cache,
+        memories: bundle.memories,
+    };
+    ensure_default_agent(data);
+
+    Ok(ImportBundleResult {
+        mode: "replace".to_string(),
+        manifest,
+        imported,
+        conflicts: Vec::new(),
+        config_imported,
+    })
+}
@@ -0,0 +1,259 @@
+// 数据目录定位：命令行 / 环境变量覆盖、便携模式，以及迁移数据目录。
+
+const DATA_DIR_FLAG: &str = "--data-dir";
+const PORTABLE_FLAG: &str = "--portable";
+const DATA_DIR_ENV: &str = "EASY_CALL_AI_DATA_DIR";
+/// 可执行文件旁存在该文件时进入便携模式，数据放在 `<exe 目录>/data`。
+const PORTABLE_MARKER_FILE_NAME: &str = "portable.flag";
+const PORTABLE_DATA_DIR_NAME: &str = "data";
+/// 迁移数据目录后写在默认目录里的指向文件，内容为新目录的绝对路径。
+const DATA_DIR_REDIRECT_FILE_NAME: &str = "data-dir.txt";
+/// 截图工具默认保存截图的子目录。
+const SCREENSHOT_DIR_NAME: &str = "screenshots";
+/// 数据目录中由本应用创建的子目录；迁移只复制、删除这些目录和下面的文件。
+const APP_OWNED_DATA_DIR_NAMES: &[&str] =
+    &["avatars", "blobs", BACKUP_DIR_NAME, SCREENSHOT_DIR_NAME];
+/// 本应用数据文件名，连同以其开头的衍生文件（`-journal`、`.corrupt`、`.migrated` 等）。
+const APP_OWNED_DATA_FILE_PREFIXES: &[&str] = &[
+    "config.toml",
+    "app_data.db",
+    "app_data.json",
+    SECRET_STORE_FILE_NAME,
+    SECRET_KEY_FILE_NAME,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DataDirLocation {
+    dir: PathBuf,
+    /// cliFlag | env | portable | redirect | default
+    source: String,
+    default_dir: PathBuf,
+}
+
+impl DataDirLocation {
+    /// 由命令行、环境变量或便携模式指定时，不能在应用内迁移。
+    fn is_fixed(&self) -> bool {
+        matches!(self.source.as_str(), "cliFlag" | "env" | "portable")
+    }
+}
+
+fn absolute_path(raw: &str) -> Option<PathBuf> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    std::path::absolute(raw).ok()
+}
+
+fn data_dir_flag_value(args: &[String]) -> Option<String> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == DATA_DIR_FLAG {
+            return iter.next().cloned();
+        }
+        if let Some(value) = arg
+            .strip_prefix(DATA_DIR_FLAG)
+            .and_then(|v| v.strip_prefix('='))
+        {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// 优先级：`--data-dir` > `EASY_CALL_AI_DATA_DIR` > 便携模式 > 迁移指向文件 > 系统默认目录。
+fn resolve_data_dir_from(
+    args: &[String],
+    env_dir: Option<String>,
+    exe_dir: Option<&Path>,
+    default_dir: &Path,
+) -> DataDirLocation {
+    let location = |dir: PathBuf, source: &str| DataDirLocation {
+        dir,
+        source: source.to_string(),
+        default_dir: default_dir.to_path_buf(),
+    };
+    if let Some(dir) = data_dir_flag_value(args).as_deref().and_then(absolute_path) {
+        return location(dir, "cliFlag");
+    }
+    if let Some(dir) = env_dir.as_deref().and_then(absolute_path) {
+        return location(dir, "env");
+    }
+    if let Some(exe_dir) = exe_dir {
+        if args.iter().any(|a| a == PORTABLE_FLAG)
+            || exe_dir.join(PORTABLE_MARKER_FILE_NAME).is_file()
+        {
+            return location(exe_dir.join(PORTABLE_DATA_DIR_NAME), "portable");
+        }
+    }
+    let redirect = fs::read_to_string(default_dir.join(DATA_DIR_REDIRECT_FILE_NAME))
+        .ok()
+        .and_then(|raw| absolute_path(&raw));
+    if let Some(dir) = redirect {
+        return location(dir, "redirect");
+    }
+    location(default_dir.to_path_buf(), "default")
+}
+
+fn app_data_dir() -> Result<&'static DataDirLocation, String> {
+    static LOCATION: OnceLock<DataDirLocation> = OnceLock::new();
+    if let Some(location) = LOCATION.get() {
+        return Ok(location);
+    }
+    let project_dirs = ProjectDirs::from("ai", "easycall", "easy-call-ai")
+        .ok_or_else(|| "Failed to resolve config directory".to_string())?;
+    let args = std::env::args().collect::<Vec<_>>();
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let location = resolve_data_dir_from(
+        &args,
+        std::env::var(DATA_DIR_ENV).ok(),
+        exe_dir.as_deref(),
+        project_dirs.config_dir(),
+    );
+    Ok(LOCATION.get_or_init(|| location))
+}
+
+/// 数据目录顶层的条目是否由本应用创建。用户放在同一目录里的其他文件不在此列。
+fn is_app_owned_data_entry(entry: &fs::DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy().to_string();
+    if entry.path().is_dir() {
+        APP_OWNED_DATA_DIR_NAMES.contains(&name.as_str())
+    } else {
+        APP_OWNED_DATA_FILE_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+    }
+}
+
+/// 目录顶层中本应用的条目，目录不存在时为空。
+fn app_owned_data_entries(dir: &Path) -> Result<Vec<fs::DirEntry>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(dir).map_err(|err| format!("Read directory failed: {err}"))?;
+    Ok(entries.flatten().filter(is_app_owned_data_entry).collect())
+}
+
+fn remove_data_entry(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+fn copy_dir_recursive(
+    from: &Path,
+    to: &Path,
+    include: &dyn Fn(&fs::DirEntry) -> bool,
+) -> Result<(), String> {
+    fs::create_dir_all(to).map_err(|err| format!("Create directory failed: {err}"))?;
+    let entries = fs::read_dir(from)
+        .map_err(|err| format!("Read directory {} failed: {err}", from.display()))?;
+    for entry in entries.flatten() {
+        if !include(&entry) {
+            continue;
+        }
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        if source.is_dir() {
+            copy_dir_recursive(&source, &target, &|_| true)?;
+        } else {
+            let copied = fs::copy(&source, &target)
+                .map_err(|err| format!("Copy {} failed: {err}", source.display()))?;
+            let expected = entry.metadata().map(|m| m.len()).unwrap_or(copied);
+            if copied != expected {
+                return Err(format!("Copy {} was incomplete", source.display()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 把数据目录中本应用的文件复制到新位置、校验后再删除旧文件，并在默认目录写入指向文件。
+/// 同目录下的其他文件不复制也不删除。复制失败时清理已复制的内容，旧数据保持不动。
+/// 调用方需持有 state_lock，完成后需重启应用。
+fn move_data_dir_contents(location: &DataDirLocation, target: &Path) -> Result<(), String> {
+    let source = &location.dir;
+    if !target.is_absolute() {
+        return Err("Target data directory must be an absolute path.".to_string());
+    }
+    if target == source.as_path() {
+        return Err("Target data directory is the current data directory.".to_string());
+    }
+    if target.starts_with(source) || source.starts_with(target) {
+        return Err(
+            "Target data directory cannot contain or be inside the current one.".to_string(),
+        );
+    }
+    if !app_owned_data_entries(target)?.is_empty() {
+        return Err(format!(
+            "Target data directory already contains app data: {}",
+            target.display()
+        ));
+    }
+
+    let created_target = !target.exists();
+    if source.exists() {
+        if let Err(err) = copy_dir_recursive(source, target, &is_app_owned_data_entry) {
+            if created_target {
+                let _ = fs::remove_dir_all(target);
+            } else {
+                for entry in app_owned_data_entries(target).unwrap_or_default() {
+                    let _ = remove_data_entry(&entry.path());
+                }
+            }
+            return Err(err);
+        }
+    } else {
+        fs::create_dir_all(target).map_err(|err| format!("Create directory failed: {err}"))?;
+    }
+
+    let redirect_path = location.default_dir.join(DATA_DIR_REDIRECT_FILE_NAME);
+    if target == location.default_dir.as_path() {
+        if redirect_path.exists() {
+            fs::remove_file(&redirect_path)
+                .map_err(|err| format!("Remove data directory redirect failed: {err}"))?;
+        }
+    } else {
+        write_file_atomically(&redirect_path, target.to_string_lossy().as_bytes())?;
+    }
+
+    for entry in app_owned_data_entries(source)? {
+        let path = entry.path();
+        if let Err(err) = remove_data_entry(&path) {
+            eprintln!(
+                "[STORAGE] remove old data entry failed: {}, err={err}",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 头像以绝对路径保存，迁移后把旧目录前缀替换为新目录。
+fn rebase_avatar_paths(data_path: &PathBuf, from: &Path, to: &Path) -> Result<(), String> {
+    if !data_path.exists() {
+        return Ok(());
+    }
+    let mut data = read_app_data(data_path)?;
+    let mut changed = false;
+    for agent in &mut data.agents {
+        let Some(rest) = agent
+            .avatar_path
+            .as_deref()
+            .and_then(|p| Path::new(p).strip_prefix(from).ok())
+        else {
+            continue;
+        };
+        agent.avatar_path = Some(to.join(rest).to_string_lossy().to_string());
+        changed = true;
+    }
+    if changed {
+        write_app_data(data_path, &data)?;
+    }
+    Ok(())
+}
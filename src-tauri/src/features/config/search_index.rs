@@ -0,0 +1,675 @@
+// 历史全文检索：消息文本、工具结果与归档摘要的倒排索引，随消息写入增量维护。
+// 中日韩文字按单字 + 相邻二字切分，其他文字按字母数字连续串切分。
+
+const SEARCH_INDEX_VERSION: &str = "1";
+/// 单条文档最多索引的字符数，过长的工具结果只取开头。
+const SEARCH_DOC_MAX_CHARS: usize = 20_000;
+const SEARCH_SNIPPET_CONTEXT_CHARS: usize = 40;
+const SEARCH_DEFAULT_LIMIT: usize = 50;
+const SEARCH_MAX_LIMIT: usize = 200;
+/// 归档摘要使用的序号，排在所有消息之前。
+const SEARCH_SUMMARY_SEQ: i64 = -1;
+/// 同一条消息拆成两份文档：正文与工具调用记录，便于按角色过滤。
+const SEARCH_PART_TEXT: i64 = 0;
+const SEARCH_PART_TOOL: i64 = 1;
+const SEARCH_BM25_K1: f64 = 1.2;
+const SEARCH_BM25_B: f64 = 0.75;
+const SEARCH_MIN_MATCH_PERCENT: usize = 60;
+
+fn is_cjk_search_char(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x3040..=0x30FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xAC00..=0xD7AF
+            | 0xF900..=0xFAFF
+            | 0x20000..=0x2A6DF
+    )
+}
+
+/// 切词。index 模式下中日韩文字同时产出单字和二字词；查询模式只用二字词，
+/// 单字查询才退回单字，以免短词命中过多。
+fn search_tokens(text: &str, index_mode: bool) -> Vec<String> {
+    fn flush_cjk(run: &mut Vec<char>, index_mode: bool, out: &mut Vec<String>) {
+        if index_mode || run.len() == 1 {
+            out.extend(run.iter().map(|c| c.to_string()));
+        }
+        out.extend(run.windows(2).map(|w| w.iter().collect::<String>()));
+        run.clear();
+    }
+    fn flush_word(word: &mut String, out: &mut Vec<String>) {
+        if !word.is_empty() {
+            out.push(std::mem::take(word));
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut word = String::new();
+    let mut run = Vec::<char>::new();
+    for ch in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk_search_char(ch) {
+            flush_word(&mut word, &mut out);
+            run.push(ch);
+        } else if ch.is_alphanumeric() {
+            flush_cjk(&mut run, index_mode, &mut out);
+            word.push(ch);
+        } else {
+            flush_word(&mut word, &mut out);
+            flush_cjk(&mut run, index_mode, &mut out);
+        }
+    }
+    flush_word(&mut word, &mut out);
+    flush_cjk(&mut run, index_mode, &mut out);
+    out
+}
+
+fn search_query_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::<String>::new();
+    for token in search_tokens(query, false) {
+        if !terms.contains(&token) {
+            terms.push(token);
+        }
+    }
+    terms
+}
+
+/// 工具调用记录的可检索文本：调用的工具名与参数、工具返回内容。
+fn tool_events_search_text(message: &ChatMessage) -> String {
+    let mut lines = Vec::<String>::new();
+    for event in message.tool_call.iter().flatten() {
+        for call in event
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = call
+                .pointer("/function/name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let args = call
+                .pointer("/function/arguments")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            lines.push(format!("{name} {args}").trim().to_string());
+        }
+        if event.get("role").and_then(Value::as_str) == Some("tool") {
+            if let Some(content) = event.get("content").and_then(Value::as_str) {
+                lines.push(content.trim().to_string());
+            }
+        }
+    }
+    lines.retain(|line| !line.is_empty());
+    lines.join("\n")
+}
+
+fn delete_search_docs_from(
+    tx: &rusqlite::Transaction<'_>,
+    owner_kind: &str,
+    owner_id: &str,
+    from_seq: i64,
+) -> Result<(), String> {
+    for sql in [
+        "DELETE FROM search_postings WHERE owner_kind = ?1 AND owner_id = ?2 AND seq >= ?3",
+        "DELETE FROM search_docs WHERE owner_kind = ?1 AND owner_id = ?2 AND seq >= ?3",
+    ] {
+        tx.prepare_cached(sql)
+            .and_then(|mut stmt| stmt.execute(rusqlite::params![owner_kind, owner_id, from_seq]))
+            .map_err(sqlite_err("Delete search index"))?;
+    }
+    Ok(())
+}
+
+struct SearchDocInput<'a> {
+    owner_kind: &'a str,
+    owner_id: &'a str,
+    seq: i64,
+    part: i64,
+    message_id: &'a str,
+    role: &'a str,
+    created_at: &'a str,
+    text: &'a str,
+}
+
+fn index_search_doc(tx: &rusqlite::Transaction<'_>, doc: SearchDocInput<'_>) -> Result<(), String> {
+    let key = rusqlite::params![doc.owner_kind, doc.owner_id, doc.seq, doc.part];
+    tx.prepare_cached(
+        "DELETE FROM search_postings WHERE owner_kind = ?1 AND owner_id = ?2 AND seq = ?3 AND part = ?4",
+    )
+    .and_then(|mut stmt| stmt.execute(key))
+    .map_err(sqlite_err("Delete search index"))?;
+    tx.prepare_cached(
+        "DELETE FROM search_docs WHERE owner_kind = ?1 AND owner_id = ?2 AND seq = ?3 AND part = ?4",
+    )
+    .and_then(|mut stmt| stmt.execute(key))
+    .map_err(sqlite_err("Delete search index"))?;
+
+    let text = doc.text.trim();
+    if text.is_empty() {
+        return Ok(());
+    }
+    let text = text.chars().take(SEARCH_DOC_MAX_CHARS).collect::<String>();
+    let tokens = search_tokens(&text, true);
+    if tokens.is_empty() {
+        return Ok(());
+    }
+    let mut term_freq = std::collections::HashMap::<&str, i64>::new();
+    for token in &tokens {
+        *term_freq.entry(token.as_str()).or_default() += 1;
+    }
+    tx.prepare_cached(
+        "INSERT INTO search_docs (owner_kind, owner_id, seq, part, message_id, role, created_at, term_count, text)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )
+    .and_then(|mut stmt| {
+        stmt.execute(rusqlite::params![
+            doc.owner_kind,
+            doc.owner_id,
+            doc.seq,
+            doc.part,
+            doc.message_id,
+            doc.role,
+            doc.created_at,
+            tokens.len() as i64,
+            text
+        ])
+    })
+    .map_err(sqlite_err("Write search index"))?;
+    let mut insert = tx
+        .prepare_cached(
+            "INSERT INTO search_postings (term, owner_kind, owner_id, seq, part, tf)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .map_err(sqlite_err("Write search index"))?;
+    for (term, tf) in term_freq {
+        insert
+            .execute(rusqlite::params![
+                term,
+                doc.owner_kind,
+                doc.owner_id,
+                doc.seq,
+                doc.part,
+                tf
+            ])
+            .map_err(sqlite_err("Write search index"))?;
+    }
+    Ok(())
+}
+
+fn index_message_search_docs(
+    tx: &rusqlite::Transaction<'_>,
+    owner_kind: &str,
+    owner_id: &str,
+    seq: i64,
+    message: &ChatMessage,
+) -> Result<(), String> {
+    let text = message
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            MessagePart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let tool_text = tool_events_search_text(message);
+    for (part, role, text) in [
+        (SEARCH_PART_TEXT, message.role.as_str(), text.as_str()),
+        (SEARCH_PART_TOOL, "tool", tool_text.as_str()),
+    ] {
+        index_search_doc(
+            tx,
+            SearchDocInput {
+                owner_kind,
+                owner_id,
+                seq,
+                part,
+                message_id: &message.id,
+                role,
+                created_at: &message.created_at,
+                text,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn index_archive_summary_search_doc(
+    tx: &rusqlite::Transaction<'_>,
+    archive: &ConversationArchive,
+) -> Result<(), String> {
+    index_search_doc(
+        tx,
+        SearchDocInput {
+            owner_kind: MESSAGE_OWNER_ARCHIVE,
+            owner_id: &archive.archive_id,
+            seq: SEARCH_SUMMARY_SEQ,
+            part: SEARCH_PART_TEXT,
+            message_id: "",
+            role: "summary",
+            created_at: &archive.archived_at,
+            text: &archive.summary,
+        },
+    )
+}
+
+/// 索引版本不符（新库或旧版本升级）时按已保存的消息整体重建。
+fn ensure_search_index(conn: &mut rusqlite::Connection) -> Result<(), String> {
+    use rusqlite::OptionalExtension;
+
+    let version = conn
+        .query_row(
+            "SELECT value FROM meta WHERE key = 'searchIndexVersion'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(sqlite_err("Read app database meta"))?;
+    if version.as_deref() == Some(SEARCH_INDEX_VERSION) {
+        return Ok(());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(sqlite_err("Begin app database transaction"))?;
+    tx.execute_batch("DELETE FROM search_postings; DELETE FROM search_docs;")
+        .map_err(sqlite_err("Clear search index"))?;
+    let mut docs = 0usize;
+    {
+        let mut stmt = tx
+            .prepare("SELECT owner_kind, owner_id, seq, body FROM messages")
+            .map_err(sqlite_err("Read messages"))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(sqlite_err("Read messages"))?;
+        for row in rows {
+            let (owner_kind, owner_id, seq, body) = row.map_err(sqlite_err("Read messages"))?;
+            let message = from_json_text::<ChatMessage>(&body)?;
+            index_message_search_docs(&tx, &owner_kind, &owner_id, seq, &message)?;
+            docs += 1;
+        }
+    }
+    for body in read_ordered_bodies(&tx, "SELECT body FROM archives ORDER BY position")? {
+        index_archive_summary_search_doc(&tx, &from_json_text::<ConversationArchive>(&body)?)?;
+    }
+    tx.execute(
+        "INSERT INTO meta (key, value) VALUES ('searchIndexVersion', ?1)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        [SEARCH_INDEX_VERSION],
+    )
+    .map_err(sqlite_err("Write app database meta"))?;
+    tx.commit()
+        .map_err(sqlite_err("Commit app database transaction"))?;
+    eprintln!("[SEARCH] rebuilt search index from {docs} messages");
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchHistoryInput {
+    query: String,
+    #[serde(default)]
+    agent_id: Option<String>,
+    /// user | assistant | tool | summary
+    #[serde(default)]
+    role: Option<String>,
+    /// RFC3339 时间或 YYYY-MM-DD（按 UTC 整天计算），包含边界。
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchHighlight {
+    /// snippet 内的 UTF-16 偏移，便于前端直接切片。
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchHit {
+    /// conversation | archive
+    source: String,
+    conversation_id: String,
+    archive_id: Option<String>,
+    /// 归档摘要命中时为空。
+    message_id: Option<String>,
+    role: String,
+    created_at: String,
+    agent_id: String,
+    title: String,
+    snippet: String,
+    highlights: Vec<SearchHighlight>,
+    score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchHistoryResult {
+    total: usize,
+    hits: Vec<SearchHit>,
+}
+
+/// 日期过滤边界；纯日期的结束边界取当天最后一刻。
+fn parse_search_date_bound(
+    raw: Option<&str>,
+    end_of_day: bool,
+) -> Result<Option<OffsetDateTime>, String> {
+    let Some(raw) = raw.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if let Some(parsed) = parse_iso(raw) {
+        return Ok(Some(parsed));
+    }
+    let invalid = || format!("Invalid date filter '{raw}'. Use YYYY-MM-DD or RFC3339.");
+    let mut fields = raw.splitn(3, '-').map(|v| v.parse::<i32>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+        (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid());
+    };
+    let month = u8::try_from(month)
+        .ok()
+        .and_then(|m| time::Month::try_from(m).ok())
+        .ok_or_else(invalid)?;
+    let day = u8::try_from(day).map_err(|_| invalid())?;
+    let date = time::Date::from_calendar_date(year, month, day).map_err(|_| invalid())?;
+    let time = if end_of_day {
+        time::Time::from_hms_nano(23, 59, 59, 999_999_999).unwrap_or(time::Time::MIDNIGHT)
+    } else {
+        time::Time::MIDNIGHT
+    };
+    Ok(Some(date.with_time(time).assume_utc()))
+}
+
+/// 截取首个命中附近的片段并标出所有命中位置。优先按查询原词匹配，找不到再按切分后的词匹配。
+fn build_search_snippet(text: &str, query: &str) -> (String, Vec<SearchHighlight>) {
+    let chars = text
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect::<Vec<_>>();
+    let lower = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect::<Vec<_>>();
+    let mark = |needles: Vec<String>| {
+        let mut marks = vec![false; chars.len()];
+        for needle in needles {
+            let needle = needle.chars().collect::<Vec<_>>();
+            if needle.is_empty() || needle.len() > lower.len() {
+                continue;
+            }
+            for start in 0..=(lower.len() - needle.len()) {
+                if lower[start..start + needle.len()] == needle[..] {
+                    marks[start..start + needle.len()].fill(true);
+                }
+            }
+        }
+        marks
+    };
+    let words = query
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>();
+    let mut marks = mark(words);
+    if !marks.contains(&true) {
+        marks = mark(search_query_terms(query));
+    }
+
+    let first = marks.iter().position(|m| *m).unwrap_or(0);
+    let start = first.saturating_sub(SEARCH_SNIPPET_CONTEXT_CHARS);
+    let end = (first + SEARCH_SNIPPET_CONTEXT_CHARS * 2).min(chars.len());
+    let mut snippet = String::new();
+    let mut highlights = Vec::<SearchHighlight>::new();
+    let mut offset = 0usize;
+    if start > 0 {
+        snippet.push('…');
+        offset += 1;
+    }
+    for idx in start..end {
+        let len = chars[idx].len_utf16();
+        if marks[idx] {
+            match highlights.last_mut() {
+                Some(last) if last.end == offset => last.end += len,
+                _ => highlights.push(SearchHighlight {
+                    start: offset,
+                    end: offset + len,
+                }),
+            }
+        }
+        snippet.push(chars[idx]);
+        offset += len;
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    (snippet, highlights)
+}
+
+struct SearchOwnerInfo {
+    conversation_id: String,
+    archive_id: Option<String>,
+    agent_id: String,
+    title: String,
+}
+
+fn read_search_owner_infos(
+    conn: &rusqlite::Connection,
+) -> Result<std::collections::HashMap<(String, String), SearchOwnerInfo>, String> {
+    let mut owners = std::collections::HashMap::new();
+    for body in read_ordered_bodies(conn, "SELECT body FROM conversations ORDER BY position")? {
+        let conversation = from_json_text::<Conversation>(&body)?;
+        owners.insert(
+            (
+                MESSAGE_OWNER_CONVERSATION.to_string(),
+                conversation.id.clone(),
+            ),
+            SearchOwnerInfo {
+                conversation_id: conversation.id,
+                archive_id: None,
+                agent_id: conversation.agent_id,
+                title: conversation.title,
+            },
+        );
+    }
+    for body in read_ordered_bodies(conn, "SELECT body FROM archives ORDER BY position")? {
+        let archive = from_json_text::<ConversationArchive>(&body)?;
+        owners.insert(
+            (
+                MESSAGE_OWNER_ARCHIVE.to_string(),
+                archive.archive_id.clone(),
+            ),
+            SearchOwnerInfo {
+                conversation_id: archive.source_conversation.id,
+                archive_id: Some(archive.archive_id),
+                agent_id: archive.source_conversation.agent_id,
+                title: archive.source_conversation.title,
+            },
+        );
+    }
+    Ok(owners)
+}
+
+/// 按 BM25 排序。中文查询切出的跨词二字组（如“北京天气”里的“京天”）未必出现在原文，
+/// 所以只要求命中六成以上的查询词，并按命中比例折算分数。
+fn search_history_in_db(
+    conn: &mut rusqlite::Connection,
+    input: &SearchHistoryInput,
+) -> Result<SearchHistoryResult, String> {
+    type DocKey = (String, String, i64, i64);
+
+    let terms = search_query_terms(&input.query);
+    if terms.is_empty() {
+        return Ok(SearchHistoryResult {
+            total: 0,
+            hits: Vec::new(),
+        });
+    }
+    let from = parse_search_date_bound(input.from.as_deref(), false)?;
+    let to = parse_search_date_bound(input.to.as_deref(), true)?;
+    let role = input
+        .role
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let agent_id = input
+        .agent_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    ensure_search_index(conn)?;
+
+    let (doc_count, avg_len) = conn
+        .query_row(
+            "SELECT COUNT(*), COALESCE(AVG(term_count), 0) FROM search_docs",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .map_err(sqlite_err("Read search index"))?;
+    let mut matches = std::collections::HashMap::<DocKey, Vec<(usize, i64)>>::new();
+    let mut doc_freq = Vec::<i64>::new();
+    {
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT owner_kind, owner_id, seq, part, tf FROM search_postings WHERE term = ?1",
+            )
+            .map_err(sqlite_err("Read search index"))?;
+        for (term_idx, term) in terms.iter().enumerate() {
+            let rows = stmt
+                .query_map([term], |row| {
+                    Ok((
+                        (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?),
+                        row.get::<_, i64>(4)?,
+                    ))
+                })
+                .map_err(sqlite_err("Read search index"))?;
+            let mut df = 0i64;
+            for row in rows {
+                let (key, tf) = row.map_err(sqlite_err("Read search index"))?;
+                df += 1;
+                matches.entry(key).or_default().push((term_idx, tf));
+            }
+            doc_freq.push(df);
+        }
+    }
+    let min_matched = (terms.len() * SEARCH_MIN_MATCH_PERCENT)
+        .div_ceil(100)
+        .max(1);
+    matches.retain(|_, hits| hits.len() >= min_matched);
+
+    let owners = read_search_owner_infos(conn)?;
+    let mut scored = Vec::<(f64, String, DocKey, String, Option<String>)>::new();
+    {
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT message_id, role, created_at, term_count FROM search_docs
+                 WHERE owner_kind = ?1 AND owner_id = ?2 AND seq = ?3 AND part = ?4",
+            )
+            .map_err(sqlite_err("Read search index"))?;
+        for (key, hits) in matches {
+            let Some(owner) = owners.get(&(key.0.clone(), key.1.clone())) else {
+                continue;
+            };
+            if agent_id.is_some_and(|id| id != owner.agent_id) {
+                continue;
+            }
+            let (message_id, doc_role, created_at, term_count) = stmt
+                .query_row(rusqlite::params![key.0, key.1, key.2, key.3], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                })
+                .map_err(sqlite_err("Read search index"))?;
+            if role.is_some_and(|r| r != doc_role) {
+                continue;
+            }
+            if from.is_some() || to.is_some() {
+                let Some(at) = parse_iso(&created_at) else {
+                    continue;
+                };
+                if from.is_some_and(|f| at < f) || to.is_some_and(|t| at > t) {
+                    continue;
+                }
+            }
+            let doc_len = term_count.max(1) as f64;
+            let score = hits
+                .iter()
+                .map(|(term_idx, tf)| {
+                    let df = doc_freq[*term_idx] as f64;
+                    let idf = (1.0 + (doc_count as f64 - df + 0.5) / (df + 0.5)).ln();
+                    let tf = *tf as f64;
+                    idf * tf * (SEARCH_BM25_K1 + 1.0)
+                        / (tf
+                            + SEARCH_BM25_K1
+                                * (1.0 - SEARCH_BM25_B
+                                    + SEARCH_BM25_B * doc_len / avg_len.max(1.0)))
+                })
+                .sum::<f64>()
+                * hits.len() as f64
+                / terms.len() as f64;
+            let message_id = Some(message_id).filter(|id| !id.is_empty());
+            scored.push((score, created_at, key, doc_role, message_id));
+        }
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.cmp(&a.1)));
+
+    let total = scored.len();
+    let limit = input
+        .limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .clamp(1, SEARCH_MAX_LIMIT);
+    let mut text_stmt = conn
+        .prepare_cached(
+            "SELECT text FROM search_docs
+             WHERE owner_kind = ?1 AND owner_id = ?2 AND seq = ?3 AND part = ?4",
+        )
+        .map_err(sqlite_err("Read search index"))?;
+    let mut hits = Vec::new();
+    for (score, created_at, key, doc_role, message_id) in scored
+        .into_iter()
+        .skip(input.offset.unwrap_or(0))
+        .take(limit)
+    {
+        let Some(owner) = owners.get(&(key.0.clone(), key.1.clone())) else {
+            continue;
+        };
+        let text = text_stmt
+            .query_row(rusqlite::params![key.0, key.1, key.2, key.3], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(sqlite_err("Read search index"))?;
+        let (snippet, highlights) = build_search_snippet(&text, &input.query);
+        hits.push(SearchHit {
+            source: key.0.clone(),
+            conversation_id: owner.conversation_id.clone(),
+            archive_id: owner.archive_id.clone(),
+            message_id,
+            role: doc_role,
+            created_at,
+            agent_id: owner.agent_id.clone(),
+            title: owner.title.clone(),
+            snippet,
+            highlights,
+            score,
+        });
+    }
+    Ok(SearchHistoryResult { total, hits })
+}
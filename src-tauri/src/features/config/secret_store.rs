@@ -0,0 +1,533 @@
+// API Key 加密存储：config.toml 只保存 `secret:<id>` 引用，密文在 secrets.json 中。
+// 密钥来自本机随机生成的 secret.key，或用户设置的口令（Argon2id 派生，仅缓存在内存）。
+
+const SECRET_REF_PREFIX: &str = "secret:";
+const ENV_KEY_REF_PREFIX: &str = "env:";
+const FILE_KEY_REF_PREFIX: &str = "file:";
+const MASKED_SECRET: &str = "••••••••";
+const SECRET_STORE_FILE_NAME: &str = "secrets.json";
+const SECRET_KEY_FILE_NAME: &str = "secret.key";
+const SECRET_STORE_VERIFIER: &str = "easy-call-ai-secret-store";
+const SECRET_MODE_MACHINE: &str = "machine";
+const SECRET_MODE_PASSPHRASE: &str = "passphrase";
+
+type SecretKey = [u8; 32];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SecretStoreFile {
+    version: u32,
+    /// machine | passphrase
+    mode: String,
+    /// 口令模式下的 Argon2 盐（base64）。
+    #[serde(default)]
+    salt: String,
+    /// 用当前密钥加密的固定文本，用于校验口令。
+    #[serde(default)]
+    verifier: String,
+    #[serde(default)]
+    secrets: std::collections::BTreeMap<String, String>,
+}
+
+impl Default for SecretStoreFile {
+    fn default() -> Self {
+        Self {
+            version: 1,
+            mode: SECRET_MODE_MACHINE.to_string(),
+            salt: String::new(),
+            verifier: String::new(),
+            secrets: std::collections::BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SecretStoreStatus {
+    mode: String,
+    locked: bool,
+}
+
+#[derive(Default)]
+struct SecretVaultState {
+    dir: Option<PathBuf>,
+    passphrase_key: Option<SecretKey>,
+}
+
+fn secret_vault() -> &'static Mutex<SecretVaultState> {
+    static VAULT: OnceLock<Mutex<SecretVaultState>> = OnceLock::new();
+    VAULT.get_or_init(|| Mutex::new(SecretVaultState::default()))
+}
+
+fn init_secret_vault(dir: &Path) {
+    if let Ok(mut vault) = secret_vault().lock() {
+        vault.dir = Some(dir.to_path_buf());
+    }
+}
+
+fn is_secret_ref(value: &str) -> bool {
+    value.trim().starts_with(SECRET_REF_PREFIX)
+}
+
+/// 返回给前端的掩码带上引用 id 的前 8 位，保存时据此找回原引用，与 key 在列表中的位置无关。
+fn masked_secret_for(reference: &str) -> String {
+    let id = reference.trim().trim_start_matches(SECRET_REF_PREFIX);
+    format!("{MASKED_SECRET}#{}", id.chars().take(8).collect::<String>())
+}
+
+fn is_masked_secret(value: &str) -> bool {
+    value.trim().starts_with(MASKED_SECRET)
+}
+
+/// 把前端回传的掩码还原为上一次保存的引用；不带标记的旧式掩码只对应主 key。
+fn unmask_secret_ref(previous: &ApiConfig, masked: &str, is_primary: bool) -> Option<String> {
+    let tag = masked
+        .trim()
+        .trim_start_matches(MASKED_SECRET)
+        .trim_start_matches('#');
+    if tag.is_empty() {
+        return Some(previous.api_key.clone())
+            .filter(|k| is_primary && is_secret_ref(k));
+    }
+    api_key_slots(previous)
+        .find(|k| {
+            is_secret_ref(k) && k.trim().trim_start_matches(SECRET_REF_PREFIX).starts_with(tag)
+        })
+        .cloned()
+}
+
+/// `env:VAR` / `file:~/path` 形式的外部引用：明文保存在配置中，请求时才解析。
+fn is_external_key_ref(value: &str) -> bool {
+    let value = value.trim();
+    value.starts_with(ENV_KEY_REF_PREFIX) || value.starts_with(FILE_KEY_REF_PREFIX)
+}
+
+fn expand_home_path(raw: &str) -> Result<PathBuf, String> {
+    let raw = raw.trim();
+    let rest = if raw == "~" {
+        Some("")
+    } else {
+        raw.strip_prefix("~/").or_else(|| raw.strip_prefix("~\\"))
+    };
+    let Some(rest) = rest else {
+        return Ok(PathBuf::from(raw));
+    };
+    let home = directories::BaseDirs::new()
+        .map(|d| d.home_dir().to_path_buf())
+        .ok_or_else(|| "Failed to resolve home directory for api_key file reference.".to_string())?;
+    Ok(home.join(rest))
+}
+
+fn resolve_external_key_ref(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    if let Some(name) = raw.strip_prefix(ENV_KEY_REF_PREFIX) {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("api_key reference 'env:' is missing a variable name.".to_string());
+        }
+        let value = std::env::var(name).map_err(|err| match err {
+            std::env::VarError::NotPresent => {
+                format!("Environment variable '{name}' referenced by api_key is not set.")
+            }
+            std::env::VarError::NotUnicode(_) => {
+                format!("Environment variable '{name}' referenced by api_key is not valid UTF-8.")
+            }
+        })?;
+        if value.trim().is_empty() {
+            return Err(format!(
+                "Environment variable '{name}' referenced by api_key is empty."
+            ));
+        }
+        return Ok(value.trim().to_string());
+    }
+    if let Some(path) = raw.strip_prefix(FILE_KEY_REF_PREFIX) {
+        if path.trim().is_empty() {
+            return Err("api_key reference 'file:' is missing a path.".to_string());
+        }
+        let path = expand_home_path(path)?;
+        let value = fs::read_to_string(&path).map_err(|err| {
+            format!(
+                "Read key file '{}' referenced by api_key failed: {err}",
+                path.display()
+            )
+        })?;
+        if value.trim().is_empty() {
+            return Err(format!(
+                "Key file '{}' referenced by api_key is empty.",
+                path.display()
+            ));
+        }
+        return Ok(value.trim().to_string());
+    }
+    Ok(raw.to_string())
+}
+
+fn read_secret_store(dir: &Path) -> Result<SecretStoreFile, String> {
+    let path = dir.join(SECRET_STORE_FILE_NAME);
+    if !path.exists() {
+        return Ok(SecretStoreFile::default());
+    }
+    let raw = fs::read_to_string(&path).map_err(|err| format!("Read secret store failed: {err}"))?;
+    serde_json::from_str(&raw).map_err(|err| format!("Parse secret store failed: {err}"))
+}
+
+fn write_secret_store(dir: &Path, store: &SecretStoreFile) -> Result<(), String> {
+    let body = serde_json::to_string_pretty(store)
+        .map_err(|err| format!("Serialize secret store failed: {err}"))?;
+    write_file_atomically(&dir.join(SECRET_STORE_FILE_NAME), body.as_bytes())
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+
+    let mut out = [0u8; N];
+    OsRng.fill_bytes(&mut out);
+    out
+}
+
+/// 读取本机密钥文件，不存在时生成。unix 下权限收紧为 0600。
+fn machine_secret_key(dir: &Path) -> Result<SecretKey, String> {
+    let path = dir.join(SECRET_KEY_FILE_NAME);
+    if path.exists() {
+        let raw = fs::read(&path).map_err(|err| format!("Read secret key failed: {err}"))?;
+        return raw
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Secret key file is invalid.".to_string());
+    }
+    let key = random_bytes::<32>();
+    write_file_atomically(&path, &key)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+    }
+    Ok(key)
+}
+
+fn derive_passphrase_key(passphrase: &str, salt: &[u8]) -> Result<SecretKey, String> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| format!("Derive key from passphrase failed: {err}"))?;
+    Ok(key)
+}
+
+fn encrypt_secret(key: &SecretKey, plain: &str) -> Result<String, String> {
+    use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+
+    let nonce = random_bytes::<24>();
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let sealed = cipher
+        .encrypt(XNonce::from_slice(&nonce), plain.as_bytes())
+        .map_err(|_| "Encrypt secret failed.".to_string())?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&sealed);
+    Ok(B64.encode(out))
+}
+
+fn decrypt_secret(key: &SecretKey, sealed_b64: &str) -> Result<String, String> {
+    use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+
+    let raw = B64
+        .decode(sealed_b64.trim())
+        .map_err(|err| format!("Decode secret failed: {err}"))?;
+    if raw.len() < 24 {
+        return Err("Secret ciphertext is too short.".to_string());
+    }
+    let (nonce, sealed) = raw.split_at(24);
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let plain = cipher
+        .decrypt(XNonce::from_slice(nonce), sealed)
+        .map_err(|_| "Decrypt secret failed (wrong key or passphrase?).".to_string())?;
+    String::from_utf8(plain).map_err(|_| "Secret is not valid UTF-8.".to_string())
+}
+
+/// 取得当前可用的密钥：本机模式读密钥文件；口令模式需已解锁。
+fn secret_store_key(
+    dir: &Path,
+    store: &SecretStoreFile,
+    passphrase_key: Option<SecretKey>,
+) -> Result<SecretKey, String> {
+    if store.mode == SECRET_MODE_PASSPHRASE {
+        return passphrase_key.ok_or_else(|| {
+            "Secret store is locked. Please unlock it with your passphrase.".to_string()
+        });
+    }
+    machine_secret_key(dir)
+}
+
+fn vault_snapshot() -> Result<(PathBuf, Option<SecretKey>), String> {
+    let vault = secret_vault()
+        .lock()
+        .map_err(|_| "Failed to lock secret vault".to_string())?;
+    let dir = vault
+        .dir
+        .clone()
+        .ok_or_else(|| "Secret store is not initialized.".to_string())?;
+    Ok((dir, vault.passphrase_key))
+}
+
+fn reveal_secret_in(
+    dir: &Path,
+    passphrase_key: Option<SecretKey>,
+    reference: &str,
+) -> Result<String, String> {
+    let id = reference.trim().trim_start_matches(SECRET_REF_PREFIX);
+    let store = read_secret_store(dir)?;
+    let sealed = store
+        .secrets
+        .get(id)
+        .ok_or_else(|| format!("Secret '{id}' not found. Please re-enter the API key."))?;
+    let key = secret_store_key(dir, &store, passphrase_key)?;
+    decrypt_secret(&key, sealed)
+}
+
+/// 把配置里的 api_key 解析为明文：`secret:` 从密钥库解密，`env:`/`file:` 读取外部来源，
+/// 其余原样返回。仅在发请求前调用。
+fn reveal_api_key(raw: &str) -> Result<String, String> {
+    if is_external_key_ref(raw) {
+        return resolve_external_key_ref(raw);
+    }
+    if !is_secret_ref(raw) {
+        return Ok(raw.trim().to_string());
+    }
+    let (dir, passphrase_key) = vault_snapshot()?;
+    reveal_secret_in(&dir, passphrase_key, raw)
+}
+
+/// 把明文 api_key 加密进密钥库并替换为引用；前端回传的掩码沿用旧引用。
+/// 不再被任何配置引用的密文会被清理。返回是否改动了配置。
+fn seal_config_secrets_in(
+    dir: &Path,
+    passphrase_key: Option<SecretKey>,
+    config: &mut AppConfig,
+    previous: Option<&AppConfig>,
+) -> Result<bool, String> {
+    let mut store = read_secret_store(dir)?;
+    let has_plain = config.api_configs.iter().flat_map(api_key_slots).any(|k| is_plain_api_key(k));
+    let key = if has_plain {
+        Some(secret_store_key(dir, &store, passphrase_key)?)
+    } else {
+        None
+    };
+    let mut changed = false;
+    for api in &mut config.api_configs {
+        let previous_api = previous.and_then(|p| p.api_configs.iter().find(|a| a.id == api.id));
+        let api_name = api.name.clone();
+        let slots = std::iter::once(&mut api.api_key).chain(api.extra_api_keys.iter_mut());
+        for (idx, slot) in slots.enumerate() {
+            let value = slot.trim().to_string();
+            if is_masked_secret(&value) {
+                // 新复制出的配置沿用来源配置的引用：带标记的掩码可在任一旧配置中找回。
+                let reference = previous_api
+                    .and_then(|a| unmask_secret_ref(a, &value, idx == 0))
+                    .or_else(|| {
+                        previous?
+                            .api_configs
+                            .iter()
+                            .find_map(|a| unmask_secret_ref(a, &value, false))
+                    });
+                *slot = reference.ok_or_else(|| {
+                    format!(
+                        "API key of '{api_name}' is masked but no saved key matches it. Please re-enter the API key."
+                    )
+                })?;
+                continue;
+            }
+            if value.is_empty() || is_secret_ref(&value) || is_external_key_ref(&value) {
+                continue;
+            }
+            let key = key
+                .as_ref()
+                .ok_or_else(|| "Secret store key is not available.".to_string())?;
+            let id = Uuid::new_v4().to_string();
+            let sealed = encrypt_secret(key, &value)?;
+            store.secrets.insert(id.clone(), sealed);
+            *slot = format!("{SECRET_REF_PREFIX}{id}");
+            changed = true;
+        }
+        api.extra_api_keys.retain(|k| !k.trim().is_empty());
+    }
+
+    let referenced = config
+        .api_configs
+        .iter()
+        .flat_map(api_key_slots)
+        .filter(|k| is_secret_ref(k))
+        .map(|k| k.trim().trim_start_matches(SECRET_REF_PREFIX).to_string())
+        .collect::<std::collections::HashSet<_>>();
+    let before = store.secrets.len();
+    store.secrets.retain(|id, _| referenced.contains(id));
+    if changed || store.secrets.len() != before {
+        if store.verifier.is_empty() {
+            if let Some(key) = key {
+                store.verifier = encrypt_secret(&key, SECRET_STORE_VERIFIER)?;
+            }
+        }
+        write_secret_store(dir, &store)?;
+    }
+    Ok(changed)
+}
+
+fn seal_config_secrets(config: &mut AppConfig, previous: Option<&AppConfig>) -> Result<bool, String> {
+    let (dir, passphrase_key) = vault_snapshot()?;
+    seal_config_secrets_in(&dir, passphrase_key, config, previous)
+}
+
+/// 迁移后清掉快照里的明文 key：与本次迁移相同的 key 换成对应引用，其余置空需重新填写。
+fn scrub_plain_api_keys_in_backups(
+    config_path: &Path,
+    sealed: &std::collections::HashMap<String, String>,
+) -> Result<(), String> {
+    let root = backup_root_dir(config_path);
+    for backup_id in list_backup_ids(&root)? {
+        let path = root
+            .join(&backup_id)
+            .join(config_path.file_name().unwrap_or_default());
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(mut snapshot) = toml::from_str::<AppConfig>(&content) else {
+            continue;
+        };
+        let mut changed = false;
+        for api in &mut snapshot.api_configs {
+            let slots = std::iter::once(&mut api.api_key).chain(api.extra_api_keys.iter_mut());
+            for slot in slots.filter(|k| is_plain_api_key(k)) {
+                *slot = sealed.get(slot.trim()).cloned().unwrap_or_default();
+                changed = true;
+            }
+        }
+        if changed {
+            let toml_str = toml::to_string_pretty(&snapshot)
+                .map_err(|err| format!("Serialize config failed: {err}"))?;
+            write_file_atomically(&path, toml_str.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn is_plain_api_key(value: &str) -> bool {
+    let key = value.trim();
+    !key.is_empty() && !is_masked_secret(key) && !is_secret_ref(key) && !is_external_key_ref(key)
+}
+
+/// 首次读取旧配置时把明文 api_key 迁入密钥库，并清掉快照中的明文；口令模式未解锁时保持原样。
+fn migrate_plain_api_keys(config_path: &Path, config: &mut AppConfig) -> Result<bool, String> {
+    let has_plain = config.api_configs.iter().flat_map(api_key_slots).any(|k| is_plain_api_key(k));
+    if !has_plain {
+        return Ok(false);
+    }
+    let dir = config_path
+        .parent()
+        .ok_or_else(|| "Config path has no parent directory".to_string())?;
+    let passphrase_key = secret_vault().lock().ok().and_then(|v| v.passphrase_key);
+    let store = read_secret_store(dir)?;
+    if store.mode == SECRET_MODE_PASSPHRASE && passphrase_key.is_none() {
+        return Ok(false);
+    }
+    let before = config.clone();
+    let changed = seal_config_secrets_in(dir, passphrase_key, config, None)?;
+    // 迁移只替换非空 key，逐个配置按非空顺序对应即可得到明文到引用的映射。
+    let mut sealed = std::collections::HashMap::new();
+    for (old, new) in before.api_configs.iter().zip(config.api_configs.iter()) {
+        let old_keys = api_key_slots(old).filter(|k| !k.trim().is_empty());
+        let new_keys = api_key_slots(new).filter(|k| !k.trim().is_empty());
+        for (plain, reference) in old_keys.zip(new_keys) {
+            if is_plain_api_key(plain) {
+                sealed.insert(plain.trim().to_string(), reference.clone());
+            }
+        }
+    }
+    scrub_plain_api_keys_in_backups(config_path, &sealed)?;
+    Ok(changed)
+}
+
+/// 返回给前端前把密钥引用替换为掩码。
+fn mask_config_secrets(config: &mut AppConfig) {
+    for api in &mut config.api_configs {
+        let slots = std::iter::once(&mut api.api_key).chain(api.extra_api_keys.iter_mut());
+        for slot in slots {
+            if is_secret_ref(slot) {
+                *slot = masked_secret_for(slot);
+            }
+        }
+    }
+}
+
+fn secret_store_status() -> Result<SecretStoreStatus, String> {
+    let (dir, passphrase_key) = vault_snapshot()?;
+    let store = read_secret_store(&dir)?;
+    Ok(SecretStoreStatus {
+        locked: store.mode == SECRET_MODE_PASSPHRASE && passphrase_key.is_none(),
+        mode: store.mode,
+    })
+}
+
+fn unlock_secret_store_in(dir: &Path, passphrase: &str) -> Result<SecretKey, String> {
+    let store = read_secret_store(dir)?;
+    if store.mode != SECRET_MODE_PASSPHRASE {
+        return Err("Secret store is not protected by a passphrase.".to_string());
+    }
+    let salt = B64
+        .decode(store.salt.trim())
+        .map_err(|err| format!("Decode secret store salt failed: {err}"))?;
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    if decrypt_secret(&key, &store.verifier).ok().as_deref() != Some(SECRET_STORE_VERIFIER) {
+        return Err("Incorrect passphrase.".to_string());
+    }
+    Ok(key)
+}
+
+/// 设置或清除口令：用旧密钥解密全部密文，再用新密钥（口令或本机密钥）重新加密。
+fn change_secret_store_passphrase_in(
+    dir: &Path,
+    passphrase_key: Option<SecretKey>,
+    new_passphrase: Option<&str>,
+) -> Result<Option<SecretKey>, String> {
+    let store = read_secret_store(dir)?;
+    let old_key = secret_store_key(dir, &store, passphrase_key)?;
+    let mut plain = std::collections::BTreeMap::new();
+    for (id, sealed) in &store.secrets {
+        plain.insert(id.clone(), decrypt_secret(&old_key, sealed)?);
+    }
+
+    let mut next = SecretStoreFile::default();
+    let (new_key, cached) = match new_passphrase.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(passphrase) => {
+            let salt = random_bytes::<16>();
+            next.mode = SECRET_MODE_PASSPHRASE.to_string();
+            next.salt = B64.encode(salt);
+            let key = derive_passphrase_key(passphrase, &salt)?;
+            (key, Some(key))
+        }
+        None => (machine_secret_key(dir)?, None),
+    };
+    next.verifier = encrypt_secret(&new_key, SECRET_STORE_VERIFIER)?;
+    for (id, value) in plain {
+        next.secrets.insert(id, encrypt_secret(&new_key, &value)?);
+    }
+    write_secret_store(dir, &next)?;
+    Ok(cached)
+}
+
+fn unlock_vault_with_passphrase(passphrase: &str) -> Result<(), String> {
+    let (dir, _) = vault_snapshot()?;
+    let key = unlock_secret_store_in(&dir, passphrase)?;
+    let mut vault = secret_vault()
+        .lock()
+        .map_err(|_| "Failed to lock secret vault".to_string())?;
+    vault.passphrase_key = Some(key);
+    Ok(())
+}
+
+fn change_vault_passphrase(new_passphrase: Option<&str>) -> Result<(), String> {
+    let (dir, passphrase_key) = vault_snapshot()?;
+    let cached = change_secret_store_passphrase_in(&dir, passphrase_key, new_passphrase)?;
+    let mut vault = secret_vault()
+        .lock()
+        .map_err(|_| "Failed to lock secret vault".to_string())?;
+    vault.passphrase_key = cached;
+    Ok(())
+}
@@ -0,0 +1,764 @@
+// SQLite 存储：会话/消息/归档/记忆/图片转文缓存/智能体/回复风格分表保存。
+// 写入时按行比对，只对新增或变化的行做 upsert，新消息为增量插入。
+
+const APP_DB_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS agents (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS response_styles (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    agent_id TEXT NOT NULL,
+    api_config_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_conversations_active
+    ON conversations (status, agent_id, api_config_id, position);
+CREATE TABLE IF NOT EXISTS archives (
+    archive_id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    agent_id TEXT NOT NULL,
+    archived_at TEXT NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_archives_agent ON archives (agent_id, archived_at);
+CREATE TABLE IF NOT EXISTS messages (
+    owner_kind TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    body TEXT NOT NULL,
+    PRIMARY KEY (owner_kind, owner_id, seq)
+);
+CREATE TABLE IF NOT EXISTS memories (
+    id TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS image_text_cache (
+    hash TEXT NOT NULL,
+    vision_api_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (hash, vision_api_id)
+);
+CREATE TABLE IF NOT EXISTS search_docs (
+    owner_kind TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    part INTEGER NOT NULL,
+    message_id TEXT NOT NULL,
+    role TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    term_count INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    PRIMARY KEY (owner_kind, owner_id, seq, part)
+);
+CREATE TABLE IF NOT EXISTS search_postings (
+    term TEXT NOT NULL,
+    owner_kind TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    part INTEGER NOT NULL,
+    tf INTEGER NOT NULL,
+    PRIMARY KEY (term, owner_kind, owner_id, seq, part)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS idx_search_postings_doc
+    ON search_postings (owner_kind, owner_id, seq);
+";
+
+const MESSAGE_OWNER_CONVERSATION: &str = "conversation";
+const MESSAGE_OWNER_ARCHIVE: &str = "archive";
+
+fn legacy_app_data_json_path(db_path: &Path) -> PathBuf {
+    db_path.with_file_name("app_data.json")
+}
+
+fn try_open_app_db(path: &Path) -> Result<rusqlite::Connection, rusqlite::Error> {
+    let conn = rusqlite::Connection::open(path)?;
+    conn.execute_batch(APP_DB_SCHEMA)?;
+    Ok(conn)
+}
+
+fn is_sqlite_corruption(err: &rusqlite::Error) -> bool {
+    matches!(
+        err.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::NotADatabase | rusqlite::ErrorCode::DatabaseCorrupt)
+    )
+}
+
+fn open_app_db(path: &PathBuf) -> Result<rusqlite::Connection, String> {
+    ensure_parent_dir(path)?;
+    match try_open_app_db(path) {
+        Ok(conn) => Ok(conn),
+        Err(err) if is_sqlite_corruption(&err) => {
+            let moved = move_aside_corrupt_app_db(path)?;
+            eprintln!(
+                "[STORAGE] app database corrupt, moved to {}: {err}",
+                moved.display()
+            );
+            try_open_app_db(path).map_err(|err| format!("Open app database failed: {err}"))
+        }
+        Err(err) => Err(format!("Open app database failed: {err}")),
+    }
+}
+
+fn sqlite_err(action: &str) -> impl Fn(rusqlite::Error) -> String + '_ {
+    move |err| format!("{action} failed: {err}")
+}
+
+fn to_json_text<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|err| format!("Serialize app_data failed: {err}"))
+}
+
+fn from_json_text<T: serde::de::DeserializeOwned>(raw: &str) -> Result<T, String> {
+    serde_json::from_str(raw).map_err(|err| format!("Parse app_data row failed: {err}"))
+}
+
+fn db_is_initialized(conn: &rusqlite::Connection) -> Result<bool, String> {
+    use rusqlite::OptionalExtension;
+
+    conn.query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+        row.get::<_, String>(0)
+    })
+    .optional()
+    .map(|v| v.is_some())
+    .map_err(sqlite_err("Read app database meta"))
+}
+
+fn read_meta_map(
+    conn: &rusqlite::Connection,
+) -> Result<std::collections::HashMap<String, String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM meta")
+        .map_err(sqlite_err("Read app database meta"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(sqlite_err("Read app database meta"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(sqlite_err("Read app database meta"))
+}
+
+/// meta 中记录的数据版本；缺失或无法解析时返回 None。
+fn read_db_schema_version(conn: &rusqlite::Connection) -> Result<Option<u32>, String> {
+    Ok(read_meta_map(conn)?
+        .get("version")
+        .and_then(|v| v.parse::<u32>().ok()))
+}
+
+fn read_ordered_bodies(conn: &rusqlite::Connection, sql: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(sql).map_err(sqlite_err("Read app database"))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(sqlite_err("Read app database"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(sqlite_err("Read app database"))
+}
+
+fn read_owner_messages(
+    conn: &rusqlite::Connection,
+    owner_kind: &str,
+    owner_id: &str,
+) -> Result<Vec<ChatMessage>, String> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT body FROM messages WHERE owner_kind = ?1 AND owner_id = ?2 ORDER BY seq",
+        )
+        .map_err(sqlite_err("Read messages"))?;
+    let rows = stmt
+        .query_map(rusqlite::params![owner_kind, owner_id], |row| {
+            row.get::<_, String>(0)
+        })
+        .map_err(sqlite_err("Read messages"))?;
+    let mut out = Vec::new();
+    for row in rows {
+        let body = row.map_err(sqlite_err("Read messages"))?;
+        out.push(from_json_text::<ChatMessage>(&body)?);
+    }
+    Ok(out)
+}
+
+/// `with_messages` 为 false 时只读会话与归档的头部，消息留待 `load_conversation_messages` 按需加载。
+fn load_app_data_from_db(
+    conn: &rusqlite::Connection,
+    with_messages: bool,
+) -> Result<AppData, String> {
+    let meta = read_meta_map(conn)?;
+    let mut data = AppData {
+        version: meta
+            .get("version")
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(APP_DATA_SCHEMA_VERSION),
+        agents: Vec::new(),
+        selected_agent_id: meta
+            .get("selectedAgentId")
+            .cloned()
+            .unwrap_or_else(default_selected_agent_id),
+        user_alias: meta
+            .get("userAlias")
+            .cloned()
+            .unwrap_or_else(default_user_alias),
+        response_style_id: meta
+            .get("responseStyleId")
+            .cloned()
+            .unwrap_or_else(default_response_style_id),
+        response_styles: Vec::new(),
+        conversations: Vec::new(),
+        archived_conversations: Vec::new(),
+        image_text_cache: Vec::new(),
+        memories: Vec::new(),
+    };
+
+    for body in read_ordered_bodies(conn, "SELECT body FROM agents ORDER BY position")? {
+        data.agents.push(from_json_text(&body)?);
+    }
+    for body in read_ordered_bodies(conn, "SELECT body FROM response_styles ORDER BY position")? {
+        data.response_styles.push(from_json_text(&body)?);
+    }
+    for body in read_ordered_bodies(conn, "SELECT body FROM conversations ORDER BY position")? {
+        let mut conversation = from_json_text::<Conversation>(&body)?;
+        if with_messages {
+            conversation.messages =
+                read_owner_messages(conn, MESSAGE_OWNER_CONVERSATION, &conversation.id)?;
+        } else {
+            conversation.messages_deferred = true;
+        }
+        data.conversations.push(conversation);
+    }
+    for body in read_ordered_bodies(conn, "SELECT body FROM archives ORDER BY position")? {
+        let mut archive = from_json_text::<ConversationArchive>(&body)?;
+        if with_messages {
+            archive.source_conversation.messages =
+                read_owner_messages(conn, MESSAGE_OWNER_ARCHIVE, &archive.archive_id)?;
+        } else {
+            archive.source_conversation.messages_deferred = true;
+        }
+        data.archived_conversations.push(archive);
+    }
+    for body in read_ordered_bodies(conn, "SELECT body FROM memories ORDER BY position")? {
+        data.memories.push(from_json_text(&body)?);
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT hash, vision_api_id, text, updated_at FROM image_text_cache ORDER BY position",
+        )
+        .map_err(sqlite_err("Read image text cache"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ImageTextCacheEntry {
+                hash: row.get(0)?,
+                vision_api_id: row.get(1)?,
+                text: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })
+        .map_err(sqlite_err("Read image text cache"))?;
+    data.image_text_cache = rows
+        .collect::<Result<_, _>>()
+        .map_err(sqlite_err("Read image text cache"))?;
+    Ok(data)
+}
+
+/// 读取某张表现有的 key -> (position, body)，用于与内存数据比对。
+fn read_keyed_rows(
+    tx: &rusqlite::Transaction<'_>,
+    sql: &str,
+) -> Result<std::collections::HashMap<String, (i64, String)>, String> {
+    let mut stmt = tx.prepare(sql).map_err(sqlite_err("Read app database"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (row.get::<_, i64>(1)?, row.get::<_, String>(2)?),
+            ))
+        })
+        .map_err(sqlite_err("Read app database"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(sqlite_err("Read app database"))
+}
+
+fn delete_missing_keys(
+    tx: &rusqlite::Transaction<'_>,
+    delete_sql: &str,
+    existing: &std::collections::HashMap<String, (i64, String)>,
+    kept: &std::collections::HashSet<String>,
+) -> Result<(), String> {
+    for key in existing.keys().filter(|k| !kept.contains(*k)) {
+        tx.execute(delete_sql, [key])
+            .map_err(sqlite_err("Delete app database row"))?;
+    }
+    Ok(())
+}
+
+fn sync_owner_messages(
+    tx: &rusqlite::Transaction<'_>,
+    owner_kind: &str,
+    owner_id: &str,
+    conversation: &Conversation,
+    is_new_owner: bool,
+) -> Result<(), String> {
+    // 消息未加载的会话保持库中原样；若它是新行（例如被复制成新归档），写入会丢消息，直接报错。
+    if conversation.messages_deferred {
+        if is_new_owner {
+            return Err(format!(
+                "Messages of '{owner_id}' were not loaded and cannot be written."
+            ));
+        }
+        return Ok(());
+    }
+    let messages = &conversation.messages;
+    let existing = {
+        let mut stmt = tx
+            .prepare_cached(
+                "SELECT digest FROM messages WHERE owner_kind = ?1 AND owner_id = ?2 ORDER BY seq",
+            )
+            .map_err(sqlite_err("Read messages"))?;
+        let rows = stmt
+            .query_map(rusqlite::params![owner_kind, owner_id], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(sqlite_err("Read messages"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_err("Read messages"))?
+    };
+
+    let mut upsert = tx
+        .prepare_cached(
+            "INSERT OR REPLACE INTO messages (owner_kind, owner_id, seq, id, role, created_at, digest, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .map_err(sqlite_err("Write messages"))?;
+    for (seq, message) in messages.iter().enumerate() {
+        let body = to_json_text(message)?;
+        let digest = compute_bytes_hash_hex(body.as_bytes());
+        if existing.get(seq) == Some(&digest) {
+            continue;
+        }
+        upsert
+            .execute(rusqlite::params![
+                owner_kind,
+                owner_id,
+                seq as i64,
+                message.id,
+                message.role,
+                message.created_at,
+                digest,
+                body
+            ])
+            .map_err(sqlite_err("Write messages"))?;
+        index_message_search_docs(tx, owner_kind, owner_id, seq as i64, message)?;
+    }
+    if existing.len() > messages.len() {
+        tx.execute(
+            "DELETE FROM messages WHERE owner_kind = ?1 AND owner_id = ?2 AND seq >= ?3",
+            rusqlite::params![owner_kind, owner_id, messages.len() as i64],
+        )
+        .map_err(sqlite_err("Delete messages"))?;
+        delete_search_docs_from(tx, owner_kind, owner_id, messages.len() as i64)?;
+    }
+    Ok(())
+}
+
+fn sync_app_data_to_db(conn: &mut rusqlite::Connection, data: &AppData) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(sqlite_err("Begin app database transaction"))?;
+
+    for (key, value) in [
+        ("version", data.version.to_string()),
+        ("selectedAgentId", data.selected_agent_id.clone()),
+        ("userAlias", data.user_alias.clone()),
+        ("responseStyleId", data.response_style_id.clone()),
+    ] {
+        tx.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value WHERE value <> excluded.value",
+            rusqlite::params![key, value],
+        )
+        .map_err(sqlite_err("Write app database meta"))?;
+    }
+
+    // agents
+    let existing = read_keyed_rows(&tx, "SELECT id, position, body FROM agents")?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, agent) in data.agents.iter().enumerate() {
+        let body = to_json_text(agent)?;
+        kept.insert(agent.id.clone());
+        if existing.get(&agent.id) == Some(&(position as i64, body.clone())) {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO agents (id, position, body) VALUES (?1, ?2, ?3)",
+            rusqlite::params![agent.id, position as i64, body],
+        )
+        .map_err(sqlite_err("Write agents"))?;
+    }
+    delete_missing_keys(&tx, "DELETE FROM agents WHERE id = ?1", &existing, &kept)?;
+
+    // response styles
+    let existing = read_keyed_rows(&tx, "SELECT id, position, body FROM response_styles")?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, style) in data.response_styles.iter().enumerate() {
+        let body = to_json_text(style)?;
+        kept.insert(style.id.clone());
+        if existing.get(&style.id) == Some(&(position as i64, body.clone())) {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO response_styles (id, position, body) VALUES (?1, ?2, ?3)",
+            rusqlite::params![style.id, position as i64, body],
+        )
+        .map_err(sqlite_err("Write response styles"))?;
+    }
+    delete_missing_keys(
+        &tx,
+        "DELETE FROM response_styles WHERE id = ?1",
+        &existing,
+        &kept,
+    )?;
+
+    // conversations：会话元数据与消息分开保存，消息按序号增量同步。
+    let existing = read_keyed_rows(&tx, "SELECT id, position, body FROM conversations")?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, conversation) in data.conversations.iter().enumerate() {
+        let header = Conversation {
+            messages: Vec::new(),
+            ..conversation.clone()
+        };
+        let body = to_json_text(&header)?;
+        kept.insert(conversation.id.clone());
+        if existing.get(&conversation.id) != Some(&(position as i64, body.clone())) {
+            tx.execute(
+                "INSERT OR REPLACE INTO conversations (id, position, agent_id, api_config_id, status, updated_at, body)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    conversation.id,
+                    position as i64,
+                    conversation.agent_id,
+                    conversation.api_config_id,
+                    conversation.status,
+                    conversation.updated_at,
+                    body
+                ],
+            )
+            .map_err(sqlite_err("Write conversations"))?;
+        }
+        sync_owner_messages(
+            &tx,
+            MESSAGE_OWNER_CONVERSATION,
+            &conversation.id,
+            conversation,
+            !existing.contains_key(&conversation.id),
+        )?;
+    }
+    for id in existing.keys().filter(|k| !kept.contains(*k)) {
+        tx.execute("DELETE FROM conversations WHERE id = ?1", [id])
+            .map_err(sqlite_err("Delete conversations"))?;
+        tx.execute(
+            "DELETE FROM messages WHERE owner_kind = ?1 AND owner_id = ?2",
+            rusqlite::params![MESSAGE_OWNER_CONVERSATION, id],
+        )
+        .map_err(sqlite_err("Delete messages"))?;
+        delete_search_docs_from(&tx, MESSAGE_OWNER_CONVERSATION, id, i64::MIN)?;
+    }
+
+    // archives
+    let existing = read_keyed_rows(&tx, "SELECT archive_id, position, body FROM archives")?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, archive) in data.archived_conversations.iter().enumerate() {
+        let header = ConversationArchive {
+            source_conversation: Conversation {
+                messages: Vec::new(),
+                ..archive.source_conversation.clone()
+            },
+            ..archive.clone()
+        };
+        let body = to_json_text(&header)?;
+        kept.insert(archive.archive_id.clone());
+        if existing.get(&archive.archive_id) != Some(&(position as i64, body.clone())) {
+            tx.execute(
+                "INSERT OR REPLACE INTO archives (archive_id, position, agent_id, archived_at, body)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    archive.archive_id,
+                    position as i64,
+                    archive.source_conversation.agent_id,
+                    archive.archived_at,
+                    body
+                ],
+            )
+            .map_err(sqlite_err("Write archives"))?;
+            index_archive_summary_search_doc(&tx, archive)?;
+        }
+        sync_owner_messages(
+            &tx,
+            MESSAGE_OWNER_ARCHIVE,
+            &archive.archive_id,
+            &archive.source_conversation,
+            !existing.contains_key(&archive.archive_id),
+        )?;
+    }
+    for id in existing.keys().filter(|k| !kept.contains(*k)) {
+        tx.execute("DELETE FROM archives WHERE archive_id = ?1", [id])
+            .map_err(sqlite_err("Delete archives"))?;
+        tx.execute(
+            "DELETE FROM messages WHERE owner_kind = ?1 AND owner_id = ?2",
+            rusqlite::params![MESSAGE_OWNER_ARCHIVE, id],
+        )
+        .map_err(sqlite_err("Delete messages"))?;
+        delete_search_docs_from(&tx, MESSAGE_OWNER_ARCHIVE, id, i64::MIN)?;
+    }
+
+    // memories
+    let existing = read_keyed_rows(&tx, "SELECT id, position, body FROM memories")?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, memory) in data.memories.iter().enumerate() {
+        let body = to_json_text(memory)?;
+        kept.insert(memory.id.clone());
+        if existing.get(&memory.id) == Some(&(position as i64, body.clone())) {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO memories (id, position, body) VALUES (?1, ?2, ?3)",
+            rusqlite::params![memory.id, position as i64, body],
+        )
+        .map_err(sqlite_err("Write memories"))?;
+    }
+    delete_missing_keys(&tx, "DELETE FROM memories WHERE id = ?1", &existing, &kept)?;
+
+    // image_text_cache：以 hash + vision_api_id 为键。
+    let existing = read_keyed_rows(
+        &tx,
+        "SELECT hash || char(31) || vision_api_id, position, text || char(31) || updated_at FROM image_text_cache",
+    )?;
+    let mut kept = std::collections::HashSet::new();
+    for (position, entry) in data.image_text_cache.iter().enumerate() {
+        let key = format!("{}\u{1f}{}", entry.hash, entry.vision_api_id);
+        let body = format!("{}\u{1f}{}", entry.text, entry.updated_at);
+        let unchanged = existing.get(&key) == Some(&(position as i64, body));
+        kept.insert(key);
+        if unchanged {
+            continue;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO image_text_cache (hash, vision_api_id, position, text, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                entry.hash,
+                entry.vision_api_id,
+                position as i64,
+                entry.text,
+                entry.updated_at
+            ],
+        )
+        .map_err(sqlite_err("Write image text cache"))?;
+    }
+    for key in existing.keys().filter(|k| !kept.contains(*k)) {
+        let (hash, vision_api_id) = key.split_once('\u{1f}').unwrap_or((key.as_str(), ""));
+        tx.execute(
+            "DELETE FROM image_text_cache WHERE hash = ?1 AND vision_api_id = ?2",
+            rusqlite::params![hash, vision_api_id],
+        )
+        .map_err(sqlite_err("Delete image text cache"))?;
+    }
+
+    tx.commit()
+        .map_err(sqlite_err("Commit app database transaction"))
+}
+
+/// 只同步一条活动会话的头部与消息，其余表保持不动；用于只改动了这条会话的高频写入。
+fn sync_conversation_to_db(
+    conn: &mut rusqlite::Connection,
+    conversation: &Conversation,
+    position: usize,
+) -> Result<(), String> {
+    use rusqlite::OptionalExtension;
+
+    let tx = conn
+        .transaction()
+        .map_err(sqlite_err("Begin app database transaction"))?;
+    let existing = tx
+        .query_row(
+            "SELECT position, body FROM conversations WHERE id = ?1",
+            [&conversation.id],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()
+        .map_err(sqlite_err("Read conversations"))?;
+    let header = Conversation {
+        messages: Vec::new(),
+        ..conversation.clone()
+    };
+    let body = to_json_text(&header)?;
+    if existing.as_ref() != Some(&(position as i64, body.clone())) {
+        tx.execute(
+            "INSERT OR REPLACE INTO conversations (id, position, agent_id, api_config_id, status, updated_at, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                conversation.id,
+                position as i64,
+                conversation.agent_id,
+                conversation.api_config_id,
+                conversation.status,
+                conversation.updated_at,
+                body
+            ],
+        )
+        .map_err(sqlite_err("Write conversations"))?;
+    }
+    sync_owner_messages(
+        &tx,
+        MESSAGE_OWNER_CONVERSATION,
+        &conversation.id,
+        conversation,
+        existing.is_none(),
+    )?;
+    tx.commit()
+        .map_err(sqlite_err("Commit app database transaction"))
+}
+
+/// 读取旧版 app_data.json（若存在）。解析失败时把文件挪为 `.corrupt` 并视为不存在。
+fn read_legacy_app_data_json(db_path: &Path) -> Result<Option<AppData>, String> {
+    let json_path = legacy_app_data_json_path(db_path);
+    if !json_path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&json_path)
+        .map_err(|err| format!("Read legacy app_data.json failed: {err}"))?;
+    match serde_json::from_str::<AppData>(&content) {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(err) => {
+            let moved = move_aside_corrupt_file(&json_path)?;
+            eprintln!(
+                "[STORAGE] legacy app_data.json parse failed, moved to {}: {err}",
+                moved.display()
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// 旧版 app_data.json 导入数据库后改名为 `.migrated` 留作备份。
+fn finish_legacy_app_data_json_import(db_path: &Path) -> Result<(), String> {
+    let json_path = legacy_app_data_json_path(db_path);
+    if !json_path.exists() {
+        return Ok(());
+    }
+    let migrated_path = json_path.with_extension("json.migrated");
+    fs::rename(&json_path, &migrated_path)
+        .map_err(|err| format!("Rename legacy app_data.json failed: {err}"))?;
+    eprintln!(
+        "[STORAGE] imported {} into {}",
+        json_path.display(),
+        db_path.display()
+    );
+    Ok(())
+}
+
+/// 挪走损坏的数据库，连同未完成的回滚日志一起，避免日志被重放到新库。
+fn move_aside_corrupt_app_db(path: &Path) -> Result<PathBuf, String> {
+    let moved = move_aside_corrupt_file(path)?;
+    let journal = PathBuf::from(format!("{}-journal", path.display()));
+    if journal.exists() {
+        let moved_journal = PathBuf::from(format!("{}-journal", moved.display()));
+        fs::rename(&journal, &moved_journal)
+            .map_err(|err| format!("Move corrupt journal aside failed: {err}"))?;
+    }
+    Ok(moved)
+}
+
+/// 通过索引查找某智能体在某 API 下最近的活动会话，只加载这一条会话及其消息。
+/// 智能体已删除或为内置用户人设时返回 None，由调用方走完整读取流程。
+fn read_latest_active_conversation(
+    path: &PathBuf,
+    api_config_id: &str,
+    agent_id: &str,
+) -> Result<Option<Conversation>, String> {
+    use rusqlite::OptionalExtension;
+
+    let conn = open_app_db(path)?;
+    let body = conn
+        .query_row(
+            "SELECT c.body FROM conversations c
+             WHERE c.status = 'active' AND c.agent_id = ?1 AND c.api_config_id = ?2
+               AND EXISTS (
+                   SELECT 1 FROM agents a
+                   WHERE a.id = ?1 AND COALESCE(json_extract(a.body, '$.isBuiltInUser'), 0) = 0
+               )
+             ORDER BY c.position DESC LIMIT 1",
+            rusqlite::params![agent_id, api_config_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(sqlite_err("Read conversations"))?;
+    let Some(body) = body else {
+        return Ok(None);
+    };
+    let mut conversation = from_json_text::<Conversation>(&body)?;
+    conversation.messages =
+        read_owner_messages(&conn, MESSAGE_OWNER_CONVERSATION, &conversation.id)?;
+    Ok(Some(conversation))
+}
+
+/// 为只读了头部的活动会话补齐消息；已加载的会话不做任何事。
+fn load_conversation_messages(path: &PathBuf, conversation: &mut Conversation) -> Result<(), String> {
+    if !conversation.messages_deferred {
+        return Ok(());
+    }
+    let conn = open_app_db(path)?;
+    conversation.messages =
+        read_owner_messages(&conn, MESSAGE_OWNER_CONVERSATION, &conversation.id)?;
+    conversation.messages_deferred = false;
+    Ok(())
+}
+
+/// 按 id 为活动会话列表中的某条会话补齐消息，找不到时什么也不做。
+fn load_conversation_messages_by_id(
+    path: &PathBuf,
+    data: &mut AppData,
+    conversation_id: &str,
+) -> Result<(), String> {
+    match data.conversations.iter_mut().find(|c| c.id == conversation_id) {
+        Some(conversation) => load_conversation_messages(path, conversation),
+        None => Ok(()),
+    }
+}
+
+/// 各活动会话的消息条数，供会话列表在不加载消息的情况下显示。
+fn read_conversation_message_counts(
+    path: &PathBuf,
+) -> Result<std::collections::HashMap<String, usize>, String> {
+    let conn = open_app_db(path)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT owner_id, COUNT(*) FROM messages WHERE owner_kind = ?1 GROUP BY owner_id",
+        )
+        .map_err(sqlite_err("Read messages"))?;
+    let rows = stmt
+        .query_map([MESSAGE_OWNER_CONVERSATION], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })
+        .map_err(sqlite_err("Read messages"))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(sqlite_err("Read messages"))
+}
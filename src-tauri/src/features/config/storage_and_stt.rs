@@ -11,8 +11,25 @@ fn read_config(path: &PathBuf) -> Result<AppConfig, String> {
     }
 
     let content = fs::read_to_string(path).map_err(|err| format!("Read config failed: {err}"))?;
-    let mut parsed = toml::from_str::<AppConfig>(&content).unwrap_or_default();
+    let mut parsed = match toml::from_str::<AppConfig>(&content) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            // 解析失败时先把原文件挪走保留，再以默认配置继续，避免被后续保存覆盖。
+            let moved = move_aside_corrupt_file(path)?;
+            eprintln!(
+                "[STORAGE] config parse failed, moved to {}: {err}",
+                moved.display()
+            );
+            AppConfig::default()
+        }
+    };
     normalize_app_config(&mut parsed);
+    if migrate_plain_api_keys(path, &mut parsed)? {
+        // 直接覆盖而不走每日快照，避免再多留一份明文副本。
+        let toml_str = toml::to_string_pretty(&parsed)
+            .map_err(|err| format!("Serialize config failed: {err}"))?;
+        write_file_atomically(path, toml_str.as_bytes())?;
+    }
     Ok(parsed)
 }
 
@@ -20,7 +37,8 @@ fn write_config(path: &PathBuf, config: &AppConfig) -> Result<(), String> {
     ensure_parent_dir(path)?;
     let toml_str =
         toml::to_string_pretty(config).map_err(|err| format!("Serialize config failed: {err}"))?;
-    fs::write(path, toml_str).map_err(|err| format!("Write config failed: {err}"))
+    ensure_daily_backup(path)?;
+    write_file_atomically(path, toml_str.as_bytes())
 }
 
 fn normalize_api_tools(config: &mut AppConfig) {
@@ -65,11 +83,13 @@ fn normalize_app_config(config: &mut AppConfig) {
         config.selected_api_config_id = config.api_configs[0].id.clone();
     }
 
-    let chat_valid = config.api_configs.iter().any(|a| {
-        a.id == config.chat_api_config_id
-            && a.enable_text
-            && !a.request_format.is_openai_tts()
-    });
+    // 全局对话 API 是没有自带 API 的智能体的默认值；留空时用第一个文本 API。
+    let chat_valid = config.chat_api_config_id.is_empty()
+        || config.api_configs.iter().any(|a| {
+            a.id == config.chat_api_config_id
+                && a.enable_text
+                && !a.request_format.is_openai_tts()
+        });
     if !chat_valid {
         if let Some(api) = config
             .api_configs
@@ -92,6 +112,15 @@ fn normalize_app_config(config: &mut AppConfig) {
         config.max_record_seconds = default_max_record_seconds().max(config.min_record_seconds);
     }
     config.tool_max_iterations = config.tool_max_iterations.clamp(1, 100);
+    normalize_archive_policy(&mut config.archive_policy);
+    normalize_highest_instruction(&mut config.highest_instruction);
+    config.timezone = if config.timezone.trim().is_empty() {
+        String::new()
+    } else {
+        parse_utc_offset(&config.timezone)
+            .map(format_utc_offset_label)
+            .unwrap_or_default()
+    };
 
     config.vision_api_config_id = config
         .vision_api_config_id
@@ -104,6 +133,17 @@ fn normalize_app_config(config: &mut AppConfig) {
         })
         .map(ToOwned::to_owned);
 
+    config.title_api_config_id = config
+        .title_api_config_id
+        .as_deref()
+        .filter(|id| {
+            config
+                .api_configs
+                .iter()
+                .any(|a| a.id == *id && a.enable_text && !a.request_format.is_openai_tts())
+        })
+        .map(ToOwned::to_owned);
+
     config.stt_api_config_id = config
         .stt_api_config_id
         .as_deref()
@@ -119,42 +159,404 @@ fn normalize_app_config(config: &mut AppConfig) {
     }
 }
 
-fn read_app_data(path: &PathBuf) -> Result<AppData, String> {
-    if !path.exists() {
-        return Ok(AppData::default());
+const CORRUPT_FILE_SUFFIX: &str = "corrupt";
+
+/// 把无法解析的数据文件改名为 `<name>.<UTC时间>.corrupt`，返回新路径。
+fn move_aside_corrupt_file(path: &Path) -> Result<PathBuf, String> {
+    let now = now_utc();
+    let stamp = format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "data".to_string());
+    let mut target = path.with_file_name(format!("{file_name}.{stamp}.{CORRUPT_FILE_SUFFIX}"));
+    let mut n = 1;
+    while target.exists() {
+        target = path.with_file_name(format!("{file_name}.{stamp}-{n}.{CORRUPT_FILE_SUFFIX}"));
+        n += 1;
     }
+    fs::rename(path, &target)
+        .map_err(|err| format!("Move corrupt file aside failed ({}): {err}", path.display()))?;
+    Ok(target)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CorruptDataFile {
+    file_name: String,
+    path: String,
+    /// config | appData
+    kind: String,
+    size_bytes: u64,
+    moved_at: Option<String>,
+}
+
+/// 从 `<name>.<YYYYMMDD-HHMMSS>[-n].corrupt` 中解析出挪走时间（RFC3339）。
+fn parse_corrupt_file_stamp(file_name: &str) -> Option<String> {
+    let stem = file_name.strip_suffix(&format!(".{CORRUPT_FILE_SUFFIX}"))?;
+    let stamp = stem.rsplit('.').next()?.get(..15)?;
+    let (date, clock) = stamp.split_once('-')?;
+    let digits = format!("{date}{clock}");
+    if date.len() != 8 || clock.len() != 6 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let value = format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &date[..4],
+        &date[4..6],
+        &date[6..],
+        &clock[..2],
+        &clock[2..4],
+        &clock[4..]
+    );
+    parse_iso(&value).map(|_| value)
+}
+
+/// 列出数据目录中被挪走的 `.corrupt` 文件，供恢复界面展示，最新的排在前面。
+fn list_corrupt_data_files_in(dir: &Path) -> Result<Vec<CorruptDataFile>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(dir).map_err(|err| format!("Read data directory failed: {err}"))?;
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.ends_with(&format!(".{CORRUPT_FILE_SUFFIX}")) {
+            continue;
+        }
+        let metadata = entry.metadata().ok();
+        let kind = if file_name.starts_with("config.toml") {
+            "config"
+        } else {
+            "appData"
+        };
+        files.push(CorruptDataFile {
+            path: entry.path().to_string_lossy().to_string(),
+            kind: kind.to_string(),
+            size_bytes: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+            moved_at: parse_corrupt_file_stamp(&file_name),
+            file_name,
+        });
+    }
+    files.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+    Ok(files)
+}
+
+type AppDataMigration = fn(&mut AppData, &Path) -> Result<(), String>;
+
+/// 按目标版本号排列的迁移步骤；新增 schema 版本时在末尾追加一项并提升 APP_DATA_SCHEMA_VERSION。
+const APP_DATA_MIGRATIONS: &[(u32, AppDataMigration)] = &[(2, migrate_app_data_to_v2)];
 
-    let content = fs::read_to_string(path).map_err(|err| format!("Read app_data failed: {err}"))?;
-    let mut parsed = serde_json::from_str::<AppData>(&content).unwrap_or_default();
-    parsed.version = APP_DATA_SCHEMA_VERSION;
+/// v2：附件从内联 base64 移到 blobs 目录。
+fn migrate_app_data_to_v2(data: &mut AppData, blob_dir: &Path) -> Result<(), String> {
+    externalize_app_data_blobs(blob_dir, data)
+}
+
+fn ensure_supported_app_data_version(version: u32) -> Result<(), String> {
+    if version > APP_DATA_SCHEMA_VERSION {
+        return Err(format!(
+            "App data schema version {version} is newer than supported version {APP_DATA_SCHEMA_VERSION}. Please upgrade the app."
+        ));
+    }
+    Ok(())
+}
+
+/// 把数据从其记录的版本逐步迁移到当前版本，返回是否发生了迁移。
+fn migrate_app_data(data: &mut AppData, blob_dir: &Path) -> Result<bool, String> {
+    ensure_supported_app_data_version(data.version)?;
+    let from_version = data.version;
+    for (to_version, migrate) in APP_DATA_MIGRATIONS {
+        if data.version >= *to_version {
+            continue;
+        }
+        migrate(data, blob_dir)
+            .map_err(|err| format!("Migrate app data to v{to_version} failed: {err}"))?;
+        data.version = *to_version;
+    }
+    data.version = APP_DATA_SCHEMA_VERSION;
+    if data.version != from_version {
+        eprintln!(
+            "[STORAGE] migrated app data from v{from_version} to v{}",
+            data.version
+        );
+    }
+    Ok(data.version != from_version)
+}
+
+fn read_app_data(path: &PathBuf) -> Result<AppData, String> {
+    let mut conn = open_app_db(path)?;
+    let mut from_legacy_json = false;
+    let mut parsed = if db_is_initialized(&conn)? {
+        // 降级后库里可能是新版本写入的数据，先比较版本号，避免解析失败被当成损坏挪走。
+        if let Some(version) = read_db_schema_version(&conn)? {
+            ensure_supported_app_data_version(version)?;
+        }
+        match load_app_data_from_db(&conn, true) {
+            Ok(data) => data,
+            Err(err) => {
+                // 数据行无法解析时不覆盖原库：整体挪走保留，以空数据继续。
+                drop(conn);
+                let moved = move_aside_corrupt_app_db(path)?;
+                eprintln!(
+                    "[STORAGE] app data load failed, moved to {}: {err}",
+                    moved.display()
+                );
+                return Ok(AppData::default());
+            }
+        }
+    } else if let Some(legacy) = read_legacy_app_data_json(path)? {
+        from_legacy_json = true;
+        legacy
+    } else {
+        return Ok(AppData::default());
+    };
+    let blob_dir = blob_storage_dir(path);
+    let migrated = migrate_app_data(&mut parsed, &blob_dir)?;
     ensure_default_agent(&mut parsed);
+    if migrated || from_legacy_json {
+        ensure_daily_backup(path)?;
+        externalize_app_data_blobs(&blob_dir, &mut parsed)?;
+        sync_app_data_to_db(&mut conn, &parsed)?;
+        finish_legacy_app_data_json_import(path)?;
+    }
     Ok(parsed)
 }
 
+/// 只读会话与归档的头部，消息由调用方对要用到的会话调用 `load_conversation_messages` 加载。
+/// 写回时未加载消息的会话不会重新序列化消息。需要迁移或导入旧数据时退回完整读取。
+fn read_app_data_headers(path: &PathBuf) -> Result<AppData, String> {
+    let conn = open_app_db(path)?;
+    let current = db_is_initialized(&conn)?
+        && read_db_schema_version(&conn)? == Some(APP_DATA_SCHEMA_VERSION);
+    if !current {
+        drop(conn);
+        return read_app_data(path);
+    }
+    let mut data = match load_app_data_from_db(&conn, false) {
+        Ok(data) => data,
+        Err(_) => {
+            drop(conn);
+            return read_app_data(path);
+        }
+    };
+    ensure_default_agent(&mut data);
+    Ok(data)
+}
+
 fn write_app_data(path: &PathBuf, data: &AppData) -> Result<(), String> {
-    ensure_parent_dir(path)?;
-    let body = serde_json::to_string_pretty(data)
-        .map_err(|err| format!("Serialize app_data failed: {err}"))?;
-    fs::write(path, body).map_err(|err| format!("Write app_data failed: {err}"))
+    ensure_daily_backup(path)?;
+    let mut conn = open_app_db(path)?;
+    if app_data_has_inline_blobs(data) {
+        let mut stored = data.clone();
+        externalize_app_data_blobs(&blob_storage_dir(path), &mut stored)?;
+        sync_app_data_to_db(&mut conn, &stored)
+    } else {
+        sync_app_data_to_db(&mut conn, data)
+    }
 }
 
-fn candidate_debug_config_paths() -> Vec<PathBuf> {
-    vec![PathBuf::from(".debug").join("api-key.json")]
+/// 只写回 `data` 中的一条活动会话，不比对其他会话、归档与记忆。
+/// 调用方只改动了这条会话时使用，其余改动需走 `write_app_data`。
+fn write_conversation_data(
+    path: &PathBuf,
+    data: &AppData,
+    conversation_id: &str,
+) -> Result<(), String> {
+    let (position, conversation) = data
+        .conversations
+        .iter()
+        .enumerate()
+        .find(|(_, c)| c.id == conversation_id)
+        .ok_or_else(|| format!("Conversation '{conversation_id}' not found."))?;
+    ensure_daily_backup(path)?;
+    let mut conn = open_app_db(path)?;
+    if conversation_has_inline_blobs(conversation) {
+        let mut stored = conversation.clone();
+        externalize_message_blobs(&blob_storage_dir(path), &mut stored.messages)?;
+        externalize_message_blobs(&blob_storage_dir(path), &mut stored.alternate_messages)?;
+        sync_conversation_to_db(&mut conn, &stored, position)
+    } else {
+        sync_conversation_to_db(&mut conn, conversation, position)
+    }
 }
 
-fn read_debug_api_config() -> Result<Option<DebugApiConfig>, String> {
-    for path in candidate_debug_config_paths() {
-        if !path.exists() {
+fn blob_storage_dir(data_path: &Path) -> PathBuf {
+    data_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("blobs")
+}
+
+fn blob_file_path(blob_dir: &Path, hash: &str) -> Result<PathBuf, String> {
+    let hash = hash.trim().to_ascii_lowercase();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid blob hash: {hash}"));
+    }
+    Ok(blob_dir.join(&hash[..2]).join(hash))
+}
+
+fn store_blob(blob_dir: &Path, raw: &[u8]) -> Result<String, String> {
+    let hash = compute_bytes_hash_hex(raw);
+    let path = blob_file_path(blob_dir, &hash)?;
+    if path.exists() {
+        return Ok(hash);
+    }
+    write_file_atomically(&path, raw)?;
+    Ok(hash)
+}
+
+fn read_blob(blob_dir: &Path, hash: &str) -> Result<Vec<u8>, String> {
+    let path = blob_file_path(blob_dir, hash)?;
+    fs::read(&path).map_err(|err| format!("Read blob failed ({}): {err}", path.display()))
+}
+
+fn conversation_has_inline_blobs(conversation: &Conversation) -> bool {
+    conversation
+        .messages
+        .iter()
+        .chain(conversation.alternate_messages.iter())
+        .flat_map(|m| m.parts.iter())
+        .any(|part| match part {
+            MessagePart::Image { bytes_base64, .. } | MessagePart::Audio { bytes_base64, .. } => {
+                !bytes_base64.is_empty()
+            }
+            MessagePart::Text { .. } => false,
+        })
+}
+
+fn app_data_has_inline_blobs(data: &AppData) -> bool {
+    data.conversations
+        .iter()
+        .chain(data.archived_conversations.iter().map(|a| &a.source_conversation))
+        .any(conversation_has_inline_blobs)
+}
+
+fn externalize_message_blobs(blob_dir: &Path, messages: &mut [ChatMessage]) -> Result<(), String> {
+    for part in messages.iter_mut().flat_map(|m| m.parts.iter_mut()) {
+        let Some((bytes_base64, blob_hash)) = part.binary_fields_mut() else {
+            continue;
+        };
+        if bytes_base64.is_empty() {
+            continue;
+        }
+        let raw = B64
+            .decode(bytes_base64.trim())
+            .map_err(|err| format!("Decode attachment base64 failed: {err}"))?;
+        *blob_hash = Some(store_blob(blob_dir, &raw)?);
+        bytes_base64.clear();
+    }
+    Ok(())
+}
+
+fn externalize_app_data_blobs(blob_dir: &Path, data: &mut AppData) -> Result<(), String> {
+    let conversations = data
+        .conversations
+        .iter_mut()
+        .chain(data.archived_conversations.iter_mut().map(|a| &mut a.source_conversation));
+    for conversation in conversations {
+        externalize_message_blobs(blob_dir, &mut conversation.messages)?;
+        externalize_message_blobs(blob_dir, &mut conversation.alternate_messages)?;
+    }
+    Ok(())
+}
+
+fn hydrate_message_blobs(blob_dir: &Path, messages: &mut [ChatMessage]) {
+    for part in messages.iter_mut().flat_map(|m| m.parts.iter_mut()) {
+        let Some((bytes_base64, Some(hash))) = part.binary_fields_mut() else {
+            continue;
+        };
+        if !bytes_base64.is_empty() {
+            continue;
+        }
+        match read_blob(blob_dir, hash) {
+            Ok(raw) => *bytes_base64 = B64.encode(raw),
+            Err(err) => eprintln!("[BLOB] {err}"),
+        }
+    }
+}
+
+fn collect_referenced_blob_hashes(data: &AppData) -> std::collections::HashSet<String> {
+    data.conversations
+        .iter()
+        .chain(data.archived_conversations.iter().map(|a| &a.source_conversation))
+        .flat_map(|c| c.messages.iter().chain(c.alternate_messages.iter()))
+        .flat_map(|m| m.parts.iter())
+        .filter_map(|part| part.blob_hash().map(|h| h.to_ascii_lowercase()))
+        .collect()
+}
+
+/// 快照里的会话仍引用的附件；任一快照读不出来时返回错误，由调用方跳过本次回收。
+fn collect_backup_blob_hashes(
+    data_path: &Path,
+) -> Result<std::collections::HashSet<String>, String> {
+    let root = backup_root_dir(data_path);
+    let mut hashes = std::collections::HashSet::new();
+    for backup_id in list_backup_ids(&root)? {
+        let db_path = root.join(&backup_id).join(data_path.file_name().unwrap_or_default());
+        if !db_path.exists() {
             continue;
         }
+        let conn = rusqlite::Connection::open_with_flags(
+            &db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )
+        .map_err(|err| format!("Open backup {backup_id} failed: {err}"))?;
+        let data = load_app_data_from_db(&conn, true)
+            .map_err(|err| format!("Read backup {backup_id} failed: {err}"))?;
+        hashes.extend(collect_referenced_blob_hashes(&data));
+    }
+    Ok(hashes)
+}
 
-        let content = fs::read_to_string(&path)
-            .map_err(|err| format!("Read debug config failed ({}): {err}", path.display()))?;
-        let parsed = serde_json::from_str::<DebugApiConfig>(&content)
-            .map_err(|err| format!("Parse debug config failed ({}): {err}", path.display()))?;
-        return Ok(Some(parsed));
+/// 删除当前数据与保留中的快照都不再引用的附件，保证恢复快照后附件仍在。
+fn gc_unreferenced_blobs(data_path: &Path, data: &AppData) -> Result<usize, String> {
+    let blob_dir = blob_storage_dir(data_path);
+    if !blob_dir.exists() {
+        return Ok(0);
+    }
+    let deferred = data
+        .conversations
+        .iter()
+        .chain(data.archived_conversations.iter().map(|a| &a.source_conversation))
+        .any(|c| c.messages_deferred);
+    // 只读了头部的数据看不到未加载会话的附件，改从数据库完整读取引用。
+    let mut referenced = if deferred {
+        let conn = open_app_db(&data_path.to_path_buf())?;
+        collect_referenced_blob_hashes(&load_app_data_from_db(&conn, true)?)
+    } else {
+        collect_referenced_blob_hashes(data)
+    };
+    referenced.extend(collect_backup_blob_hashes(data_path)?);
+    let mut removed = 0usize;
+    let shards = fs::read_dir(&blob_dir).map_err(|err| format!("Read blob dir failed: {err}"))?;
+    for shard in shards.flatten() {
+        let shard_path = shard.path();
+        if !shard_path.is_dir() {
+            continue;
+        }
+        let entries =
+            fs::read_dir(&shard_path).map_err(|err| format!("Read blob dir failed: {err}"))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if referenced.contains(&name) {
+                continue;
+            }
+            if fs::remove_file(entry.path()).is_ok() {
+                removed += 1;
+            }
+        }
+        let _ = fs::remove_dir(&shard_path);
     }
-    Ok(None)
+    Ok(removed)
 }
 
 fn resolve_selected_api_config(
@@ -174,54 +576,30 @@ fn resolve_selected_api_config(
         return Some(found.clone());
     }
 
-    app_config.api_configs.first().cloned()
+    app_config
+        .api_configs
+        .iter()
+        .find(|a| a.enable_text && !a.request_format.is_openai_tts())
+        .or_else(|| app_config.api_configs.first())
+        .cloned()
 }
 
 fn resolve_api_config(
     app_config: &AppConfig,
     requested_id: Option<&str>,
 ) -> Result<ResolvedApiConfig, String> {
-    if let Some(debug_cfg) = read_debug_api_config()? {
-        let enabled = debug_cfg.enabled.unwrap_or(true);
-        let request_format_ok = debug_cfg
-            .request_format
-            .unwrap_or(RequestFormat::OpenAI)
-            .is_openai_style();
-
-        if enabled && request_format_ok {
-            if debug_cfg.api_key.trim().is_empty() {
-                return Err(".debug/api-key.json exists but apiKey is empty.".to_string());
-            }
-            return Ok(ResolvedApiConfig {
-                request_format: RequestFormat::OpenAI,
-                base_url: debug_cfg.base_url.trim().to_string(),
-                api_key: debug_cfg.api_key.trim().to_string(),
-                model: debug_cfg.model.trim().to_string(),
-                temperature: debug_cfg
-                    .temperature
-                    .unwrap_or(default_api_temperature())
-                    .clamp(0.0, 2.0),
-                fixed_test_prompt: debug_cfg
-                    .fixed_test_prompt
-                    .unwrap_or_else(|| "EASY_CALL_AI_CACHE_TEST_V1".to_string()),
-            });
-        }
-    }
-
     let selected = resolve_selected_api_config(app_config, requested_id).ok_or_else(|| {
         "No API config configured. Please add at least one API config.".to_string()
     })?;
 
-    if selected.api_key.trim().is_empty() {
-        return Err(
-            "Selected API config API key is empty. Please fill it in settings.".to_string(),
-        );
-    }
+    let (api_key, api_key_refs) = select_api_key(&selected)?;
 
     Ok(ResolvedApiConfig {
+        api_config_id: selected.id.clone(),
         request_format: selected.request_format,
         base_url: selected.base_url.trim().to_string(),
-        api_key: selected.api_key.trim().to_string(),
+        api_key,
+        api_key_refs,
         model: selected.model.trim().to_string(),
         temperature: selected.temperature.clamp(0.0, 2.0),
         fixed_test_prompt: "EASY_CALL_AI_CACHE_TEST_V1".to_string(),
@@ -246,7 +624,7 @@ fn resolve_vision_api_config(app_config: &AppConfig) -> Result<ApiConfig, String
     if api.base_url.trim().is_empty() {
         return Err("图转文AI Base URL is empty.".to_string());
     }
-    if api.api_key.trim().is_empty() {
+    if api_key_pool(&api).is_empty() {
         return Err("图转文AI API key is empty.".to_string());
     }
     if api.model.trim().is_empty() {
@@ -261,13 +639,17 @@ fn decode_image_bytes(image: &BinaryPart) -> Result<Vec<u8>, String> {
         .map_err(|err| format!("Decode image base64 failed: {err}"))
 }
 
-fn compute_image_hash_hex(image: &BinaryPart) -> Result<String, String> {
+fn compute_bytes_hash_hex(raw: &[u8]) -> String {
     use sha2::{Digest, Sha256};
 
-    let raw = decode_image_bytes(image)?;
     let mut hasher = Sha256::new();
     hasher.update(raw);
-    Ok(format!("{:x}", hasher.finalize()))
+    format!("{:x}", hasher.finalize())
+}
+
+fn compute_image_hash_hex(image: &BinaryPart) -> Result<String, String> {
+    let raw = decode_image_bytes(image)?;
+    Ok(compute_bytes_hash_hex(&raw))
 }
 
 fn find_image_text_cache(
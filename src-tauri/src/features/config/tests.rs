@@ -28,6 +28,1005 @@
         assert!(!h1.is_empty());
     }
 
+    fn test_image_message(bytes: &[u8]) -> ChatMessage {
+        let mut message = test_text_message("user", "look", &now_iso());
+        message.parts.push(MessagePart::Image {
+            mime: "image/webp".to_string(),
+            bytes_base64: B64.encode(bytes),
+            blob_hash: None,
+            name: None,
+            compressed: true,
+        });
+        message
+    }
+
+    #[test]
+    fn write_app_data_should_move_attachments_into_blob_store() {
+        let dir = std::env::temp_dir().join(format!("easy-call-blob-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![test_image_message(b"image-bytes")],
+            None,
+        ));
+
+        write_app_data(&data_path, &data).expect("write app data");
+        let raw = fs::read(&data_path).expect("read app data");
+        assert!(!String::from_utf8_lossy(&raw).contains(&B64.encode(b"image-bytes")));
+
+        let mut loaded = read_app_data(&data_path).expect("read app data");
+        let hash = loaded.conversations[0].messages[0].parts[1]
+            .blob_hash()
+            .expect("blob hash")
+            .to_string();
+        assert_eq!(hash, compute_bytes_hash_hex(b"image-bytes"));
+
+        let blob_dir = blob_storage_dir(&data_path);
+        hydrate_message_blobs(&blob_dir, &mut loaded.conversations[0].messages);
+        match &loaded.conversations[0].messages[0].parts[1] {
+            MessagePart::Image { bytes_base64, .. } => {
+                assert_eq!(bytes_base64, &B64.encode(b"image-bytes"))
+            }
+            other => panic!("unexpected part: {other:?}"),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn gc_unreferenced_blobs_should_keep_referenced_blobs_only() {
+        let dir = std::env::temp_dir().join(format!("easy-call-blob-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![test_image_message(b"kept"), test_image_message(b"dropped")],
+            None,
+        ));
+        write_app_data(&data_path, &data).expect("write app data");
+
+        let mut loaded = read_app_data(&data_path).expect("read app data");
+        loaded.conversations[0].messages.truncate(1);
+        let blob_dir = blob_storage_dir(&data_path);
+        let removed = gc_unreferenced_blobs(&data_path, &loaded).expect("gc");
+        assert_eq!(removed, 1);
+        assert!(read_blob(&blob_dir, &compute_bytes_hash_hex(b"kept")).is_ok());
+        assert!(read_blob(&blob_dir, &compute_bytes_hash_hex(b"dropped")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn gc_unreferenced_blobs_should_keep_blobs_referenced_by_backups() {
+        let dir = std::env::temp_dir().join(format!("easy-call-blob-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![test_image_message(b"in-backup")],
+            None,
+        ));
+        write_app_data(&data_path, &data).expect("write app data");
+        snapshot_current_files(&dir.join("config.toml"), &data_path, "manual")
+            .expect("snapshot");
+
+        let mut loaded = read_app_data(&data_path).expect("read app data");
+        loaded.conversations[0].messages.clear();
+        write_app_data(&data_path, &loaded).expect("write app data");
+        let removed = gc_unreferenced_blobs(&data_path, &loaded).expect("gc");
+        assert_eq!(removed, 0);
+        let blob_dir = blob_storage_dir(&data_path);
+        assert!(read_blob(&blob_dir, &compute_bytes_hash_hex(b"in-backup")).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_app_data_should_append_new_messages_incrementally() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![test_text_message("user", "hi", "2026-01-01T00:00:00Z")],
+            None,
+        ));
+        data.memories.push(MemoryEntry {
+            id: "m1".to_string(),
+            content: "likes tea".to_string(),
+            keywords: vec!["tea".to_string()],
+            created_at: now_iso(),
+            updated_at: now_iso(),
+        });
+        upsert_image_text_cache(&mut data, "h1", "vision-a", "text-a");
+        write_app_data(&data_path, &data).expect("first write");
+
+        let conn = open_app_db(&data_path).expect("open db");
+        conn.execute("UPDATE messages SET created_at = 'untouched' WHERE seq = 0", [])
+            .expect("mark first row");
+        drop(conn);
+
+        data.conversations[0].messages.push(test_text_message(
+            "assistant",
+            "hello",
+            "2026-01-01T00:00:01Z",
+        ));
+        write_app_data(&data_path, &data).expect("second write");
+
+        let conn = open_app_db(&data_path).expect("open db");
+        let marker: String = conn
+            .query_row("SELECT created_at FROM messages WHERE seq = 0", [], |row| row.get(0))
+            .expect("first row");
+        assert_eq!(marker, "untouched");
+        drop(conn);
+
+        let loaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(loaded.conversations.len(), 1);
+        assert_eq!(loaded.conversations[0].messages.len(), 2);
+        assert_eq!(loaded.conversations[0].messages[1].role, "assistant");
+        assert_eq!(loaded.memories.len(), 1);
+        assert_eq!(
+            find_image_text_cache(&loaded, "h1", "vision-a"),
+            Some("text-a".to_string())
+        );
+
+        let mut trimmed = loaded.clone();
+        trimmed.conversations[0].messages.truncate(1);
+        trimmed.memories.clear();
+        write_app_data(&data_path, &trimmed).expect("third write");
+        let reloaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(reloaded.conversations[0].messages.len(), 1);
+        assert!(reloaded.memories.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn search_history_should_rank_cjk_hits_and_follow_incremental_writes() {
+        let dir = std::env::temp_dir().join(format!("easy-call-search-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        let mut assistant = test_text_message("assistant", "好的，我查一下", "2026-02-01T00:00:01Z");
+        assistant.tool_call = Some(vec![serde_json::json!({
+            "role": "tool",
+            "tool_call_id": "call_1",
+            "content": "北京明天晴，气温 12 度"
+        })]);
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![
+                test_text_message("user", "帮我看看北京的天气", "2026-02-01T00:00:00Z"),
+                assistant,
+            ],
+            None,
+        ));
+        let mut archived = test_active_conversation_with_messages(
+            vec![test_text_message("user", "上个月讨论的 Rust 项目计划", "2026-01-05T00:00:00Z")],
+            None,
+        );
+        archived.status = "archived".to_string();
+        archived.agent_id = "agent-b".to_string();
+        data.archived_conversations.push(ConversationArchive {
+            archive_id: "archive-1".to_string(),
+            archived_at: "2026-01-06T00:00:00Z".to_string(),
+            reason: "manual_force_archive".to_string(),
+            summary: "确定了 Rust 项目的里程碑".to_string(),
+            source_conversation: archived,
+        });
+        write_app_data(&data_path, &data).expect("write app data");
+
+        let search = |input: SearchHistoryInput| {
+            let mut conn = open_app_db(&data_path).expect("open db");
+            search_history_in_db(&mut conn, &input).expect("search")
+        };
+        let query = |q: &str| SearchHistoryInput {
+            query: q.to_string(),
+            ..SearchHistoryInput::default()
+        };
+
+        let result = search(query("北京天气"));
+        assert_eq!(result.total, 1);
+        assert_eq!(result.hits[0].role, "user");
+        let hit = &result.hits[0];
+        let highlighted = &hit.highlights[0];
+        let units = hit.snippet.encode_utf16().collect::<Vec<_>>();
+        assert_eq!(String::from_utf16_lossy(&units[highlighted.start..highlighted.end]), "北京");
+
+        let tool_hits = search(SearchHistoryInput {
+            role: Some("tool".to_string()),
+            ..query("气温")
+        });
+        assert_eq!(tool_hits.total, 1);
+
+        let rust_hits = search(query("rust 项目"));
+        assert_eq!(rust_hits.total, 2);
+        assert!(rust_hits.hits.iter().all(|h| h.archive_id.as_deref() == Some("archive-1")));
+        assert!(rust_hits.hits.iter().any(|h| h.role == "summary"));
+        assert_eq!(
+            search(SearchHistoryInput {
+                agent_id: Some("agent-b".to_string()),
+                to: Some("2026-01-05".to_string()),
+                ..query("rust")
+            })
+            .total,
+            1
+        );
+
+        data.conversations[0].messages.truncate(1);
+        data.conversations[0].messages[0].parts = vec![MessagePart::Text {
+            text: "上海的天气".to_string(),
+        }];
+        data.archived_conversations.clear();
+        write_app_data(&data_path, &data).expect("rewrite app data");
+        assert_eq!(search(query("北京")).total, 0);
+        assert_eq!(search(query("rust")).total, 0);
+        assert_eq!(search(query("上海")).total, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_app_data_should_migrate_legacy_json_once() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        let data_path = dir.join("app_data.db");
+        let mut legacy = AppData::default();
+        let mut conversation = test_active_conversation_with_messages(
+            vec![test_image_message(b"legacy-image")],
+            None,
+        );
+        conversation.title = "Legacy".to_string();
+        legacy.conversations.push(conversation);
+        let json_path = legacy_app_data_json_path(&data_path);
+        fs::write(&json_path, serde_json::to_string(&legacy).expect("serialize"))
+            .expect("write legacy json");
+
+        let loaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(loaded.conversations[0].title, "Legacy");
+        assert_eq!(loaded.conversations[0].messages.len(), 1);
+        assert!(loaded.conversations[0].messages[0].parts[1].blob_hash().is_some());
+        assert!(!json_path.exists());
+        assert!(dir.join("app_data.json.migrated").exists());
+
+        let reloaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(reloaded.conversations[0].title, "Legacy");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_latest_active_conversation_should_use_latest_active_row() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        let mut older = test_active_conversation_with_messages(Vec::new(), None);
+        older.agent_id = DEFAULT_AGENT_ID.to_string();
+        let mut newer = test_active_conversation_with_messages(
+            vec![test_text_message("user", "latest", "2026-01-01T00:00:00Z")],
+            None,
+        );
+        newer.agent_id = DEFAULT_AGENT_ID.to_string();
+        let mut archived = newer.clone();
+        archived.id = Uuid::new_v4().to_string();
+        archived.status = "archived".to_string();
+        data.conversations = vec![older, newer.clone(), archived];
+        write_app_data(&data_path, &data).expect("write app data");
+
+        let found = read_latest_active_conversation(&data_path, "api", DEFAULT_AGENT_ID)
+            .expect("lookup")
+            .expect("conversation");
+        assert_eq!(found.id, newer.id);
+        assert_eq!(found.messages.len(), 1);
+        assert!(read_latest_active_conversation(&data_path, "other-api", DEFAULT_AGENT_ID)
+            .expect("lookup")
+            .is_none());
+        assert!(read_latest_active_conversation(&data_path, "api", "missing-agent")
+            .expect("lookup")
+            .is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_app_data_headers_should_keep_unloaded_messages_on_write() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let mut data = AppData::default();
+        let first = test_active_conversation_with_messages(
+            vec![test_text_message("user", "first", "2026-01-01T00:00:00Z")],
+            None,
+        );
+        let second = test_active_conversation_with_messages(
+            vec![test_text_message("user", "second", "2026-01-01T00:00:00Z")],
+            None,
+        );
+        data.conversations = vec![first.clone(), second.clone()];
+        write_app_data(&data_path, &data).expect("write app data");
+
+        let mut headers = read_app_data_headers(&data_path).expect("read headers");
+        assert!(headers.conversations.iter().all(|c| c.messages_deferred && c.messages.is_empty()));
+        assert_eq!(
+            read_conversation_message_counts(&data_path).expect("counts").get(&first.id),
+            Some(&1)
+        );
+        load_conversation_messages_by_id(&data_path, &mut headers, &second.id).expect("load");
+        headers.conversations[0].title = "Renamed".to_string();
+        headers.conversations[1]
+            .messages
+            .push(test_text_message("assistant", "reply", "2026-01-01T00:00:01Z"));
+        write_app_data(&data_path, &headers).expect("write headers");
+
+        let reloaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(reloaded.conversations[0].title, "Renamed");
+        assert_eq!(reloaded.conversations[0].messages.len(), 1);
+        assert_eq!(reloaded.conversations[1].messages.len(), 2);
+
+        // 未加载消息的会话被复制成新行时拒绝写入，避免丢消息。
+        let mut copied = headers.conversations[0].clone();
+        copied.id = Uuid::new_v4().to_string();
+        headers.conversations.push(copied);
+        assert!(write_app_data(&data_path, &headers).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_conversation_data_should_only_touch_the_given_conversation() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let data = AppData {
+            conversations: vec![
+                test_active_conversation_with_messages(vec![test_image_message(b"other")], None),
+                test_active_conversation_with_messages(
+                    vec![test_text_message("user", "hi", "2026-01-01T00:00:00Z")],
+                    None,
+                ),
+            ],
+            ..AppData::default()
+        };
+        write_app_data(&data_path, &data).expect("write app data");
+
+        let mut headers = read_app_data_headers(&data_path).expect("read headers");
+        let target_id = headers.conversations[1].id.clone();
+        load_conversation_messages_by_id(&data_path, &mut headers, &target_id).expect("load");
+        headers.conversations[0].title = "Not written".to_string();
+        headers.conversations[1].messages.push(test_image_message(b"reply"));
+        write_conversation_data(&data_path, &headers, &target_id).expect("write conversation");
+
+        let reloaded = read_app_data(&data_path).expect("read app data");
+        assert_ne!(reloaded.conversations[0].title, "Not written");
+        assert_eq!(reloaded.conversations[1].messages.len(), 2);
+        assert!(reloaded.conversations[1].messages[1].parts[1].blob_hash().is_some());
+        assert!(write_conversation_data(&data_path, &headers, "missing").is_err());
+
+        // 只读了头部的数据做回收时，未加载会话的附件仍被保留。
+        let removed = gc_unreferenced_blobs(&data_path, &headers).expect("gc");
+        assert_eq!(removed, 0);
+        let blob_dir = blob_storage_dir(&data_path);
+        assert!(read_blob(&blob_dir, &compute_bytes_hash_hex(b"other")).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_config_should_move_aside_unparseable_file() {
+        let dir = std::env::temp_dir().join(format!("easy-call-corrupt-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        let config_path = dir.join("config.toml");
+        fs::write(&config_path, "hotkey = [broken").expect("write config");
+
+        let config = read_config(&config_path).expect("read config");
+        assert!(!config.api_configs.is_empty());
+        assert!(!config_path.exists());
+
+        let files = list_corrupt_data_files_in(&dir).expect("list corrupt files");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].kind, "config");
+        assert!(files[0].file_name.starts_with("config.toml."));
+        assert!(files[0].moved_at.is_some());
+        assert_eq!(
+            fs::read_to_string(&files[0].path).expect("read corrupt copy"),
+            "hotkey = [broken"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_app_data_should_move_aside_corrupt_database() {
+        let dir = std::env::temp_dir().join(format!("easy-call-corrupt-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        let data_path = dir.join("app_data.db");
+        fs::write(&data_path, vec![0x42u8; 4096]).expect("write garbage");
+
+        let data = read_app_data(&data_path).expect("read app data");
+        assert!(data.conversations.is_empty());
+
+        let files = list_corrupt_data_files_in(&dir).expect("list corrupt files");
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].kind, "appData");
+        assert_eq!(files[0].size_bytes, 4096);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn migrate_app_data_should_run_steps_and_refuse_newer_versions() {
+        let dir = std::env::temp_dir().join(format!("easy-call-migrate-{}", Uuid::new_v4()));
+        let mut data = AppData {
+            version: 1,
+            ..AppData::default()
+        };
+        data.conversations.push(test_active_conversation_with_messages(
+            vec![test_image_message(b"v1-image")],
+            None,
+        ));
+        assert!(migrate_app_data(&mut data, &dir).expect("migrate"));
+        assert_eq!(data.version, APP_DATA_SCHEMA_VERSION);
+        assert!(!app_data_has_inline_blobs(&data));
+        assert!(!migrate_app_data(&mut data, &dir).expect("migrate again"));
+
+        let mut newer = AppData {
+            version: APP_DATA_SCHEMA_VERSION + 1,
+            ..AppData::default()
+        };
+        let err = migrate_app_data(&mut newer, &dir).expect_err("newer version");
+        assert!(err.contains("newer than supported"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_app_data_should_reject_newer_database_without_moving_it() {
+        let dir = std::env::temp_dir().join(format!("easy-call-db-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        write_app_data(&data_path, &AppData::default()).expect("write app data");
+        let conn = open_app_db(&data_path).expect("open db");
+        conn.execute(
+            "UPDATE meta SET value = ?1 WHERE key = 'version'",
+            [(APP_DATA_SCHEMA_VERSION + 1).to_string()],
+        )
+        .expect("bump version");
+        conn.execute("UPDATE agents SET body = '[\"future\"]'", [])
+            .expect("future rows");
+        drop(conn);
+
+        let err = read_app_data(&data_path).expect_err("newer database");
+        assert!(err.contains("newer than supported"));
+        assert!(read_app_data_headers(&data_path).is_err());
+        let names = fs::read_dir(&dir)
+            .expect("read dir")
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"app_data.db".to_string()));
+        assert!(!names.iter().any(|n| n.contains("corrupt")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_file_atomically_should_replace_content_without_leftovers() {
+        let dir = std::env::temp_dir().join(format!("easy-call-atomic-{}", Uuid::new_v4()));
+        let path = dir.join("config.toml");
+        write_file_atomically(&path, b"first").expect("first write");
+        write_file_atomically(&path, b"second").expect("second write");
+        assert_eq!(fs::read(&path).expect("read"), b"second");
+        let names = fs::read_dir(&dir)
+            .expect("read dir")
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["config.toml".to_string()]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn daily_backup_should_snapshot_once_and_prune_old_days() {
+        let dir = std::env::temp_dir().join(format!("easy-call-backup-{}", Uuid::new_v4()));
+        let config_path = dir.join("config.toml");
+        let data_path = dir.join("app_data.db");
+        let root = backup_root_dir(&config_path);
+        for day in 1..=DAILY_BACKUP_KEEP_COUNT {
+            let old = root.join(format!("2000-01-{day:02}"));
+            fs::create_dir_all(&old).expect("create old backup");
+            fs::write(old.join("config.toml"), "old").expect("write old backup");
+        }
+
+        write_config(&config_path, &AppConfig::default()).expect("first write");
+        assert_eq!(list_backup_ids(&root).expect("ids").len(), DAILY_BACKUP_KEEP_COUNT);
+
+        let config = AppConfig {
+            hotkey: "Alt+K".to_string(),
+            ..AppConfig::default()
+        };
+        write_config(&config_path, &config).expect("second write");
+        write_config(&config_path, &AppConfig::default()).expect("third write");
+
+        let ids = list_backup_ids(&root).expect("ids");
+        assert_eq!(ids.len(), DAILY_BACKUP_KEEP_COUNT);
+        assert!(!ids.contains(&"2000-01-01".to_string()));
+        let today = root.join(today_backup_id()).join("config.toml");
+        let snapshot = fs::read_to_string(&today).expect("today snapshot");
+        assert!(!snapshot.contains("Alt+K"));
+
+        let backups = list_backups_in(&config_path, &data_path).expect("list backups");
+        assert_eq!(backups[0].backup_id, today_backup_id());
+        assert_eq!(backups[0].files[0].kind, "config");
+
+        // 操作前快照单独计数，不挤掉每日快照，也不被每日快照挤掉。
+        for second in 0..=OPERATION_BACKUP_KEEP_COUNT {
+            let id = format!("2000-02-01-0000{second:02}-{PRE_RESTORE_BACKUP_SUFFIX}");
+            fs::create_dir_all(root.join(id)).expect("create pre-restore backup");
+        }
+        prune_operation_backups(&root, PRE_RESTORE_BACKUP_SUFFIX).expect("prune operations");
+        prune_backups(&root, is_daily_backup_id, 1).expect("prune daily");
+        let ids = list_backup_ids(&root).expect("ids");
+        assert_eq!(ids.iter().filter(|id| is_daily_backup_id(id)).count(), 1);
+        assert_eq!(
+            ids.iter().filter(|id| id.ends_with(PRE_RESTORE_BACKUP_SUFFIX)).count(),
+            OPERATION_BACKUP_KEEP_COUNT
+        );
+        assert!(!ids.contains(&format!("2000-02-01-000000-{PRE_RESTORE_BACKUP_SUFFIX}")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn restore_backup_should_overwrite_and_keep_pre_restore_copy() {
+        let dir = std::env::temp_dir().join(format!("easy-call-restore-{}", Uuid::new_v4()));
+        let config_path = dir.join("config.toml");
+        let data_path = dir.join("app_data.db");
+        let snapshot_dir = backup_root_dir(&config_path).join("2000-01-01");
+        fs::create_dir_all(&snapshot_dir).expect("create snapshot");
+        let config_text = |hotkey: &str| {
+            toml::to_string_pretty(&AppConfig {
+                hotkey: hotkey.to_string(),
+                ..AppConfig::default()
+            })
+            .expect("serialize config")
+        };
+        fs::write(snapshot_dir.join("config.toml"), config_text("Alt+R")).expect("write snapshot");
+        fs::write(&config_path, config_text("Alt+C")).expect("write current");
+
+        let restored = restore_backup_files(&config_path, &data_path, "2000-01-01", &[])
+            .expect("restore");
+        assert_eq!(restored, vec!["config".to_string()]);
+        assert_eq!(read_config(&config_path).expect("read config").hotkey, "Alt+R");
+
+        let pre_restore = list_backup_ids(&backup_root_dir(&config_path))
+            .expect("ids")
+            .into_iter()
+            .find(|id| id.ends_with(PRE_RESTORE_BACKUP_SUFFIX))
+            .expect("pre-restore backup");
+        let saved_path = backup_root_dir(&config_path)
+            .join(pre_restore)
+            .join("config.toml");
+        let saved = fs::read_to_string(saved_path).expect("read pre-restore");
+        assert!(saved.contains("Alt+C"));
+        assert!(restore_backup_files(&config_path, &data_path, "../x", &[]).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn seal_config_secrets_should_store_refs_and_keep_masked_keys() {
+        let dir = std::env::temp_dir().join(format!("easy-call-secret-{}", Uuid::new_v4()));
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = "sk-plain".to_string();
+
+        assert!(seal_config_secrets_in(&dir, None, &mut config, None).expect("seal"));
+        let reference = config.api_configs[0].api_key.clone();
+        assert!(is_secret_ref(&reference));
+        let stored = fs::read_to_string(dir.join(SECRET_STORE_FILE_NAME)).expect("read store");
+        assert!(!stored.contains("sk-plain"));
+        assert_eq!(
+            reveal_secret_in(&dir, None, &reference).expect("reveal"),
+            "sk-plain"
+        );
+
+        let mut masked = config.clone();
+        mask_config_secrets(&mut masked);
+        assert_eq!(masked.api_configs[0].api_key, masked_secret_for(&reference));
+        assert!(!seal_config_secrets_in(&dir, None, &mut masked, Some(&config)).expect("reseal"));
+        assert_eq!(masked.api_configs[0].api_key, reference);
+
+        // 复制出的新配置沿用来源的引用；找不到对应引用的掩码报错而不是存成空 key。
+        let mut duplicated = masked.clone();
+        let mut copy = config.api_configs[0].clone();
+        copy.id = "copied".to_string();
+        copy.api_key = masked_secret_for(&reference);
+        duplicated.api_configs.push(copy);
+        seal_config_secrets_in(&dir, None, &mut duplicated, Some(&config)).expect("duplicate");
+        assert_eq!(duplicated.api_configs[1].api_key, reference);
+        duplicated.api_configs[1].api_key = MASKED_SECRET.to_string();
+        assert!(seal_config_secrets_in(&dir, None, &mut duplicated, Some(&config)).is_err());
+
+        masked.api_configs[0].api_key.clear();
+        seal_config_secrets_in(&dir, None, &mut masked, Some(&config)).expect("clear");
+        assert!(read_secret_store(&dir).expect("store").secrets.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn migrate_plain_api_keys_should_scrub_plaintext_from_backups() {
+        let dir = std::env::temp_dir().join(format!("easy-call-secret-{}", Uuid::new_v4()));
+        let config_path = dir.join("config.toml");
+        let plain_toml = |key: &str| {
+            let mut config = AppConfig::default();
+            config.api_configs[0].api_key = key.to_string();
+            toml::to_string_pretty(&config).expect("serialize config")
+        };
+        let root = backup_root_dir(&config_path);
+        for (id, key) in [("2000-01-01", "sk-live"), ("2000-01-02", "sk-retired")] {
+            fs::create_dir_all(root.join(id)).expect("create backup");
+            fs::write(root.join(id).join("config.toml"), plain_toml(key)).expect("write backup");
+        }
+        fs::write(&config_path, plain_toml("sk-live")).expect("write config");
+
+        let config = read_config(&config_path).expect("read config");
+        let reference = config.api_configs[0].api_key.clone();
+        assert!(is_secret_ref(&reference));
+        let same = fs::read_to_string(root.join("2000-01-01").join("config.toml")).expect("read");
+        assert!(!same.contains("sk-live") && same.contains(&reference));
+        let other = fs::read_to_string(root.join("2000-01-02").join("config.toml")).expect("read");
+        assert!(!other.contains("sk-retired"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn secret_store_passphrase_should_reencrypt_and_require_unlock() {
+        let dir = std::env::temp_dir().join(format!("easy-call-secret-{}", Uuid::new_v4()));
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = "sk-pass".to_string();
+        seal_config_secrets_in(&dir, None, &mut config, None).expect("seal");
+        let reference = config.api_configs[0].api_key.clone();
+
+        let cached = change_secret_store_passphrase_in(&dir, None, Some("hunter2"))
+            .expect("set passphrase");
+        assert!(cached.is_some());
+        assert!(reveal_secret_in(&dir, None, &reference)
+            .expect_err("locked")
+            .contains("locked"));
+        assert!(unlock_secret_store_in(&dir, "wrong").is_err());
+        let key = unlock_secret_store_in(&dir, "hunter2").expect("unlock");
+        assert_eq!(
+            reveal_secret_in(&dir, Some(key), &reference).expect("reveal"),
+            "sk-pass"
+        );
+
+        assert!(change_secret_store_passphrase_in(&dir, Some(key), None)
+            .expect("clear passphrase")
+            .is_none());
+        assert_eq!(
+            reveal_secret_in(&dir, None, &reference).expect("reveal"),
+            "sk-pass"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reveal_api_key_should_resolve_env_and_file_refs() {
+        let var = format!("EASY_CALL_TEST_KEY_{}", Uuid::new_v4().simple());
+        assert!(reveal_api_key(&format!("env:{var}"))
+            .expect_err("missing env")
+            .contains(&var));
+        std::env::set_var(&var, " sk-env \n");
+        assert_eq!(reveal_api_key(&format!("env:{var}")).expect("env"), "sk-env");
+        std::env::remove_var(&var);
+
+        let dir = std::env::temp_dir().join(format!("easy-call-keyref-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        let key_path = dir.join("key.txt");
+        assert!(reveal_api_key(&format!("file:{}", key_path.display())).is_err());
+        fs::write(&key_path, "sk-file\n").expect("write key");
+        assert_eq!(
+            reveal_api_key(&format!("file:{}", key_path.display())).expect("file"),
+            "sk-file"
+        );
+
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = format!("env:{var}");
+        assert!(!seal_config_secrets_in(&dir, None, &mut config, None).expect("seal"));
+        mask_config_secrets(&mut config);
+        assert_eq!(config.api_configs[0].api_key, format!("env:{var}"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn seal_config_secrets_should_match_masked_extra_api_keys_by_tag() {
+        let dir = std::env::temp_dir().join(format!("easy-call-secret-{}", Uuid::new_v4()));
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = "sk-a".to_string();
+        config.api_configs[0].extra_api_keys = vec![
+            "sk-b".to_string(),
+            "sk-c".to_string(),
+            "env:EASY_CALL_B".to_string(),
+        ];
+        seal_config_secrets_in(&dir, None, &mut config, None).expect("seal");
+        let extra_ref = config.api_configs[0].extra_api_keys[1].clone();
+        assert!(is_secret_ref(&extra_ref));
+
+        let mut masked = config.clone();
+        mask_config_secrets(&mut masked);
+        assert_eq!(masked.api_configs[0].extra_api_keys[1], masked_secret_for(&extra_ref));
+        assert_eq!(masked.api_configs[0].extra_api_keys[2], "env:EASY_CALL_B");
+        // 前端删掉中间的 key 后其余 key 前移，仍按掩码标记对应回原引用。
+        masked.api_configs[0].extra_api_keys.remove(0);
+        seal_config_secrets_in(&dir, None, &mut masked, Some(&config)).expect("reseal");
+        assert_eq!(
+            masked.api_configs[0].extra_api_keys,
+            vec![extra_ref.clone(), "env:EASY_CALL_B".to_string()]
+        );
+        assert_eq!(
+            reveal_secret_in(&dir, None, &extra_ref).expect("reveal"),
+            "sk-c"
+        );
+        assert_eq!(read_secret_store(&dir).expect("store").secrets.len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn api_key_rotation_should_skip_rate_limited_key_within_one_request() {
+        let api = ApiConfig {
+            id: format!("rotation-{}", Uuid::new_v4()),
+            api_key: "k1".to_string(),
+            extra_api_keys: vec!["k2".to_string(), "k1".to_string()],
+            ..ApiConfig::default()
+        };
+        assert_eq!(api_key_pool(&api), vec!["k1".to_string(), "k2".to_string()]);
+        let app_config = AppConfig {
+            api_configs: vec![api.clone()],
+            ..AppConfig::default()
+        };
+
+        let resolved = resolve_api_config(&app_config, Some(&api.id)).expect("resolve");
+        assert_eq!(resolved.api_key, "k1");
+        let rt = test_runtime();
+        let used = rt
+            .block_on(call_with_api_key_rotation(&resolved, |attempt| async move {
+                if attempt.api_key == "k1" {
+                    Err(ApiCallError::new(Some(429), "slow down".to_string()))
+                } else {
+                    Ok(attempt.api_key)
+                }
+            }))
+            .expect("rotate to next key");
+        assert_eq!(used, "k2");
+
+        let health = api_key_health_report(&api);
+        assert_eq!(health[0].status, "coolingDown");
+        assert_eq!(health[0].last_status_code, Some(429));
+        assert_eq!(health[1].status, "ok");
+
+        let resolved = resolve_api_config(&app_config, Some(&api.id)).expect("resolve again");
+        assert_eq!(resolved.api_key, "k2");
+        assert_eq!(resolved.api_key_refs, vec!["k2".to_string(), "k1".to_string()]);
+
+        // 只按状态码判断：错误文本里出现 429 也不会换 key。
+        let err = rt
+            .block_on(call_with_api_key_rotation(&resolved, |_| async move {
+                Err::<(), _>(ApiCallError::from("upstream said 429".to_string()))
+            }))
+            .expect_err("non key error");
+        assert_eq!(err, "upstream said 429");
+        assert_eq!(api_key_health_report(&api)[1].status, "ok");
+
+        let body = r#"{"type":"error","error":{"type":"authentication_error"}}"#;
+        assert_eq!(provider_error_body_status(body), Some(401));
+        let body = r#"{"error":{"code":429,"status":"RESOURCE_EXHAUSTED"}}"#;
+        assert_eq!(provider_error_body_status(body), Some(429));
+        assert_eq!(provider_error_body_status("invalid_api_key"), None);
+    }
+
+    #[test]
+    fn resolve_data_dir_should_follow_override_priority() {
+        let root = std::env::temp_dir().join(format!("easy-call-datadir-{}", Uuid::new_v4()));
+        let default_dir = root.join("default");
+        let exe_dir = root.join("exe");
+        fs::create_dir_all(&default_dir).expect("create default dir");
+        fs::create_dir_all(&exe_dir).expect("create exe dir");
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+
+        let loc = resolve_data_dir_from(&args(&["app"]), None, Some(&exe_dir), &default_dir);
+        assert_eq!((loc.dir.clone(), loc.source.as_str()), (default_dir.clone(), "default"));
+
+        fs::write(default_dir.join(DATA_DIR_REDIRECT_FILE_NAME), root.join("moved").to_string_lossy().as_bytes())
+            .expect("write redirect");
+        let loc = resolve_data_dir_from(&args(&["app"]), None, Some(&exe_dir), &default_dir);
+        assert_eq!((loc.dir.clone(), loc.source.as_str()), (root.join("moved"), "redirect"));
+
+        fs::write(exe_dir.join(PORTABLE_MARKER_FILE_NAME), "").expect("write marker");
+        let loc = resolve_data_dir_from(&args(&["app"]), None, Some(&exe_dir), &default_dir);
+        assert_eq!((loc.dir.clone(), loc.source.as_str()), (exe_dir.join("data"), "portable"));
+        assert!(loc.is_fixed());
+
+        let env_dir = root.join("env").to_string_lossy().to_string();
+        let loc = resolve_data_dir_from(&args(&["app"]), Some(env_dir), Some(&exe_dir), &default_dir);
+        assert_eq!(loc.source, "env");
+
+        let flag_dir = root.join("flag").to_string_lossy().to_string();
+        let loc = resolve_data_dir_from(
+            &args(&["app", &format!("--data-dir={flag_dir}")]),
+            Some(root.join("env").to_string_lossy().to_string()),
+            Some(&exe_dir),
+            &default_dir,
+        );
+        assert_eq!((loc.dir.clone(), loc.source.as_str()), (root.join("flag"), "cliFlag"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn move_data_dir_should_move_app_files_only_and_write_redirect() {
+        let root = std::env::temp_dir().join(format!("easy-call-datadir-{}", Uuid::new_v4()));
+        let default_dir = root.join("default");
+        fs::create_dir_all(default_dir.join("avatars")).expect("create avatars");
+        fs::write(default_dir.join("config.toml"), "hotkey = 1").expect("write config");
+        fs::write(default_dir.join("avatars").join("a.webp"), [1u8, 2, 3]).expect("write avatar");
+        fs::write(default_dir.join("notes.txt"), "mine").expect("write unrelated file");
+        let location = resolve_data_dir_from(&[], None, None, &default_dir);
+
+        let busy = root.join("busy");
+        fs::create_dir_all(&busy).expect("create busy");
+        fs::write(busy.join("app_data.db"), "x").expect("write busy");
+        assert!(move_data_dir_contents(&location, &busy).is_err());
+        assert!(move_data_dir_contents(&location, &default_dir.join("nested")).is_err());
+
+        let mut data = AppData::default();
+        data.agents[0].avatar_path = Some(
+            default_dir.join("avatars").join("a.webp").to_string_lossy().to_string(),
+        );
+        write_app_data(&default_dir.join("app_data.db"), &data).expect("write app data");
+
+        let target = root.join("moved");
+        move_data_dir_contents(&location, &target).expect("move");
+        rebase_avatar_paths(&target.join("app_data.db"), &default_dir, &target).expect("rebase");
+        let moved_data = read_app_data(&target.join("app_data.db")).expect("read moved data");
+        assert_eq!(
+            moved_data.agents[0].avatar_path.as_deref().map(PathBuf::from),
+            Some(target.join("avatars").join("a.webp"))
+        );
+        assert_eq!(fs::read(target.join("avatars").join("a.webp")).expect("read avatar"), vec![1u8, 2, 3]);
+        assert!(!default_dir.join("config.toml").exists());
+        assert!(!target.join("notes.txt").exists());
+        assert_eq!(fs::read_to_string(default_dir.join("notes.txt")).expect("unrelated file"), "mine");
+        let moved = resolve_data_dir_from(&[], None, None, &default_dir);
+        assert_eq!((moved.dir.clone(), moved.source.as_str()), (target.clone(), "redirect"));
+
+        move_data_dir_contents(&moved, &default_dir).expect("move back");
+        assert!(default_dir.join("config.toml").exists());
+        assert!(!default_dir.join(DATA_DIR_REDIRECT_FILE_NAME).exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn backup_bundle_should_round_trip_and_report_merge_conflicts() {
+        let root = std::env::temp_dir().join(format!("easy-call-bundle-{}", Uuid::new_v4()));
+        let avatar_dir = root.join("avatars");
+        fs::create_dir_all(&avatar_dir).expect("create avatar dir");
+        let avatar_path = avatar_dir.join("agent-default-agent.webp");
+        fs::write(&avatar_path, [7u8, 8, 9]).expect("write avatar");
+
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = "secret:abc".to_string();
+        config.api_configs[0].extra_api_keys = vec!["env:EASY_CALL_KEY".to_string()];
+        let mut data = AppData::default();
+        data.agents[0].avatar_path = Some(avatar_path.to_string_lossy().to_string());
+        data.conversations.push(Conversation {
+            id: "c1".to_string(),
+            title: "Hello".to_string(),
+            title_settled: false,
+            api_config_id: config.api_configs[0].id.clone(),
+            agent_id: DEFAULT_AGENT_ID.to_string(),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+            last_user_at: None,
+            last_assistant_at: None,
+            last_context_usage_ratio: 0.0,
+            status: "active".to_string(),
+            messages: vec![test_text_message("user", "hi", "2026-01-01T00:00:00Z")],
+            alternate_messages: Vec::new(),
+            forked_from_archive_id: None,
+            compaction: None,
+            messages_deferred: false,
+        });
+        data.memories.push(MemoryEntry {
+            id: "m1".to_string(),
+            content: "likes tea".to_string(),
+            keywords: vec!["tea".to_string()],
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+        });
+
+        let reveal = |_: &str| -> Result<String, String> { Ok("sk-plain".to_string()) };
+        let (bytes, manifest) =
+            build_backup_bundle(&config, &data, &root.join("blobs"), false, &reveal).expect("build");
+        assert!(!manifest.includes_secrets);
+        assert_eq!(manifest.counts.avatars, 1);
+        let bundle = read_backup_bundle(&bytes).expect("read");
+        let bundle_config = bundle.config.clone().expect("config");
+        assert_eq!(bundle_config.api_configs[0].api_key, "");
+        assert_eq!(bundle_config.api_configs[0].extra_api_keys, vec!["env:EASY_CALL_KEY".to_string()]);
+        assert_eq!(bundle.avatars[0].1, vec![7u8, 8, 9]);
+
+        let mut local_config = config.clone();
+        let mut local = data.clone();
+        local.conversations[0].title = "Renamed locally".to_string();
+        local.memories.clear();
+        let result = merge_backup_bundle(&mut local_config, &mut local, bundle.clone(), &avatar_dir)
+            .expect("merge");
+        assert_eq!(result.imported.memories, 1);
+        assert_eq!(result.imported.conversations, 0);
+        assert!(result
+            .conflicts
+            .iter()
+            .any(|c| c.kind == "conversation" && c.id == "c1"));
+        assert!(!result.conflicts.iter().any(|c| c.kind == "agent"));
+        assert_eq!(local.conversations[0].title, "Renamed locally");
+
+        let mut fresh_config = config.clone();
+        let mut fresh = AppData::default();
+        let target_avatars = root.join("other-avatars");
+        let result = replace_with_backup_bundle(&mut fresh_config, &mut fresh, bundle, &target_avatars)
+            .expect("replace");
+        assert_eq!(result.imported.conversations, 1);
+        assert_eq!(fresh_config.api_configs[0].api_key, "secret:abc");
+        let restored_avatar = fresh.agents[0].avatar_path.clone().expect("avatar path");
+        assert_eq!(fs::read(restored_avatar).expect("read avatar"), vec![7u8, 8, 9]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn backup_bundle_should_carry_images_on_inactive_branches() {
+        let root = std::env::temp_dir().join(format!("easy-call-bundle-{}", Uuid::new_v4()));
+        let blob_dir = root.join("blobs");
+        let mut conversation = test_active_conversation_with_messages(
+            vec![test_text_message("user", "hi", "2026-01-01T00:00:00Z")],
+            None,
+        );
+        conversation.alternate_messages = vec![test_image_message(&[1, 2, 3])];
+        externalize_message_blobs(&blob_dir, &mut conversation.alternate_messages)
+            .expect("externalize");
+        let mut archived = conversation.clone();
+        archived.id = "archived".to_string();
+        archived.status = "archived".to_string();
+        let data = AppData {
+            conversations: vec![conversation],
+            archived_conversations: vec![ConversationArchive {
+                archive_id: "archive-1".to_string(),
+                archived_at: "2026-01-02T00:00:00Z".to_string(),
+                reason: "manual_force_archive".to_string(),
+                summary: String::new(),
+                source_conversation: archived,
+            }],
+            ..AppData::default()
+        };
+        let config = AppConfig::default();
+        let reveal = |_: &str| -> Result<String, String> { Ok(String::new()) };
+
+        let (bytes, _) =
+            build_backup_bundle(&config, &data, &blob_dir, false, &reveal).expect("build");
+        let bundle = read_backup_bundle(&bytes).expect("read");
+        let mut fresh_config = config.clone();
+        let mut fresh = AppData::default();
+        replace_with_backup_bundle(&mut fresh_config, &mut fresh, bundle, &root.join("avatars"))
+            .expect("replace");
+
+        let branch_images = fresh
+            .conversations
+            .iter()
+            .chain(fresh.archived_conversations.iter().map(|a| &a.source_conversation))
+            .map(|c| match &c.alternate_messages[0].parts[1] {
+                MessagePart::Image { bytes_base64, .. } => B64.decode(bytes_base64).expect("b64"),
+                other => panic!("unexpected part: {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(branch_images, vec![vec![1u8, 2, 3], vec![1u8, 2, 3]]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn read_config_should_migrate_plain_api_keys() {
+        let dir = std::env::temp_dir().join(format!("easy-call-secret-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).expect("create dir");
+        let config_path = dir.join("config.toml");
+        let mut config = AppConfig::default();
+        config.api_configs[0].api_key = "sk-legacy".to_string();
+        fs::write(&config_path, toml::to_string_pretty(&config).expect("serialize"))
+            .expect("write config");
+
+        let loaded = read_config(&config_path).expect("read config");
+        assert!(is_secret_ref(&loaded.api_configs[0].api_key));
+        let raw = fs::read_to_string(&config_path).expect("read raw config");
+        assert!(!raw.contains("sk-legacy"));
+        assert_eq!(
+            reveal_secret_in(&dir, None, &loaded.api_configs[0].api_key).expect("reveal"),
+            "sk-legacy"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn normalize_app_config_should_fix_invalid_record_and_stt_fields() {
         let mut cfg = AppConfig {
@@ -40,8 +1039,12 @@
             selected_api_config_id: "a1".to_string(),
             chat_api_config_id: "a1".to_string(),
             vision_api_config_id: None,
+            title_api_config_id: None,
             stt_api_config_id: None,
             stt_auto_send: false,
+            archive_policy: ArchivePolicy::default(),
+            timezone: String::new(),
+            highest_instruction: None,
             api_configs: vec![
                 ApiConfig {
                     id: "a1".to_string(),
@@ -54,6 +1057,8 @@
                     tools: vec![],
                     base_url: "https://api.openai.com/v1".to_string(),
                     api_key: "k".to_string(),
+                    extra_api_keys: Vec::new(),
+                    key_rotation: ApiKeyRotation::RoundRobin,
                     model: "m".to_string(),
                     temperature: 1.0,
                     context_window_tokens: 128_000,
@@ -69,6 +1074,8 @@
                     tools: vec![],
                     base_url: "https://api.openai.com/v1".to_string(),
                     api_key: "k".to_string(),
+                    extra_api_keys: Vec::new(),
+                    key_rotation: ApiKeyRotation::RoundRobin,
                     model: "m".to_string(),
                     temperature: 1.0,
                     context_window_tokens: 128_000,
@@ -95,8 +1102,12 @@
             selected_api_config_id: "edit-b".to_string(),
             chat_api_config_id: "chat-a".to_string(),
             vision_api_config_id: None,
+            title_api_config_id: None,
             stt_api_config_id: None,
             stt_auto_send: false,
+            archive_policy: ArchivePolicy::default(),
+            timezone: String::new(),
+            highest_instruction: None,
             api_configs: vec![
                 ApiConfig {
                     id: "chat-a".to_string(),
@@ -109,6 +1120,8 @@
                     tools: vec![],
                     base_url: "https://api.openai.com/v1".to_string(),
                     api_key: "k".to_string(),
+                    extra_api_keys: Vec::new(),
+                    key_rotation: ApiKeyRotation::RoundRobin,
                     model: "m".to_string(),
                     temperature: 1.0,
                     context_window_tokens: 128_000,
@@ -124,6 +1137,8 @@
                     tools: vec![],
                     base_url: "https://api.openai.com/v1".to_string(),
                     api_key: "k".to_string(),
+                    extra_api_keys: Vec::new(),
+                    key_rotation: ApiKeyRotation::RoundRobin,
                     model: "m".to_string(),
                     temperature: 1.0,
                     context_window_tokens: 128_000,
@@ -133,6 +1148,13 @@
         normalize_app_config(&mut cfg);
         assert_eq!(cfg.selected_api_config_id, "edit-b".to_string());
         assert_eq!(cfg.chat_api_config_id, "chat-a".to_string());
+
+        // 全局对话 API 留空时回退到第一个文本 API，规范化时保留空值。
+        cfg.chat_api_config_id.clear();
+        normalize_app_config(&mut cfg);
+        assert!(cfg.chat_api_config_id.is_empty());
+        let resolved = resolve_selected_api_config(&cfg, None).expect("fallback api");
+        assert_eq!(resolved.id, "chat-a");
     }
 
     #[test]
@@ -147,8 +1169,12 @@
             selected_api_config_id: "tts-a".to_string(),
             chat_api_config_id: "tts-a".to_string(),
             vision_api_config_id: Some("tts-a".to_string()),
+            title_api_config_id: None,
             stt_api_config_id: Some("tts-a".to_string()),
             stt_auto_send: true,
+            archive_policy: ArchivePolicy::default(),
+            timezone: String::new(),
+            highest_instruction: None,
             api_configs: vec![ApiConfig {
                 id: "tts-a".to_string(),
                 name: "tts-a".to_string(),
@@ -160,6 +1186,8 @@
                 tools: vec![],
                 base_url: "https://api.siliconflow.cn/v1/audio/transcriptions".to_string(),
                 api_key: "k".to_string(),
+                extra_api_keys: Vec::new(),
+                key_rotation: ApiKeyRotation::RoundRobin,
                 model: "m".to_string(),
                 temperature: 1.0,
                 context_window_tokens: 128_000,
@@ -175,3 +1203,141 @@
         assert!(cfg.stt_auto_send);
     }
 
+    #[test]
+    fn custom_response_styles_should_persist_and_fall_back_when_deleted() {
+        let dir = std::env::temp_dir().join(format!("easy-call-styles-{}", Uuid::new_v4()));
+        let data_path = dir.join("app_data.db");
+        let styles = normalize_custom_response_styles(vec![
+            ResponseStylePreset {
+                id: DEFAULT_RESPONSE_STYLE_ID.to_string(),
+                name: "覆盖内置".to_string(),
+                prompt: "- ignored".to_string(),
+                built_in: false,
+            },
+            ResponseStylePreset {
+                id: String::new(),
+                name: " 海盗 ".to_string(),
+                prompt: "- 用海盗口吻回答。".to_string(),
+                built_in: false,
+            },
+            ResponseStylePreset {
+                id: "blank".to_string(),
+                name: "  ".to_string(),
+                prompt: String::new(),
+                built_in: false,
+            },
+        ]);
+        let mut data = AppData {
+            response_styles: styles,
+            ..AppData::default()
+        };
+        assert_eq!(data.response_styles.len(), 1);
+        let pirate = data.response_styles[0].clone();
+        assert_eq!(pirate.name, "海盗");
+        assert!(pirate.id.starts_with("style-"));
+
+        data.response_style_id = pirate.id.clone();
+        data.agents[0].response_style_id = Some("teaching".to_string());
+        assert!(!ensure_default_agent(&mut data));
+        assert_eq!(agent_response_style(&data, &data.agents[0]).id, "teaching");
+        data.agents[0].response_style_id = None;
+        assert_eq!(agent_response_style(&data, &data.agents[0]).prompt, pirate.prompt);
+
+        write_app_data(&data_path, &data).expect("write app data");
+        let mut loaded = read_app_data(&data_path).expect("read app data");
+        assert_eq!(loaded.response_style_id, pirate.id);
+        let all = all_response_styles(&loaded.response_styles);
+        assert!(all.iter().take_while(|s| s.built_in).count() >= 1);
+        assert_eq!(all.last().map(|s| s.id.as_str()), Some(pirate.id.as_str()));
+
+        loaded.response_styles.clear();
+        loaded.agents[0].response_style_id = Some(pirate.id.clone());
+        assert!(ensure_default_agent(&mut loaded));
+        assert_eq!(loaded.response_style_id, DEFAULT_RESPONSE_STYLE_ID);
+        assert_eq!(loaded.agents[0].response_style_id, None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn agent_export_should_round_trip_json_and_character_card_png() {
+        let agent = AgentProfile {
+            id: "persona-1".to_string(),
+            name: "小猫".to_string(),
+            system_prompt: "你是一只猫，称呼用户为{{user_name}}。".to_string(),
+            avatar_path: Some("/tmp/avatar.webp".to_string()),
+            temperature: Some(0.4),
+            denied_tools: vec!["fetch".to_string()],
+            highest_instruction: Some(AgentInstructionOverride {
+                disabled: true,
+                extra_rules: Vec::new(),
+            }),
+            ..default_agent()
+        };
+        let mut avatar = Vec::<u8>::new();
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(4, 4, image::Rgba([1, 2, 3, 255])))
+            .write_to(&mut Cursor::new(&mut avatar), ImageFormat::Png)
+            .expect("encode avatar");
+
+        let json = build_agent_export_json(&agent, Some(&avatar)).expect("export json");
+        let parsed = parse_agent_import(&json).expect("import json");
+        assert_eq!(parsed.agent.system_prompt, agent.system_prompt);
+        assert_eq!(parsed.agent.temperature, Some(0.4));
+        assert!(parsed.agent.avatar_path.is_none());
+        assert!(parsed.agent.highest_instruction.is_none());
+        assert_eq!(parsed.avatar.as_deref(), Some(avatar.as_slice()));
+
+        let png = build_agent_card_png(&agent, Some(&avatar)).expect("export card");
+        assert!(image::load_from_memory(&png).is_ok());
+        let texts = png_text_chunks(&png).expect("text chunks");
+        assert_eq!(texts.iter().filter(|(key, _)| key == "chara").count(), 1);
+        let card: Value = serde_json::from_slice(&B64.decode(&texts[0].1).expect("base64")).expect("card");
+        assert_eq!(card["spec"], "chara_card_v2");
+        assert_eq!(card["data"]["description"], "你是一只猫，称呼用户为{{user}}。");
+        let parsed = parse_agent_import(&png).expect("import card");
+        assert_eq!(parsed.agent.name, "小猫");
+        assert_eq!(parsed.agent.system_prompt, agent.system_prompt);
+        assert_eq!(parsed.agent.denied_tools, vec!["fetch"]);
+        assert!(parsed.agent.highest_instruction.is_none());
+        assert!(parsed.avatar.is_some());
+
+        // 重写同名文本块时旧的会被替换。
+        let rewritten = png_with_text_chunk(&png, "chara", "eA==").expect("rewrite");
+        let texts = png_text_chunks(&rewritten).expect("text chunks");
+        assert_eq!(texts, vec![("chara".to_string(), "eA==".to_string())]);
+
+        assert_eq!(agent_export_file_name(" 小猫 v2 ", "png"), "小猫_v2.png");
+        assert_eq!(agent_export_file_name("//", "json"), "agent.json");
+    }
+
+    #[test]
+    fn foreign_character_card_should_map_fields_into_system_prompt() {
+        let card = serde_json::json!({
+            "spec": "chara_card_v2",
+            "spec_version": "2.0",
+            "data": {
+                "name": " Aria ",
+                "description": "{{char}} is a librarian.",
+                "personality": "calm",
+                "scenario": "",
+                "first_mes": "Hello {{user}}!",
+                "tags": ["books"],
+                "extensions": {}
+            }
+        });
+        let parsed = parse_agent_import(card.to_string().as_bytes()).expect("import");
+        assert_eq!(parsed.agent.name, "Aria");
+        assert_eq!(
+            parsed.agent.system_prompt,
+            "Aria is a librarian.\n\n## 性格\ncalm\n\n## 开场白\nHello {{user_name}}!"
+        );
+        assert!(parsed.avatar.is_none());
+
+        let v1 = serde_json::json!({ "name": "Bob", "description": "A pirate." });
+        assert_eq!(
+            parse_agent_import(v1.to_string().as_bytes()).expect("v1").agent.system_prompt,
+            "A pirate."
+        );
+        assert!(parse_agent_import(br#"{"spec":"chara_card_v3","data":{}}"#).is_err());
+        assert!(parse_agent_import(b"\x89PNG\r\n\x1a\n").is_err());
+    }
+
@@ -1,6 +1,13 @@
-const APP_DATA_SCHEMA_VERSION: u32 = 1;
-const ARCHIVE_IDLE_SECONDS: i64 = 30 * 60;
+const APP_DATA_SCHEMA_VERSION: u32 = 2;
+/// 恢复归档超出上下文时，分叉对话默认保留的最近轮数。
+const RESTORE_ARCHIVE_RECENT_TURNS: usize = 3;
 const MAX_MULTIMODAL_BYTES: usize = 10 * 1024 * 1024;
+const MAX_PDF_EXTRACTED_CHARS: usize = 60_000;
+const DAILY_BACKUP_KEEP_COUNT: usize = 7;
+/// 每种操作前快照（pre-restore / pre-import）各自保留的份数，与每日快照分开计数。
+const OPERATION_BACKUP_KEEP_COUNT: usize = 5;
+const API_KEY_RATE_LIMIT_COOLDOWN_SECONDS: u64 = 60;
+const API_KEY_AUTH_FAILURE_COOLDOWN_SECONDS: u64 = 10 * 60;
 const DEFAULT_AGENT_ID: &str = "default-agent";
 const USER_PERSONA_ID: &str = "user-persona";
 const DEFAULT_RESPONSE_STYLE_ID: &str = "concise";
@@ -11,54 +18,113 @@ struct ResponseStylePreset {
     id: String,
     name: String,
     prompt: String,
+    /// 内置风格只读，仅作为新建自定义风格的模板。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    built_in: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct HighestInstruction {
+    #[serde(default)]
     title: String,
+    #[serde(default)]
     rules: Vec<String>,
 }
 
+/// 智能体对最高准则的调整：关闭整段准则，或在其后追加规则。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AgentInstructionOverride {
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    extra_rules: Vec<String>,
+}
+
 fn built_in_response_styles() -> &'static Vec<ResponseStylePreset> {
     static STYLES: OnceLock<Vec<ResponseStylePreset>> = OnceLock::new();
     STYLES.get_or_init(|| {
-        serde_json::from_str(include_str!("../../../../src/constants/response-styles.json")).unwrap_or_else(
-            |_| {
-                vec![ResponseStylePreset {
-                    id: DEFAULT_RESPONSE_STYLE_ID.to_string(),
-                    name: "简洁".to_string(),
-                    prompt: "- 用最少但足够的信息回答。".to_string(),
-                }]
-            },
-        )
+        let mut styles: Vec<ResponseStylePreset> =
+            serde_json::from_str(include_str!("../../../../src/constants/response-styles.json"))
+                .unwrap_or_else(|_| vec![fallback_response_style()]);
+        for style in &mut styles {
+            style.built_in = true;
+        }
+        styles
     })
 }
 
+fn fallback_response_style() -> ResponseStylePreset {
+    ResponseStylePreset {
+        id: DEFAULT_RESPONSE_STYLE_ID.to_string(),
+        name: "简洁".to_string(),
+        prompt: "- 用最少但足够的信息回答。".to_string(),
+        built_in: true,
+    }
+}
+
 fn default_response_style_id() -> String {
     DEFAULT_RESPONSE_STYLE_ID.to_string()
 }
 
-fn normalize_response_style_id(value: &str) -> String {
-    let id = value.trim();
-    if built_in_response_styles().iter().any(|s| s.id == id) {
-        id.to_string()
-    } else {
-        default_response_style_id()
-    }
+/// 内置风格在前，用户自定义风格按保存顺序排在后面。
+fn all_response_styles(custom: &[ResponseStylePreset]) -> Vec<ResponseStylePreset> {
+    built_in_response_styles()
+        .iter()
+        .chain(custom.iter())
+        .cloned()
+        .collect()
 }
 
-fn response_style_preset(id: &str) -> ResponseStylePreset {
+fn find_response_style(id: &str, custom: &[ResponseStylePreset]) -> Option<ResponseStylePreset> {
+    let id = id.trim();
     built_in_response_styles()
         .iter()
+        .chain(custom.iter())
         .find(|s| s.id == id)
         .cloned()
+}
+
+fn normalize_response_style_id(value: &str, custom: &[ResponseStylePreset]) -> String {
+    find_response_style(value, custom)
+        .map(|s| s.id)
+        .unwrap_or_else(default_response_style_id)
+}
+
+fn response_style_preset(id: &str, custom: &[ResponseStylePreset]) -> ResponseStylePreset {
+    find_response_style(id, custom)
         .or_else(|| built_in_response_styles().first().cloned())
-        .unwrap_or(ResponseStylePreset {
-            id: DEFAULT_RESPONSE_STYLE_ID.to_string(),
-            name: "简洁".to_string(),
-            prompt: "- 用最少但足够的信息回答。".to_string(),
-        })
+        .unwrap_or_else(fallback_response_style)
+}
+
+/// 智能体设置了默认风格时优先使用，否则使用全局选择的风格。
+fn agent_response_style(data: &AppData, agent: &AgentProfile) -> ResponseStylePreset {
+    agent
+        .response_style_id
+        .as_deref()
+        .and_then(|id| find_response_style(id, &data.response_styles))
+        .unwrap_or_else(|| response_style_preset(&data.response_style_id, &data.response_styles))
+}
+
+/// 整理用户提交的自定义风格：丢弃内置风格与空名称，补全 id 并去重。
+fn normalize_custom_response_styles(styles: Vec<ResponseStylePreset>) -> Vec<ResponseStylePreset> {
+    let mut out = Vec::<ResponseStylePreset>::new();
+    for mut style in styles {
+        if style.built_in || built_in_response_styles().iter().any(|s| s.id == style.id.trim()) {
+            continue;
+        }
+        style.name = style.name.trim().to_string();
+        if style.name.is_empty() {
+            continue;
+        }
+        style.id = style.id.trim().to_string();
+        if style.id.is_empty() || out.iter().any(|s| s.id == style.id) {
+            style.id = format!("style-{}", Uuid::new_v4());
+        }
+        out.push(style);
+    }
+    out
 }
 
 fn highest_instruction() -> &'static HighestInstruction {
@@ -76,8 +142,109 @@ fn highest_instruction() -> &'static HighestInstruction {
     })
 }
 
-fn highest_instruction_markdown() -> String {
-    let source = highest_instruction();
+fn normalize_instruction_rules(rules: &mut Vec<String>) {
+    for rule in rules.iter_mut() {
+        *rule = rule.trim().to_string();
+    }
+    rules.retain(|r| !r.is_empty());
+}
+
+/// 去掉空规则；标题和规则都为空的覆盖视为未设置。
+fn normalize_highest_instruction(value: &mut Option<HighestInstruction>) {
+    if let Some(instruction) = value.as_mut() {
+        instruction.title = instruction.title.trim().to_string();
+        normalize_instruction_rules(&mut instruction.rules);
+        if instruction.title.is_empty() && instruction.rules.is_empty() {
+            *value = None;
+        }
+    }
+}
+
+fn normalize_agent_instruction_override(value: &mut Option<AgentInstructionOverride>) {
+    if let Some(adjust) = value.as_mut() {
+        normalize_instruction_rules(&mut adjust.extra_rules);
+        if !adjust.disabled && adjust.extra_rules.is_empty() {
+            *value = None;
+        }
+    }
+}
+
+fn normalize_agent_model_overrides(agent: &mut AgentProfile) {
+    agent.api_config_id = agent
+        .api_config_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(ToOwned::to_owned);
+    agent.temperature = agent
+        .temperature
+        .filter(|v| v.is_finite())
+        .map(|v| v.clamp(0.0, 2.0));
+    for list in [&mut agent.allowed_tools, &mut agent.denied_tools] {
+        let mut seen = std::collections::HashSet::<String>::new();
+        list.retain_mut(|id| {
+            *id = id.trim().to_string();
+            !id.is_empty() && seen.insert(id.clone())
+        });
+    }
+}
+
+/// 智能体的默认 API 仅在存在且支持文本时生效。
+fn agent_default_api_config<'a>(
+    app_config: &'a AppConfig,
+    agent: &AgentProfile,
+) -> Option<&'a ApiConfig> {
+    let api_id = agent.api_config_id.as_deref()?;
+    app_config
+        .api_configs
+        .iter()
+        .find(|a| a.id == api_id && a.enable_text && a.request_format != RequestFormat::OpenAITts)
+}
+
+fn agent_allows_tool(agent: &AgentProfile, tool_id: &str) -> bool {
+    !agent.denied_tools.iter().any(|id| id == tool_id)
+        && (agent.allowed_tools.is_empty() || agent.allowed_tools.iter().any(|id| id == tool_id))
+}
+
+/// 把智能体的工具过滤与温度覆盖套到本次请求使用的 API 配置上。
+fn apply_agent_overrides(api: &mut ApiConfig, resolved: &mut ResolvedApiConfig, agent: &AgentProfile) {
+    for tool in &mut api.tools {
+        if !agent_allows_tool(agent, &tool.id) {
+            tool.enabled = false;
+        }
+    }
+    if let Some(temperature) = agent.temperature {
+        api.temperature = temperature;
+        resolved.temperature = temperature;
+    }
+}
+
+/// 配置中的准则覆盖内置准则；智能体可关闭准则或追加规则。返回 None 表示不输出准则段落。
+fn effective_highest_instruction(
+    app_config: &AppConfig,
+    agent: Option<&AgentProfile>,
+) -> Option<HighestInstruction> {
+    let mut instruction = app_config
+        .highest_instruction
+        .clone()
+        .unwrap_or_else(|| highest_instruction().clone());
+    if let Some(adjust) = agent.and_then(|a| a.highest_instruction.as_ref()) {
+        if adjust.disabled {
+            return None;
+        }
+        instruction.rules.extend(adjust.extra_rules.iter().cloned());
+    }
+    normalize_instruction_rules(&mut instruction.rules);
+    if instruction.rules.is_empty() {
+        return None;
+    }
+    Some(instruction)
+}
+
+fn highest_instruction_markdown(source: Option<&HighestInstruction>) -> String {
+    let Some(source) = source else {
+        return String::new();
+    };
     let title = source.title.trim();
     let title = if title.is_empty() { "系统准则" } else { title };
     let mut out = format!("# {}\n", title);
@@ -159,6 +326,10 @@ impl RequestFormat {
     fn is_openai_style(self) -> bool {
         matches!(self, Self::OpenAI | Self::DeepSeekKimi)
     }
+
+    fn supports_document_input(self) -> bool {
+        matches!(self, Self::OpenAI | Self::Gemini | Self::Anthropic)
+    }
 }
 
 impl std::fmt::Display for RequestFormat {
@@ -230,6 +401,16 @@ fn default_api_tools() -> Vec<ApiToolConfig> {
     ]
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ApiKeyRotation {
+    /// 依次轮换，每次请求从最久未使用的 key 开始。
+    #[default]
+    RoundRobin,
+    /// 优先使用最久未被限流（从未限流优先）的 key。
+    LeastRateLimited,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ApiConfig {
@@ -249,6 +430,11 @@ struct ApiConfig {
     tools: Vec<ApiToolConfig>,
     base_url: String,
     api_key: String,
+    /// 额外的 key，与 api_key 组成 key 池按 key_rotation 轮换。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    extra_api_keys: Vec<String>,
+    #[serde(default)]
+    key_rotation: ApiKeyRotation,
     model: String,
     #[serde(default = "default_api_temperature")]
     temperature: f64,
@@ -301,6 +487,8 @@ impl Default for ApiConfig {
             tools: default_api_tools(),
             base_url: "https://api.openai.com/v1".to_string(),
             api_key: String::new(),
+            extra_api_keys: Vec::new(),
+            key_rotation: ApiKeyRotation::default(),
             model: "gpt-4o-mini".to_string(),
             temperature: default_api_temperature(),
             context_window_tokens: default_context_window_tokens(),
@@ -327,10 +515,21 @@ struct AppConfig {
     chat_api_config_id: String,
     #[serde(default)]
     vision_api_config_id: Option<String>,
+    /// 生成会话标题用的 API 配置，为空时使用会话自身的聊天配置。
+    #[serde(default)]
+    title_api_config_id: Option<String>,
     #[serde(default)]
     stt_api_config_id: Option<String>,
     #[serde(default)]
     stt_auto_send: bool,
+    #[serde(default)]
+    archive_policy: ArchivePolicy,
+    /// 用户所在时区的 UTC 偏移，如 "UTC+08:00"；为空时跟随系统。
+    #[serde(default)]
+    timezone: String,
+    /// 覆盖内置的最高准则（标题与规则列表），为空时使用内置准则。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    highest_instruction: Option<HighestInstruction>,
     api_configs: Vec<ApiConfig>,
 }
 
@@ -347,23 +546,130 @@ impl Default for AppConfig {
             selected_api_config_id: api_config.id.clone(),
             chat_api_config_id: api_config.id.clone(),
             vision_api_config_id: None,
+            title_api_config_id: None,
             stt_api_config_id: None,
             stt_auto_send: false,
+            archive_policy: ArchivePolicy::default(),
+            timezone: String::new(),
+            highest_instruction: None,
             api_configs: vec![api_config],
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct DebugApiConfig {
-    request_format: Option<RequestFormat>,
-    base_url: String,
-    api_key: String,
-    model: String,
-    temperature: Option<f64>,
-    fixed_test_prompt: Option<String>,
-    enabled: Option<bool>,
+enum ArchiveMode {
+    /// 闲置超时且上下文占用达到阈值时归档。
+    #[default]
+    Idle,
+    /// 每天到达设定的本地时间后，首条消息前归档前一天的对话。
+    Daily,
+    /// 从不自动归档，只能手动归档。
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum ContextOverflowAction {
+    /// 归档整段对话并开启新对话。
+    #[default]
+    Archive,
+    /// 把较早的消息压缩成摘要，在同一对话里继续。
+    Compact,
+}
+
+/// 发消息前的自动归档策略；智能体可以用自己的策略整体覆盖全局配置。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchivePolicy {
+    #[serde(default)]
+    mode: ArchiveMode,
+    /// 上下文占用达到该比例时强制归档（idle / daily 模式）或压缩。
+    #[serde(default = "default_archive_force_usage_ratio")]
+    force_usage_ratio: f64,
+    /// 占用达到 force_usage_ratio 时的处理方式；compact 在 never 模式下同样生效。
+    #[serde(default)]
+    overflow_action: ContextOverflowAction,
+    #[serde(default = "default_archive_idle_minutes")]
+    idle_minutes: u32,
+    /// 闲置超时后，占用达到该比例才归档。
+    #[serde(default = "default_archive_idle_usage_ratio")]
+    idle_usage_ratio: f64,
+    /// daily 模式的换日时间，本地时间 HH:MM。
+    #[serde(default = "default_archive_daily_time")]
+    daily_time: String,
+    /// 归档摘要失败时保留的最近轮数，也是压缩时保留原文的轮数。
+    #[serde(default = "default_archive_fallback_recent_turns")]
+    fallback_recent_turns: usize,
+}
+
+impl Default for ArchivePolicy {
+    fn default() -> Self {
+        Self {
+            mode: ArchiveMode::default(),
+            force_usage_ratio: default_archive_force_usage_ratio(),
+            overflow_action: ContextOverflowAction::default(),
+            idle_minutes: default_archive_idle_minutes(),
+            idle_usage_ratio: default_archive_idle_usage_ratio(),
+            daily_time: default_archive_daily_time(),
+            fallback_recent_turns: default_archive_fallback_recent_turns(),
+        }
+    }
+}
+
+fn default_archive_force_usage_ratio() -> f64 {
+    0.82
+}
+
+fn default_archive_idle_minutes() -> u32 {
+    30
+}
+
+fn default_archive_idle_usage_ratio() -> f64 {
+    0.30
+}
+
+fn default_archive_daily_time() -> String {
+    "04:00".to_string()
+}
+
+fn default_archive_fallback_recent_turns() -> usize {
+    3
+}
+
+/// 解析 HH:MM，非法时返回 None。
+fn parse_daily_time(raw: &str) -> Option<(u8, u8)> {
+    let (hour, minute) = raw.trim().split_once(':')?;
+    let hour = hour.trim().parse::<u8>().ok().filter(|h| *h < 24)?;
+    let minute = minute.trim().parse::<u8>().ok().filter(|m| *m < 60)?;
+    Some((hour, minute))
+}
+
+fn normalize_archive_policy(policy: &mut ArchivePolicy) {
+    if !policy.force_usage_ratio.is_finite() || policy.force_usage_ratio <= 0.0 {
+        policy.force_usage_ratio = default_archive_force_usage_ratio();
+    }
+    policy.force_usage_ratio = policy.force_usage_ratio.clamp(0.1, 1.0);
+    if !policy.idle_usage_ratio.is_finite() {
+        policy.idle_usage_ratio = default_archive_idle_usage_ratio();
+    }
+    policy.idle_usage_ratio = policy.idle_usage_ratio.clamp(0.0, policy.force_usage_ratio);
+    if policy.idle_minutes == 0 {
+        policy.idle_minutes = default_archive_idle_minutes();
+    }
+    policy.daily_time = match parse_daily_time(&policy.daily_time) {
+        Some((hour, minute)) => format!("{hour:02}:{minute:02}"),
+        None => default_archive_daily_time(),
+    };
+    policy.fallback_recent_turns = policy.fallback_recent_turns.clamp(1, 50);
+}
+
+/// 智能体自定义的策略优先于全局配置。
+fn effective_archive_policy(app_config: &AppConfig, agent: Option<&AgentProfile>) -> ArchivePolicy {
+    agent
+        .and_then(|a| a.archive_policy.clone())
+        .unwrap_or_else(|| app_config.archive_policy.clone())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -388,6 +694,9 @@ struct SendChatRequest {
     payload: ChatInputPayload,
     #[serde(default)]
     session: Option<SessionSelector>,
+    /// 指定发送到哪条活动会话；为空时使用当前会话。
+    #[serde(default)]
+    conversation_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -395,6 +704,8 @@ struct SendChatRequest {
 struct StopChatRequest {
     session: SessionSelector,
     #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
     partial_assistant_text: String,
     #[serde(default)]
     partial_reasoning_standard: String,
@@ -434,6 +745,8 @@ struct ChatSnapshot {
     latest_user: Option<ChatMessage>,
     latest_assistant: Option<ChatMessage>,
     active_message_count: usize,
+    /// 当前智能体实际生效的自动归档策略。
+    archive_policy: ArchivePolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -444,12 +757,17 @@ struct PromptPreview {
     latest_images: usize,
     latest_audios: usize,
     request_body_json: String,
+    /// 合并配置与智能体调整后实际生效的最高准则，关闭时为空。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    highest_instruction: Option<HighestInstruction>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct SystemPromptPreview {
     system_prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    highest_instruction: Option<HighestInstruction>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -458,6 +776,9 @@ struct RefreshModelsInput {
     base_url: String,
     api_key: String,
     request_format: RequestFormat,
+    /// api_key 为掩码时，用该配置已保存的密钥。
+    #[serde(default)]
+    api_config_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -585,6 +906,27 @@ struct AgentProfile {
     avatar_updated_at: Option<String>,
     #[serde(default)]
     is_built_in_user: bool,
+    /// 覆盖全局自动归档策略，为空时沿用全局配置。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archive_policy: Option<ArchivePolicy>,
+    /// 该智能体默认使用的回复风格，为空时沿用全局选择。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response_style_id: Option<String>,
+    /// 关闭或扩展最高准则，为空时沿用配置中的准则。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    highest_instruction: Option<AgentInstructionOverride>,
+    /// 该智能体默认使用的对话 API，会话未指定时生效。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_config_id: Option<String>,
+    /// 覆盖 API 配置中的温度。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    /// 非空时只允许这些工具。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allowed_tools: Vec<String>,
+    /// 始终禁用的工具，优先于 allowed_tools。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    denied_tools: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -601,18 +943,49 @@ enum MessagePart {
     },
     Image {
         mime: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         bytes_base64: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blob_hash: Option<String>,
         name: Option<String>,
         compressed: bool,
     },
     Audio {
         mime: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
         bytes_base64: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blob_hash: Option<String>,
         name: Option<String>,
         compressed: bool,
     },
 }
 
+impl MessagePart {
+    fn binary_fields_mut(&mut self) -> Option<(&mut String, &mut Option<String>)> {
+        match self {
+            Self::Image {
+                bytes_base64,
+                blob_hash,
+                ..
+            }
+            | Self::Audio {
+                bytes_base64,
+                blob_hash,
+                ..
+            } => Some((bytes_base64, blob_hash)),
+            Self::Text { .. } => None,
+        }
+    }
+
+    fn blob_hash(&self) -> Option<&str> {
+        match self {
+            Self::Image { blob_hash, .. } | Self::Audio { blob_hash, .. } => blob_hash.as_deref(),
+            Self::Text { .. } => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ChatMessage {
@@ -625,6 +998,20 @@ struct ChatMessage {
     provider_meta: Option<Value>,
     tool_call: Option<Vec<Value>>,
     mcp_call: Option<Vec<Value>>,
+    /// 消息树上的父消息 id，首条消息为空。当前分支上的消息以前一条为准，该字段仅在存入备选分支时可靠。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    /// 同一父消息下的兄弟序号，重新生成或编辑时递增。
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    sibling_index: u32,
+}
+
+fn is_zero_u32(value: &u32) -> bool {
+    *value == 0
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -632,6 +1019,9 @@ struct ChatMessage {
 struct Conversation {
     id: String,
     title: String,
+    /// 标题已由模型生成或由用户命名，不再自动生成。
+    #[serde(default, skip_serializing_if = "is_false")]
+    title_settled: bool,
     api_config_id: String,
     agent_id: String,
     created_at: String,
@@ -642,7 +1032,31 @@ struct Conversation {
     #[serde(default)]
     last_context_usage_ratio: f64,
     status: String,
+    /// 当前选中的分支，构建提示词、估算 token 与导出都只看这里。
     messages: Vec<ChatMessage>,
+    /// 未选中分支上的消息，按 parent_id 挂在消息树上，越靠后越是最近离开的分支。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternate_messages: Vec<ChatMessage>,
+    /// 由归档分叉而来时记录来源归档，回顾摘要优先取该归档。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    forked_from_archive_id: Option<String>,
+    /// 原地压缩：被覆盖的消息仍留在 messages 里供搜索和导出，发给模型时以摘要代替。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compaction: Option<ConversationCompaction>,
+    /// 只读取了会话头、消息未从数据库加载；写回时跳过这条会话的消息同步。
+    #[serde(skip)]
+    messages_deferred: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationCompaction {
+    /// 覆盖范围的累计摘要（包含之前各次压缩的内容），摘要失败时可能为空。
+    #[serde(default)]
+    summary: String,
+    /// 摘要覆盖到的最后一条消息。
+    until_message_id: String,
+    created_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -697,6 +1111,9 @@ struct AppData {
     user_alias: String,
     #[serde(default = "default_response_style_id")]
     response_style_id: String,
+    /// 用户自定义的回复风格，不含内置风格。
+    #[serde(default)]
+    response_styles: Vec<ResponseStylePreset>,
     conversations: Vec<Conversation>,
     archived_conversations: Vec<ConversationArchive>,
     #[serde(default)]
@@ -713,6 +1130,7 @@ impl Default for AppData {
             selected_agent_id: default_selected_agent_id(),
             user_alias: default_user_alias(),
             response_style_id: default_response_style_id(),
+            response_styles: Vec::new(),
             conversations: Vec::new(),
             archived_conversations: Vec::new(),
             image_text_cache: Vec::new(),
@@ -748,9 +1166,13 @@ fn user_persona_intro(data: &AppData) -> String {
 
 #[derive(Debug, Clone)]
 struct ResolvedApiConfig {
+    api_config_id: String,
     request_format: RequestFormat,
     base_url: String,
+    /// 本次请求使用的明文 key（key 池中排在最前的可用 key）。
     api_key: String,
+    /// key 池中尚未解析的原始引用，按本次尝试顺序排列。
+    api_key_refs: Vec<String>,
     model: String,
     temperature: f64,
     fixed_test_prompt: String,
@@ -787,13 +1209,18 @@ struct AppState {
 
 impl AppState {
     fn new() -> Result<Self, String> {
-        let project_dirs = ProjectDirs::from("ai", "easycall", "easy-call-ai")
-            .ok_or_else(|| "Failed to resolve config directory".to_string())?;
-        let config_dir = project_dirs.config_dir().to_path_buf();
+        let location = app_data_dir()?;
+        let config_dir = location.dir.clone();
+        eprintln!(
+            "[BOOT] data directory: {} (source={})",
+            config_dir.display(),
+            location.source
+        );
+        init_secret_vault(&config_dir);
 
         Ok(Self {
             config_path: config_dir.join("config.toml"),
-            data_path: config_dir.join("app_data.json"),
+            data_path: config_dir.join("app_data.db"),
             state_lock: Arc::new(Mutex::new(())),
             inflight_chat_abort_handles: Arc::new(Mutex::new(std::collections::HashMap::new())),
         })
@@ -803,6 +1230,105 @@ fn now_utc() -> OffsetDateTime {
     OffsetDateTime::now_utc()
 }
 
+/// 系统当前的 UTC 偏移；取不到时按 UTC 处理。
+fn system_utc_offset() -> UtcOffset {
+    let seconds = chrono::Local::now().offset().local_minus_utc();
+    UtcOffset::from_whole_seconds(seconds).unwrap_or(UtcOffset::UTC)
+}
+
+/// 解析 "+08:00"、"UTC+8"、"-05:30"、"UTC" 这类偏移写法。
+fn parse_utc_offset(raw: &str) -> Option<UtcOffset> {
+    let trimmed = raw.trim();
+    let body = trimmed
+        .strip_prefix("UTC")
+        .or_else(|| trimmed.strip_prefix("GMT"))
+        .unwrap_or(trimmed)
+        .trim();
+    if body.is_empty() || body == "Z" {
+        return Some(UtcOffset::UTC);
+    }
+    let (sign, rest) = match body.chars().next()? {
+        '+' => (1i32, &body[1..]),
+        '-' => (-1i32, &body[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h.parse::<i32>().ok()?, m.parse::<i32>().ok()?),
+        None if rest.chars().count() == 4 => (
+            rest.get(..2)?.parse::<i32>().ok()?,
+            rest.get(2..)?.parse::<i32>().ok()?,
+        ),
+        None => (rest.parse::<i32>().ok()?, 0),
+    };
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    UtcOffset::from_whole_seconds(sign * (hours * 3600 + minutes * 60)).ok()
+}
+
+/// 用户时区：offset 用于换算时间，name 为时区名称，跟随系统时取系统的 IANA 名称。
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UserTimeZone {
+    name: Option<String>,
+    offset: UtcOffset,
+}
+
+impl UserTimeZone {
+    fn fixed(offset: UtcOffset) -> Self {
+        Self { name: None, offset }
+    }
+
+    /// 有名称时为 "Asia/Shanghai UTC+08:00"，否则只显示偏移。
+    fn label(&self) -> String {
+        let offset = format_utc_offset_label(self.offset);
+        match self.name.as_deref() {
+            Some(name) => format!("{name} {offset}"),
+            None => offset,
+        }
+    }
+}
+
+fn system_time_zone() -> UserTimeZone {
+    UserTimeZone {
+        name: iana_time_zone::get_timezone()
+            .ok()
+            .filter(|name| !name.trim().is_empty()),
+        offset: system_utc_offset(),
+    }
+}
+
+/// 配置的时区；未设置或无法解析时跟随系统。
+fn configured_time_zone(config: &AppConfig) -> UserTimeZone {
+    if config.timezone.trim().is_empty() {
+        return system_time_zone();
+    }
+    parse_utc_offset(&config.timezone)
+        .map(UserTimeZone::fixed)
+        .unwrap_or_else(system_time_zone)
+}
+
+fn format_utc_offset_label(offset: UtcOffset) -> String {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!("UTC{}{:02}:{:02}", sign, hours.unsigned_abs(), minutes.unsigned_abs())
+}
+
+/// 本地时间 + 星期 + 时区，例如 "2026-03-02 09:30:00 Monday (Asia/Shanghai UTC+08:00)"。
+fn format_local_time_text(dt: OffsetDateTime, zone: &UserTimeZone) -> String {
+    let local = dt.to_offset(zone.offset);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {} ({})",
+        local.year(),
+        local.month() as u8,
+        local.day(),
+        local.hour(),
+        local.minute(),
+        local.second(),
+        local.weekday(),
+        zone.label()
+    )
+}
+
 fn now_iso() -> String {
     now_utc()
         .format(&Rfc3339)
@@ -813,21 +1339,13 @@ fn parse_iso(value: &str) -> Option<OffsetDateTime> {
     OffsetDateTime::parse(value, &Rfc3339).ok()
 }
 
-fn format_message_time_text(raw: &str) -> String {
+fn format_message_time_text(raw: &str, zone: &UserTimeZone) -> String {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         return String::new();
     }
     if let Some(dt) = parse_iso(trimmed) {
-        return format!(
-            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
-            dt.year(),
-            dt.month() as u8,
-            dt.day(),
-            dt.hour(),
-            dt.minute(),
-            dt.second()
-        );
+        return format_local_time_text(dt, zone);
     }
     let mut normalized = trimmed.replace('T', " ");
     if let Some((head, _)) = normalized.split_once('.') {
@@ -854,6 +1372,13 @@ fn default_agent() -> AgentProfile {
         avatar_path: None,
         avatar_updated_at: None,
         is_built_in_user: false,
+        archive_policy: None,
+        response_style_id: None,
+        highest_instruction: None,
+        api_config_id: None,
+        temperature: None,
+        allowed_tools: Vec::new(),
+        denied_tools: Vec::new(),
     }
 }
 
@@ -868,6 +1393,13 @@ fn default_user_persona() -> AgentProfile {
         avatar_path: None,
         avatar_updated_at: None,
         is_built_in_user: true,
+        archive_policy: None,
+        response_style_id: None,
+        highest_instruction: None,
+        api_config_id: None,
+        temperature: None,
+        allowed_tools: Vec::new(),
+        denied_tools: Vec::new(),
     }
 }
 
@@ -923,11 +1455,22 @@ fn ensure_default_agent(data: &mut AppData) -> bool {
         data.user_alias = desired_alias;
         changed = true;
     }
-    let desired_style = normalize_response_style_id(&data.response_style_id);
+    let desired_style = normalize_response_style_id(&data.response_style_id, &data.response_styles);
     if data.response_style_id != desired_style {
         data.response_style_id = desired_style;
         changed = true;
     }
+    let custom_styles = data.response_styles.clone();
+    for agent in &mut data.agents {
+        let missing = agent
+            .response_style_id
+            .as_deref()
+            .is_some_and(|id| find_response_style(id, &custom_styles).is_none());
+        if missing {
+            agent.response_style_id = None;
+            changed = true;
+        }
+    }
     changed
 }
 
@@ -947,6 +1490,8 @@ struct ConversationApiSettings {
     #[serde(default)]
     vision_api_config_id: Option<String>,
     #[serde(default)]
+    title_api_config_id: Option<String>,
+    #[serde(default)]
     stt_api_config_id: Option<String>,
     #[serde(default)]
     stt_auto_send: bool,
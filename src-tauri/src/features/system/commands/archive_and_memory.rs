@@ -35,7 +35,7 @@ fn get_prompt_preview(
         .cloned()
         .ok_or_else(|| "Selected agent not found.".to_string())?;
 
-    let conversation = data
+    let mut conversation = data
         .conversations
         .iter()
         .rfind(|c| c.status == "active" && c.agent_id == effective_agent_id)
@@ -43,6 +43,7 @@ fn get_prompt_preview(
         .unwrap_or_else(|| Conversation {
             id: "preview".to_string(),
             title: "Preview".to_string(),
+            title_settled: false,
             api_config_id: api_config.id.clone(),
             agent_id: effective_agent_id.clone(),
             created_at: now_iso(),
@@ -52,7 +53,15 @@ fn get_prompt_preview(
             last_context_usage_ratio: 0.0,
             status: "active".to_string(),
             messages: Vec::new(),
+            alternate_messages: Vec::new(),
+            forked_from_archive_id: None,
+            compaction: None,
+            messages_deferred: false,
         });
+    hydrate_message_blobs(
+        &blob_storage_dir(&state.data_path),
+        &mut conversation.messages,
+    );
 
     let user_name = user_persona_name(&data);
     let user_intro = user_persona_intro(&data);
@@ -61,17 +70,10 @@ fn get_prompt_preview(
         &agent,
         &user_name,
         &user_intro,
-        &data.response_style_id,
-        &app_config.ui_language,
+        &agent_response_style(&data, &agent),
+        &app_config,
     );
-    let last_archive_summary = data
-        .archived_conversations
-        .iter()
-        .rev()
-        .find(|a| {
-            a.source_conversation.agent_id == effective_agent_id && !a.summary.trim().is_empty()
-        })
-        .map(|a| a.summary.clone());
+    let last_archive_summary = archive_recap_summary(&data, &conversation);
     if let Some(summary) = last_archive_summary {
         prepared.preamble.push_str(
             "\n[HIDDEN ARCHIVE RECAP]\nUSER: 上次我们聊到哪里？\nASSISTANT: ",
@@ -120,6 +122,7 @@ fn get_prompt_preview(
         latest_images: prepared.latest_images.len(),
         latest_audios: prepared.latest_audios.len(),
         request_body_json,
+        highest_instruction: effective_highest_instruction(&app_config, Some(&agent)),
     })
 }
 
@@ -202,6 +205,7 @@ fn get_system_prompt_preview(
     let preview = get_prompt_preview(input, state)?;
     Ok(SystemPromptPreview {
         system_prompt: preview.preamble,
+        highest_instruction: preview.highest_instruction,
     })
 }
 
@@ -246,6 +250,31 @@ fn archive_first_user_preview(conversation: &Conversation) -> String {
     }
 }
 
+/// 已生成或用户命名的标题优先，否则回退到首条用户消息预览。
+fn archive_display_title(conversation: &Conversation) -> String {
+    let title = conversation.title.trim();
+    if conversation.title_settled && !title.is_empty() {
+        title.to_string()
+    } else {
+        archive_first_user_preview(conversation)
+    }
+}
+
+fn archive_summary(archive: &ConversationArchive) -> ArchiveSummary {
+    ArchiveSummary {
+        archive_id: archive.archive_id.clone(),
+        archived_at: archive.archived_at.clone(),
+        title: format!(
+            "{} - {}",
+            archive_time_label(&archive.archived_at),
+            archive_display_title(&archive.source_conversation)
+        ),
+        message_count: archive.source_conversation.messages.len(),
+        api_config_id: archive.source_conversation.api_config_id.clone(),
+        agent_id: archive.source_conversation.agent_id.clone(),
+    }
+}
+
 #[tauri::command]
 fn list_archives(state: State<'_, AppState>) -> Result<Vec<ArchiveSummary>, String> {
     let guard = state
@@ -259,18 +288,7 @@ fn list_archives(state: State<'_, AppState>) -> Result<Vec<ArchiveSummary>, Stri
     let mut summaries = data
         .archived_conversations
         .iter()
-        .map(|archive| ArchiveSummary {
-            archive_id: archive.archive_id.clone(),
-            archived_at: archive.archived_at.clone(),
-            title: format!(
-                "{} - {}",
-                archive_time_label(&archive.archived_at),
-                archive_first_user_preview(&archive.source_conversation)
-            ),
-            message_count: archive.source_conversation.messages.len(),
-            api_config_id: archive.source_conversation.api_config_id.clone(),
-            agent_id: archive.source_conversation.agent_id.clone(),
-        })
+        .map(archive_summary)
         .collect::<Vec<_>>();
     summaries.sort_by(|a, b| b.archived_at.cmp(&a.archived_at));
     Ok(summaries)
@@ -295,7 +313,9 @@ fn get_archive_messages(
         .find(|a| a.archive_id == archive_id)
         .ok_or_else(|| "Archive not found".to_string())?;
 
-    Ok(archive.source_conversation.messages.clone())
+    let mut messages = archive.source_conversation.messages.clone();
+    hydrate_message_blobs(&blob_storage_dir(&state.data_path), &mut messages);
+    Ok(messages)
 }
 
 #[tauri::command]
@@ -320,10 +340,34 @@ fn delete_archive(archive_id: String, state: State<'_, AppState>) -> Result<(),
     }
 
     write_app_data(&state.data_path, &data)?;
+    // 归档删除后清理不再被任何消息引用的附件 blob。
+    match gc_unreferenced_blobs(&state.data_path, &data) {
+        Ok(removed) if removed > 0 => eprintln!("[BLOB] removed {removed} unreferenced blobs"),
+        Ok(_) => {}
+        Err(err) => eprintln!("[BLOB] gc failed: {err}"),
+    }
     drop(guard);
     Ok(())
 }
 
+#[tauri::command]
+fn search_history(
+    input: SearchHistoryInput,
+    state: State<'_, AppState>,
+) -> Result<SearchHistoryResult, String> {
+    if input.query.trim().is_empty() {
+        return Err("query is required".to_string());
+    }
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut conn = open_app_db(&state.data_path)?;
+    let result = search_history_in_db(&mut conn, &input);
+    drop(guard);
+    result
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ExportArchiveToFileInput {
@@ -468,7 +512,10 @@ fn archive_message_markdown_block(message: &ChatMessage) -> String {
 fn build_archive_markdown(archive: &ConversationArchive) -> String {
     let mut blocks = Vec::<String>::new();
     blocks.push("# 对话归档".to_string());
-    blocks.push(format!("- 标题: {}", archive.source_conversation.title));
+    blocks.push(format!(
+        "- 标题: {}",
+        archive_display_title(&archive.source_conversation)
+    ));
     blocks.push(format!("- 归档时间: {}", archive.archived_at));
     if !archive.summary.trim().is_empty() {
         blocks.push(String::new());
@@ -510,12 +557,18 @@ fn export_archive_to_file(
     let data = read_app_data(&state.data_path)?;
     drop(guard);
 
-    let archive = data
+    let mut archive = data
         .archived_conversations
         .iter()
         .find(|a| a.archive_id == input.archive_id)
         .cloned()
         .ok_or_else(|| "Archive not found".to_string())?;
+    // 导出只包含当前选中的分支；文件需自包含，附件从 blob 还原为 base64。
+    archive.source_conversation.alternate_messages.clear();
+    hydrate_message_blobs(
+        &blob_storage_dir(&state.data_path),
+        &mut archive.source_conversation.messages,
+    );
 
     let selected = if export_format == "json" {
         app.dialog()
@@ -535,7 +588,7 @@ fn export_archive_to_file(
 
     let body = if export_format == "json" {
         let payload = ArchiveExportPayload {
-            version: 1,
+            version: ARCHIVE_EXPORT_VERSION,
             exported_at: now_iso(),
             archive: archive.clone(),
         };
@@ -554,6 +607,112 @@ fn export_archive_to_file(
     })
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportArchivesInput {
+    /// 为空时弹出打开对话框。
+    #[serde(default)]
+    path: Option<String>,
+    /// 外部格式导入到哪个智能体，为空时用当前选中的智能体。
+    #[serde(default)]
+    agent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportArchivesResult {
+    path: String,
+    format: String,
+    imported_count: usize,
+    skipped_count: usize,
+    archives: Vec<ArchiveSummary>,
+}
+
+#[tauri::command]
+fn import_archives(
+    input: ImportArchivesInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ImportArchivesResult, String> {
+    let file_path = match input.path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .dialog()
+            .file()
+            .add_filter("JSON / JSONL", &["json", "jsonl"])
+            .blocking_pick_file()
+            .and_then(|fp| fp.as_path().map(ToOwned::to_owned))
+            .ok_or_else(|| "Import cancelled".to_string())?,
+    };
+    let raw =
+        fs::read_to_string(&file_path).map_err(|err| format!("Read import file failed: {err}"))?;
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data(&state.data_path)?;
+    let _ = ensure_default_agent(&mut data);
+    let agent_id = input
+        .agent_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .unwrap_or(&data.selected_agent_id)
+        .to_string();
+    if !data.agents.iter().any(|a| a.id == agent_id && !a.is_built_in_user) {
+        return Err(format!("Agent '{agent_id}' not found."));
+    }
+    let api_config_id = resolve_selected_api_config(&app_config, None)
+        .map(|api| api.id)
+        .unwrap_or_default();
+
+    let parsed = parse_archive_import(&raw, &agent_id, &api_config_id)?;
+    let mut imported = Vec::<ArchiveSummary>::new();
+    let mut skipped_count = 0usize;
+    for mut archive in parsed.archives {
+        // 同一份文件重复导入时按归档 id / 原对话 id 跳过。
+        let duplicated = data.archived_conversations.iter().any(|a| {
+            a.archive_id == archive.archive_id
+                || (a.reason == ARCHIVE_IMPORT_REASON
+                    && a.source_conversation.id == archive.source_conversation.id)
+        });
+        if duplicated {
+            skipped_count += 1;
+            continue;
+        }
+        let source = &mut archive.source_conversation;
+        if !data.agents.iter().any(|a| a.id == source.agent_id) {
+            source.agent_id = agent_id.clone();
+        }
+        if !app_config.api_configs.iter().any(|a| a.id == source.api_config_id) {
+            source.api_config_id = api_config_id.clone();
+        }
+        imported.push(archive_summary(&archive));
+        data.archived_conversations.push(archive);
+    }
+    if !imported.is_empty() {
+        write_app_data(&state.data_path, &data)?;
+    }
+    drop(guard);
+
+    eprintln!(
+        "[ARCHIVE] imported {} archives ({}) from {}, skipped {}",
+        imported.len(),
+        parsed.format,
+        file_path.display(),
+        skipped_count
+    );
+    Ok(ImportArchivesResult {
+        path: file_path.to_string_lossy().to_string(),
+        format: parsed.format,
+        imported_count: imported.len(),
+        skipped_count,
+        archives: imported,
+    })
+}
+
 #[tauri::command]
 fn list_memories(state: State<'_, AppState>) -> Result<Vec<MemoryEntry>, String> {
     let guard = state
@@ -869,6 +1028,31 @@ fn merge_memories_into_app_data(data: &mut AppData, drafts: &[ArchiveMemoryDraft
     merged
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreArchiveInput {
+    archive_id: String,
+    /// 恢复到哪个聊天 API 配置下，为空时沿用归档原配置。
+    #[serde(default)]
+    api_config_id: Option<String>,
+    /// 需要分叉时保留的最近轮数。
+    #[serde(default)]
+    recent_turns: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreArchiveResult {
+    /// restored | forked
+    mode: String,
+    conversation_id: String,
+    api_config_id: String,
+    agent_id: String,
+    archived_current_id: Option<String>,
+    kept_turns: Option<usize>,
+    message_count: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ForceArchiveResult {
@@ -932,7 +1116,11 @@ async fn summarize_archived_conversation_with_model(
         latest_audios: Vec::new(),
     };
 
-    let reply = call_model_openai_rig_style(resolved_api, &selected_api.model, prepared).await?;
+    let reply = call_with_api_key_rotation(resolved_api, |api_config| {
+        let prepared = prepared.clone();
+        async move { call_model_openai_rig_style(&api_config, &selected_api.model, prepared).await }
+    })
+    .await?;
     let parsed = parse_archive_summary_draft(&reply.assistant_text).ok_or_else(|| {
         format!(
             "Parse archive summary JSON failed. raw={}",
@@ -947,6 +1135,32 @@ async fn summarize_archived_conversation_with_model(
     Ok((summary, memories))
 }
 
+/// 为原地压缩生成累计摘要，覆盖到 until（含）。
+async fn compact_conversation_with_model(
+    resolved_api: &ResolvedApiConfig,
+    selected_api: &ApiConfig,
+    agent: &AgentProfile,
+    user_alias: &str,
+    conversation: &Conversation,
+    until: usize,
+) -> Result<String, String> {
+    let prepared = conversation_compaction_prompt(conversation, until, &agent.name, user_alias);
+    let reply = call_with_api_key_rotation(resolved_api, |api_config| {
+        let prepared = prepared.clone();
+        async move { call_model_openai_rig_style(&api_config, &selected_api.model, prepared).await }
+    })
+    .await?;
+    let text = match reply.assistant_text.rfind("</think>") {
+        Some(pos) => &reply.assistant_text[pos + "</think>".len()..],
+        None => reply.assistant_text.as_str(),
+    };
+    let summary = text.trim();
+    if summary.is_empty() {
+        return Err("Compaction summary is empty".to_string());
+    }
+    Ok(summary.to_string())
+}
+
 #[tauri::command]
 async fn force_archive_current(
     input: SessionSelector,
@@ -958,9 +1172,6 @@ async fn force_archive_current(
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
         let app_config = read_config(&state.config_path)?;
-        let selected_api = resolve_selected_api_config(&app_config, input.api_config_id.as_deref())
-            .ok_or_else(|| "No API config configured. Please add one.".to_string())?;
-        let resolved_api = resolve_api_config(&app_config, Some(selected_api.id.as_str()))?;
         let mut data = read_app_data(&state.data_path)?;
         ensure_default_agent(&mut data);
         let requested_agent_id = input.agent_id.trim();
@@ -989,6 +1200,9 @@ async fn force_archive_current(
             .find(|a| a.id == effective_agent_id)
             .cloned()
             .ok_or_else(|| "Selected agent not found.".to_string())?;
+        let selected_api =
+            resolve_session_api_config(&app_config, Some(&agent), input.api_config_id.as_deref())?;
+        let resolved_api = resolve_api_config(&app_config, Some(selected_api.id.as_str()))?;
         let user_alias = data.user_alias.clone();
         let memories = data.memories.clone();
         let source_idx = latest_active_conversation_index(&data, &selected_api.id, &effective_agent_id)
@@ -1032,11 +1246,7 @@ async fn force_archive_current(
         drop(guard);
         return Err("活动对话已变化，请重试强制归档。".to_string());
     }
-    let _ = ensure_active_conversation_index(
-        &mut data,
-        &selected_api.id,
-        &source.agent_id,
-    );
+    let _ = push_active_conversation(&mut data, &selected_api.id, &source.agent_id, None);
     let merged_memories = merge_memories_into_app_data(&mut data, &summary_memories);
     if merged_memories > 0 {
         invalidate_memory_matcher_cache();
@@ -1052,3 +1262,154 @@ async fn force_archive_current(
     })
 }
 
+#[tauri::command]
+async fn restore_archive(
+    input: RestoreArchiveInput,
+    state: State<'_, AppState>,
+) -> Result<RestoreArchiveResult, String> {
+    let archive_id = input.archive_id.trim().to_string();
+    if archive_id.is_empty() {
+        return Err("archiveId is required".to_string());
+    }
+    let (selected_api, resolved_api, agent, archive_policy, current, placeholder_id, user_alias, memories) = {
+        let guard = state
+            .state_lock
+            .lock()
+            .map_err(|_| "Failed to lock state mutex".to_string())?;
+        let app_config = read_config(&state.config_path)?;
+        let mut data = read_app_data(&state.data_path)?;
+        ensure_default_agent(&mut data);
+        let archive = data
+            .archived_conversations
+            .iter()
+            .find(|a| a.archive_id == archive_id)
+            .ok_or_else(|| "Archive not found".to_string())?;
+        let requested_api_id = input
+            .api_config_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .unwrap_or(&archive.source_conversation.api_config_id);
+        let selected_api = resolve_selected_api_config(&app_config, Some(requested_api_id))
+            .ok_or_else(|| "No API config configured. Please add one.".to_string())?;
+        let resolved_api = resolve_api_config(&app_config, Some(selected_api.id.as_str()))?;
+        let agent = data
+            .agents
+            .iter()
+            .find(|a| a.id == archive.source_conversation.agent_id && !a.is_built_in_user)
+            .cloned()
+            .ok_or_else(|| "The agent of this archive no longer exists.".to_string())?;
+        let archive_policy = effective_archive_policy(&app_config, Some(&agent));
+        let latest = latest_active_conversation_index(&data, &selected_api.id, &agent.id)
+            .map(|idx| data.conversations[idx].clone());
+        if let Some(latest) = &latest {
+            if chat_inflight(&state, &latest.id)? {
+                return Err(
+                    "A reply is still being generated. Stop it before restoring an archive."
+                        .to_string(),
+                );
+            }
+        }
+        // 当前会话为空时只是占位，恢复后由恢复的对话取代；否则先归档它。
+        let (current, placeholder_id) = match latest {
+            Some(c) if c.messages.is_empty() => (None, Some(c.id)),
+            other => (other, None),
+        };
+        let user_alias = data.user_alias.clone();
+        let memories = data.memories.clone();
+        drop(guard);
+        (
+            selected_api,
+            resolved_api,
+            agent,
+            archive_policy,
+            current,
+            placeholder_id,
+            user_alias,
+            memories,
+        )
+    };
+
+    // 先把当前活动对话归档；总结失败时仍归档，只是没有摘要。
+    let mut current_summary = (String::new(), Vec::new());
+    if let Some(current) = &current {
+        match summarize_archived_conversation_with_model(
+            &resolved_api,
+            &selected_api,
+            &agent,
+            &user_alias,
+            current,
+            &memories,
+        )
+        .await
+        {
+            Ok(summary) => current_summary = summary,
+            Err(err) => eprintln!(
+                "[ARCHIVE] summary before restore failed, archive without summary. conversation_id={}, err={}",
+                current.id, err
+            ),
+        }
+    }
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut data = read_app_data(&state.data_path)?;
+    ensure_default_agent(&mut data);
+    let archive_idx = data
+        .archived_conversations
+        .iter()
+        .position(|a| a.archive_id == archive_id)
+        .ok_or_else(|| "Archive not found".to_string())?;
+    let archived_current_id = match &current {
+        Some(current) => {
+            let (summary, drafts) = &current_summary;
+            let archived = archive_conversation_now(&mut data, &current.id, "restore_archive", summary);
+            if archived.is_none() {
+                drop(guard);
+                return Err("活动对话已变化，请重试恢复归档。".to_string());
+            }
+            if merge_memories_into_app_data(&mut data, drafts) > 0 {
+                invalidate_memory_matcher_cache();
+            }
+            archived
+        }
+        None => None,
+    };
+    if let Some(placeholder_id) = placeholder_id.as_deref() {
+        data.conversations.retain(|c| {
+            !(c.id == placeholder_id && c.status == "active" && c.messages.is_empty())
+        });
+    }
+
+    let (conversation, kept_turns) = conversation_from_archive(
+        &data.archived_conversations[archive_idx],
+        &selected_api.id,
+        selected_api.context_window_tokens,
+        archive_policy.force_usage_ratio,
+        input.recent_turns.unwrap_or(RESTORE_ARCHIVE_RECENT_TURNS),
+    );
+    if kept_turns.is_none() {
+        data.archived_conversations.remove(archive_idx);
+    }
+    let result = RestoreArchiveResult {
+        mode: if kept_turns.is_some() { "forked" } else { "restored" }.to_string(),
+        conversation_id: conversation.id.clone(),
+        api_config_id: selected_api.id.clone(),
+        agent_id: agent.id.clone(),
+        archived_current_id,
+        kept_turns,
+        message_count: conversation.messages.len(),
+    };
+    data.conversations.push(conversation);
+    data.selected_agent_id = agent.id.clone();
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+
+    eprintln!(
+        "[ARCHIVE] restore archive_id={} mode={} conversation_id={} messages={}",
+        archive_id, result.mode, result.conversation_id, result.message_count
+    );
+    Ok(result)
+}
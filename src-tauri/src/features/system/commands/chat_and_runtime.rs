@@ -1,45 +1,56 @@
-fn inflight_chat_key(api_config_id: &str, agent_id: &str) -> String {
-    format!("{}::{}", api_config_id.trim(), agent_id.trim())
+/// 编辑重发、重新生成与普通发送共用同一发送流程，差别由这些选项决定。
+#[derive(Debug, Clone, Default)]
+struct ChatSendOptions {
+    /// 该用户消息起（含）的历史转为备选分支，再追加新的用户消息。
+    replace_from_message_id: Option<String>,
+    /// 沿用这条（最后一条）用户消息重新生成回复，新回复落盘时旧回复转为备选分支。
+    regenerate_message_id: Option<String>,
+    /// 本次回复改用的 API 配置；会话仍归属原 API 配置。
+    reply_api_config_id: Option<String>,
 }
 
 #[tauri::command]
 async fn send_chat_message(
     input: SendChatRequest,
+    app: AppHandle,
     state: State<'_, AppState>,
     on_delta: tauri::ipc::Channel<AssistantDeltaEvent>,
 ) -> Result<SendChatResult, String> {
+    run_chat_send(input, ChatSendOptions::default(), app, state, on_delta).await
+}
+
+async fn run_chat_send(
+    input: SendChatRequest,
+    options: ChatSendOptions,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    on_delta: tauri::ipc::Channel<AssistantDeltaEvent>,
+) -> Result<SendChatResult, String> {
+    let edits_history =
+        options.replace_from_message_id.is_some() || options.regenerate_message_id.is_some();
     let requested_api_id = input
         .session
         .as_ref()
-        .and_then(|s| s.api_config_id.as_deref())
-        .map(str::trim)
-        .filter(|v| !v.is_empty())
-        .map(ToOwned::to_owned);
+        .and_then(|s| s.api_config_id.clone());
     let requested_agent_id = input
         .session
         .as_ref()
         .map(|s| s.agent_id.trim().to_string())
         .filter(|v| !v.is_empty());
+    let mut target_conversation_id = input
+        .conversation_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(ToOwned::to_owned);
 
-    let (app_config, selected_api, resolved_api, effective_agent_id) = {
+    let (app_config, selected_api, reply_api, resolved_api, effective_agent_id) = {
         let guard = state
             .state_lock
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
         let app_config = read_config(&state.config_path)?;
-        let selected_api = if let Some(api_id) = requested_api_id.as_deref() {
-            app_config
-                .api_configs
-                .iter()
-                .find(|a| a.id == api_id)
-                .cloned()
-                .ok_or_else(|| format!("Selected API config '{api_id}' not found."))?
-        } else {
-            resolve_selected_api_config(&app_config, None)
-                .ok_or_else(|| "No API config configured. Please add one.".to_string())?
-        };
-        let resolved_api = resolve_api_config(&app_config, Some(selected_api.id.as_str()))?;
-        let mut data = read_app_data(&state.data_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
         let changed = ensure_default_agent(&mut data);
         if changed {
             write_app_data(&state.data_path, &data)?;
@@ -67,11 +78,56 @@ async fn send_chat_message(
                 .map(|a| a.id.clone())
                 .ok_or_else(|| "No assistant agent configured.".to_string())?
         };
+        let agent = data
+            .agents
+            .iter()
+            .find(|a| a.id == effective_agent_id)
+            .cloned()
+            .ok_or_else(|| "Selected agent not found.".to_string())?;
+        let selected_api =
+            resolve_session_api_config(&app_config, Some(&agent), requested_api_id.as_deref())?;
+        let mut reply_api = match options.reply_api_config_id.as_deref() {
+            Some(api_id) => app_config
+                .api_configs
+                .iter()
+                .find(|a| a.id == api_id)
+                .cloned()
+                .ok_or_else(|| format!("Selected API config '{api_id}' not found."))?,
+            None => selected_api.clone(),
+        };
+        let mut resolved_api = resolve_api_config(&app_config, Some(reply_api.id.as_str()))?;
+        apply_agent_overrides(&mut reply_api, &mut resolved_api, &agent);
+        // 进行中的回复按会话登记，先确定本次发送的会话（没有时新建）。
+        let conversation_id = match target_conversation_id.as_deref() {
+            Some(id) => {
+                let idx = active_conversation_index_by_id(
+                    &data,
+                    id,
+                    &selected_api.id,
+                    &effective_agent_id,
+                )?;
+                data.conversations[idx].id.clone()
+            }
+            None => {
+                let before_len = data.conversations.len();
+                let idx = ensure_active_conversation_index(
+                    &mut data,
+                    &selected_api.id,
+                    &effective_agent_id,
+                );
+                if data.conversations.len() != before_len {
+                    write_app_data(&state.data_path, &data)?;
+                }
+                data.conversations[idx].id.clone()
+            }
+        };
+        target_conversation_id = Some(conversation_id);
         drop(guard);
-        (app_config, selected_api, resolved_api, effective_agent_id)
+        (app_config, selected_api, reply_api, resolved_api, effective_agent_id)
     };
 
-    let chat_key = inflight_chat_key(&selected_api.id, &effective_agent_id);
+    // 自动归档换成新会话后仍沿用原会话 id 登记：前端停止时传的是它发送时所在的会话。
+    let chat_key = target_conversation_id.clone().unwrap_or_default();
     let (abort_handle, abort_registration) = AbortHandle::new_pair();
     {
         let mut inflight = state
@@ -99,7 +155,7 @@ async fn send_chat_message(
         return Err("当前版本仅支持本地语音识别，发送消息不支持语音附件。".to_string());
     }
 
-    if !selected_api.enable_image {
+    if !reply_api.enable_image {
         let images = effective_payload.images.clone().unwrap_or_default();
         if !images.is_empty() {
             let vision_api = resolve_vision_api_config(&app_config).ok();
@@ -121,7 +177,7 @@ async fn send_chat_message(
                             .state_lock
                             .lock()
                             .map_err(|_| "Failed to lock state mutex".to_string())?;
-                        let data = read_app_data(&state.data_path)?;
+                        let data = read_app_data_headers(&state.data_path)?;
                         drop(guard);
                         find_image_text_cache(&data, &hash, &vision_api.id)
                     };
@@ -132,9 +188,13 @@ async fn send_chat_message(
                         continue;
                     }
 
-                    let converted =
-                        describe_image_with_vision_api(&vision_resolved, &vision_api, image)
-                            .await?;
+                    let converted = call_with_api_key_rotation(&vision_resolved, |api_config| {
+                        let vision_api = &vision_api;
+                        async move {
+                            describe_image_with_vision_api(&api_config, vision_api, image).await
+                        }
+                    })
+                    .await?;
                     let converted = converted.trim().to_string();
                     if converted.is_empty() {
                         continue;
@@ -146,7 +206,7 @@ async fn send_chat_message(
                         .state_lock
                         .lock()
                         .map_err(|_| "Failed to lock state mutex".to_string())?;
-                    let mut data = read_app_data(&state.data_path)?;
+                    let mut data = read_app_data_headers(&state.data_path)?;
                     upsert_image_text_cache(&mut data, &hash, &vision_api.id, &converted);
                     write_app_data(&state.data_path, &data)?;
                     drop(guard);
@@ -173,7 +233,7 @@ async fn send_chat_message(
         }
     }
 
-    let effective_user_parts = build_user_parts(&effective_payload, &selected_api)?;
+    let effective_user_parts = build_user_parts(&effective_payload, &reply_api)?;
     let effective_user_text = effective_user_parts
         .iter()
         .map(|part| match part {
@@ -212,36 +272,53 @@ async fn send_chat_message(
     let mut pending_archive_source: Option<Conversation> = None;
     let mut pending_archive_reason = String::new();
     let mut pending_archive_forced = false;
+    let mut pending_compact_source: Option<Conversation> = None;
+    let mut archive_policy = app_config.archive_policy.clone();
 
-    {
+    // 编辑历史时不做自动归档，否则被编辑的消息会随旧会话一起归档。
+    if !edits_history {
         let guard = state
             .state_lock
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
-        let mut data = read_app_data(&state.data_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
         ensure_default_agent(&mut data);
-        let _agent = data
+        let agent = data
             .agents
             .iter()
             .find(|a| a.id == effective_agent_id)
-            .cloned()
             .ok_or_else(|| "Selected agent not found.".to_string())?;
-
-        if let Some(idx) =
-            latest_active_conversation_index(&data, &selected_api.id, &effective_agent_id)
-        {
+        archive_policy = effective_archive_policy(&app_config, Some(agent));
+
+        let target_idx = match target_conversation_id.as_deref() {
+            Some(id) => Some(active_conversation_index_by_id(
+                &data,
+                id,
+                &selected_api.id,
+                &effective_agent_id,
+            )?),
+            None => latest_active_conversation_index(&data, &selected_api.id, &effective_agent_id),
+        };
+        if let Some(idx) = target_idx {
             let conversation = data
                 .conversations
                 .get_mut(idx)
                 .ok_or_else(|| "Active conversation index is out of bounds.".to_string())?;
-            let decision =
-                decide_archive_before_user_message(conversation, selected_api.context_window_tokens);
+            load_conversation_messages(&state.data_path, conversation)?;
+            let decision = decide_archive_before_user_message(
+                conversation,
+                selected_api.context_window_tokens,
+                &archive_policy,
+                now_utc().to_offset(configured_time_zone(&app_config).offset),
+            );
             conversation.last_context_usage_ratio = decision.usage_ratio;
             eprintln!(
                 "[ARCHIVE] check before user message: should_archive={}, forced={}, reason={}, usage_ratio={:.4}",
                 decision.should_archive, decision.forced, decision.reason, decision.usage_ratio
             );
-            if decision.should_archive {
+            if decision.compact {
+                pending_compact_source = Some(conversation.clone());
+            } else if decision.should_archive {
                 pending_archive_source = Some(conversation.clone());
                 pending_archive_reason = decision.reason.clone();
                 pending_archive_forced = decision.forced;
@@ -251,6 +328,111 @@ async fn send_chat_message(
         drop(guard);
     }
 
+    if let Some(source) = pending_compact_source {
+        match compaction_boundary(&source, archive_policy.fallback_recent_turns) {
+            // 未压缩部分只剩一轮，无从压缩，改为强制归档。
+            None => {
+                pending_archive_source = Some(source);
+                pending_archive_reason = "force_context_usage".to_string();
+                pending_archive_forced = true;
+            }
+            Some(until) => {
+                let _ = on_delta.send(AssistantDeltaEvent {
+                    delta: "".to_string(),
+                    kind: Some("tool_status".to_string()),
+                    tool_name: Some("archive".to_string()),
+                    tool_status: Some("running".to_string()),
+                    message: Some("正在压缩较早的对话...".to_string()),
+                });
+                let (agent, user_alias) = {
+                    let guard = state
+                        .state_lock
+                        .lock()
+                        .map_err(|_| "Failed to lock state mutex".to_string())?;
+                    let mut data = read_app_data_headers(&state.data_path)?;
+                    ensure_default_agent(&mut data);
+                    let agent = data
+                        .agents
+                        .iter()
+                        .find(|a| a.id == effective_agent_id)
+                        .cloned()
+                        .ok_or_else(|| "Selected agent not found.".to_string())?;
+                    drop(guard);
+                    (agent, data.user_alias)
+                };
+                let summary_result = compact_conversation_with_model(
+                    &resolved_api,
+                    &selected_api,
+                    &agent,
+                    &user_alias,
+                    &source,
+                    until,
+                )
+                .await;
+                // 摘要失败或为空时不改动压缩状态，本次仍发送完整历史。
+                let mut compacted = false;
+                match summary_result {
+                    Ok(summary) => {
+                        let guard = state
+                            .state_lock
+                            .lock()
+                            .map_err(|_| "Failed to lock state mutex".to_string())?;
+                        let mut data = read_app_data_headers(&state.data_path)?;
+                        load_conversation_messages_by_id(&state.data_path, &mut data, &source.id)?;
+                        let until_id = &source.messages[until].id;
+                        if let Some(conv) = data
+                            .conversations
+                            .iter_mut()
+                            .find(|c| c.id == source.id && c.status == "active")
+                        {
+                            if let Some(pos) = conv.messages.iter().position(|m| &m.id == until_id) {
+                                compacted = apply_conversation_compaction(conv, pos, &summary);
+                                if compacted {
+                                    conv.last_context_usage_ratio = compute_context_usage_ratio(
+                                        conv,
+                                        selected_api.context_window_tokens,
+                                    );
+                                    eprintln!(
+                                        "[ARCHIVE] compacted in place. conversation_id={}, until={}, usage_ratio={:.4}",
+                                        conv.id, pos, conv.last_context_usage_ratio
+                                    );
+                                } else {
+                                    eprintln!(
+                                        "[ARCHIVE] compaction summary empty, keep previous state. conversation_id={}",
+                                        conv.id
+                                    );
+                                }
+                            }
+                        }
+                        if compacted {
+                            write_app_data(&state.data_path, &data)?;
+                        }
+                        drop(guard);
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "[ARCHIVE] compaction summary failed, keep previous state. conversation_id={}, err={}",
+                            source.id, err
+                        );
+                    }
+                }
+
+                let (status, message) = if compacted {
+                    ("done", "已压缩较早的对话，最近几轮保留原文。")
+                } else {
+                    ("failed", "压缩摘要失败，本次仍发送未压缩的对话。")
+                };
+                let _ = on_delta.send(AssistantDeltaEvent {
+                    delta: "".to_string(),
+                    kind: Some("tool_status".to_string()),
+                    tool_name: Some("archive".to_string()),
+                    tool_status: Some(status.to_string()),
+                    message: Some(message.to_string()),
+                });
+            }
+        }
+    }
+
     if let Some(source) = pending_archive_source {
         if pending_archive_forced {
             let _ = on_delta.send(AssistantDeltaEvent {
@@ -267,7 +449,7 @@ async fn send_chat_message(
                 .state_lock
                 .lock()
                 .map_err(|_| "Failed to lock state mutex".to_string())?;
-            let mut data = read_app_data(&state.data_path)?;
+            let mut data = read_app_data_headers(&state.data_path)?;
             ensure_default_agent(&mut data);
             let agent = data
                 .agents
@@ -298,8 +480,9 @@ async fn send_chat_message(
             .state_lock
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
-        let mut data = read_app_data(&state.data_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
         ensure_default_agent(&mut data);
+        load_conversation_messages_by_id(&state.data_path, &mut data, &source.id)?;
 
         match summary_result {
             Ok(summary) => {
@@ -311,11 +494,14 @@ async fn send_chat_message(
                 )
                 .is_some()
                 {
-                    let _ = ensure_active_conversation_index(
+                    // 自动归档只作用于本次发送的会话，由新会话接替它。
+                    let idx = push_active_conversation(
                         &mut data,
                         &selected_api.id,
                         &effective_agent_id,
+                        None,
                     );
+                    target_conversation_id = Some(data.conversations[idx].id.clone());
                     let memory_merged = merge_memories_into_app_data(&mut data, &summary_memories);
                     if memory_merged > 0 {
                         invalidate_memory_matcher_cache();
@@ -340,21 +526,27 @@ async fn send_chat_message(
                     .iter_mut()
                     .find(|c| c.id == source.id && c.status == "active")
                 {
-                    let fallback_messages = keep_recent_turns(&source.messages, 3);
+                    let fallback_messages =
+                        keep_recent_turns(&source.messages, archive_policy.fallback_recent_turns);
                     conv.messages = fallback_messages.clone();
+                    prune_orphan_branches(conv);
+                    drop_stale_compaction(conv);
                     let mut tmp = conv.clone();
                     tmp.messages = fallback_messages.clone();
                     let usage_after = compute_context_usage_ratio(&tmp, selected_api.context_window_tokens);
-                    if usage_after >= 0.82 {
+                    if usage_after >= archive_policy.force_usage_ratio {
                         let now = now_iso();
                         conv.id = Uuid::new_v4().to_string();
                         conv.title = format!("Chat {}", &now.chars().take(16).collect::<String>());
                         conv.created_at = now.clone();
                         conv.updated_at = now;
                         conv.messages.clear();
+                        conv.alternate_messages.clear();
                         conv.last_user_at = None;
                         conv.last_assistant_at = None;
                         conv.last_context_usage_ratio = 0.0;
+                        conv.forked_from_archive_id = None;
+                        conv.compaction = None;
                         write_app_data(&state.data_path, &data)?;
                         drop(guard);
                         if pending_archive_forced {
@@ -368,24 +560,7 @@ async fn send_chat_message(
                         }
                         return Err("归档失败且上下文仍超限，已自动开启新对话，请重新发送消息。".to_string());
                     }
-                    conv.last_user_at = conv
-                        .messages
-                        .iter()
-                        .rev()
-                        .find(|m| m.role == "user")
-                        .map(|m| m.created_at.clone());
-                    conv.last_assistant_at = conv
-                        .messages
-                        .iter()
-                        .rev()
-                        .find(|m| m.role == "assistant")
-                        .map(|m| m.created_at.clone());
-                    conv.updated_at = now_iso();
-                    conv.last_context_usage_ratio = if conv.messages.is_empty() {
-                        0.0
-                    } else {
-                        compute_context_usage_ratio(conv, selected_api.context_window_tokens)
-                    };
+                    refresh_conversation_after_edit(conv, selected_api.context_window_tokens);
                 }
             }
         }
@@ -398,7 +573,7 @@ async fn send_chat_message(
             let message = if archived_before_send {
                 "归档完成，已优化上下文。"
             } else {
-                "归档失败，已自动回退为最近三轮或新对话。"
+                "归档失败，已自动回退为最近几轮或新对话。"
             };
             let _ = on_delta.send(AssistantDeltaEvent {
                 delta: "".to_string(),
@@ -416,7 +591,7 @@ async fn send_chat_message(
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
 
-        let mut data = read_app_data(&state.data_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
         ensure_default_agent(&mut data);
         let agent = data
             .agents
@@ -425,7 +600,15 @@ async fn send_chat_message(
             .cloned()
             .ok_or_else(|| "Selected agent not found.".to_string())?;
 
-        let idx = ensure_active_conversation_index(&mut data, &selected_api.id, &effective_agent_id);
+        let idx = match target_conversation_id.as_deref() {
+            Some(id) => {
+                active_conversation_index_by_id(&data, id, &selected_api.id, &effective_agent_id)?
+            }
+            None => {
+                ensure_active_conversation_index(&mut data, &selected_api.id, &effective_agent_id)
+            }
+        };
+        load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
 
         // 聊天记录保留用户原始多模态内容；模型请求使用 effective_payload（可能已做图转文）。
         let mut storage_api = selected_api.clone();
@@ -436,15 +619,7 @@ async fn send_chat_message(
         let search_text = conversation_search_text(&conversation_before);
         let memory_board_xml =
             build_memory_board_xml(&data.memories, &search_text, &effective_user_text);
-        let last_archive_summary = data
-            .archived_conversations
-            .iter()
-            .rev()
-            .find(|a| {
-                a.source_conversation.agent_id == effective_agent_id
-                    && !a.summary.trim().is_empty()
-            })
-            .map(|a| a.summary.clone());
+        let last_archive_summary = archive_recap_summary(&data, &conversation_before);
 
         let mut extra_text_blocks = Vec::<String>::new();
         if let Some(xml) = &memory_board_xml {
@@ -454,24 +629,48 @@ async fn send_chat_message(
 
         let now = now_iso();
 
-        let user_message = ChatMessage {
-            id: Uuid::new_v4().to_string(),
-            role: "user".to_string(),
-            created_at: now.clone(),
-            parts: user_parts,
-            extra_text_blocks,
-            provider_meta: None,
-            tool_call: None,
-            mcp_call: None,
-        };
-
-        data.conversations[idx].messages.push(user_message);
-        data.conversations[idx].updated_at = now.clone();
-        data.conversations[idx].last_user_at = Some(now_iso());
-        data.conversations[idx].last_context_usage_ratio =
-            compute_context_usage_ratio(&data.conversations[idx], selected_api.context_window_tokens);
+        let (conversation, user_time) = if let Some(message_id) =
+            options.regenerate_message_id.as_deref()
+        {
+            // 旧回复先保留，新回复生成成功后旧回复转为备选分支；提示词只用到这条用户消息为止。
+            let mut conversation = data.conversations[idx].clone();
+            let user_pos = regenerate_user_message_position(&conversation, message_id)?;
+            conversation.messages.truncate(user_pos + 1);
+            let user_time = conversation.messages[user_pos].created_at.clone();
+            (conversation, user_time)
+        } else {
+            if let Some(message_id) = options.replace_from_message_id.as_deref() {
+                let conversation = &mut data.conversations[idx];
+                let pos = conversation
+                    .messages
+                    .iter()
+                    .position(|m| m.id == message_id && m.role == "user")
+                    .ok_or_else(|| "The user message to edit was not found.".to_string())?;
+                // 原消息及其后的对话保留为备选分支，新消息作为它的兄弟。
+                stash_branch_tail(conversation, pos);
+            }
+            let user_message = ChatMessage {
+                id: Uuid::new_v4().to_string(),
+                role: "user".to_string(),
+                created_at: now.clone(),
+                parts: user_parts,
+                extra_text_blocks,
+                provider_meta: None,
+                tool_call: None,
+                mcp_call: None,
+                parent_id: None,
+                sibling_index: 0,
+            };
 
-        let conversation = data.conversations[idx].clone();
+            push_branch_message(&mut data.conversations[idx], user_message);
+            data.conversations[idx].updated_at = now.clone();
+            data.conversations[idx].last_user_at = Some(now_iso());
+            data.conversations[idx].last_context_usage_ratio = compute_context_usage_ratio(
+                &data.conversations[idx],
+                selected_api.context_window_tokens,
+            );
+            (data.conversations[idx].clone(), now.clone())
+        };
         let user_name = user_persona_name(&data);
         let user_intro = user_persona_intro(&data);
         let mut prepared = build_prompt(
@@ -479,8 +678,8 @@ async fn send_chat_message(
             &agent,
             &user_name,
             &user_intro,
-            &data.response_style_id,
-            &app_config.ui_language,
+            &agent_response_style(&data, &agent),
+            &app_config,
         );
         if let Some(summary) = last_archive_summary {
             prepared.preamble.push_str(
@@ -494,14 +693,15 @@ async fn send_chat_message(
             block2_parts.push(xml.clone());
         }
         prepared.latest_user_text = latest_user_text.clone();
-        prepared.latest_user_time_text = format_message_time_text(&now);
+        prepared.latest_user_time_text =
+            format_message_time_text(&user_time, &configured_time_zone(&app_config));
         prepared.latest_user_system_text = block2_parts.join("\n\n");
         prepared.latest_images = effective_images.clone();
         prepared.latest_audios = effective_audios.clone();
 
         // Use persisted API config as the source of truth to avoid stale
         // frontend model overrides after editing/saving config.
-        let model_name = selected_api.model.trim().to_string();
+        let model_name = reply_api.model.trim().to_string();
         let model_name = if model_name.trim().is_empty() {
             resolved_api.model.clone()
         } else {
@@ -509,7 +709,9 @@ async fn send_chat_message(
         };
         let conversation_id = conversation.id.clone();
 
-        write_app_data(&state.data_path, &data)?;
+        if options.regenerate_message_id.is_none() {
+            write_app_data(&state.data_path, &data)?;
+        }
         drop(guard);
 
         (
@@ -520,15 +722,26 @@ async fn send_chat_message(
         )
     };
 
-    let model_reply = call_model_openai_style(
-        &resolved_api,
-        &selected_api,
-        &model_name,
-        prepared_prompt,
-        Some(&state),
-        &on_delta,
-        app_config.tool_max_iterations as usize,
-    )
+    let app_state: &AppState = &state;
+    let max_tool_iterations = app_config.tool_max_iterations as usize;
+    let model_reply = call_with_api_key_rotation(&resolved_api, |api_config| {
+        let prepared = prepared_prompt.clone();
+        let selected_api = &reply_api;
+        let model_name = &model_name;
+        let on_delta = &on_delta;
+        async move {
+            call_model_openai_style(
+                &api_config,
+                selected_api,
+                model_name,
+                prepared,
+                Some(app_state),
+                on_delta,
+                max_tool_iterations,
+            )
+            .await
+        }
+    })
     .await?;
     let assistant_text = model_reply.assistant_text;
     let reasoning_standard = model_reply.reasoning_standard;
@@ -555,14 +768,19 @@ async fn send_chat_message(
             .lock()
             .map_err(|_| "Failed to lock state mutex".to_string())?;
 
-        let mut data = read_app_data(&state.data_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
+        load_conversation_messages_by_id(&state.data_path, &mut data, &conversation_id)?;
         if let Some(conversation) = data
             .conversations
             .iter_mut()
             .find(|c| c.id == conversation_id && c.status == "active")
         {
+            if let Some(message_id) = options.regenerate_message_id.as_deref() {
+                let user_pos = regenerate_user_message_position(conversation, message_id)?;
+                stash_branch_tail(conversation, user_pos + 1);
+            }
             let now = now_iso();
-            conversation.messages.push(ChatMessage {
+            push_branch_message(conversation, ChatMessage {
                 id: Uuid::new_v4().to_string(),
                 role: "assistant".to_string(),
                 created_at: now.clone(),
@@ -577,12 +795,14 @@ async fn send_chat_message(
                     Some(tool_history_events.clone())
                 },
                 mcp_call: None,
+                parent_id: None,
+                sibling_index: 0,
             });
             conversation.updated_at = now.clone();
             conversation.last_assistant_at = Some(now);
             conversation.last_context_usage_ratio =
                 compute_context_usage_ratio(conversation, selected_api.context_window_tokens);
-            write_app_data(&state.data_path, &data)?;
+            write_conversation_data(&state.data_path, &data, &conversation_id)?;
         }
         drop(guard);
     }
@@ -606,10 +826,12 @@ async fn send_chat_message(
         inflight.remove(&chat_key);
     }
 
-    match result {
-        Ok(inner) => inner,
-        Err(_) => Err("CHAT_ABORTED_BY_USER".to_string()),
-    }
+    let result = match result {
+        Ok(inner) => inner?,
+        Err(_) => return Err("CHAT_ABORTED_BY_USER".to_string()),
+    };
+    spawn_conversation_title_task(&app, &result.conversation_id);
+    Ok(result)
 }
 
 #[tauri::command]
@@ -617,20 +839,39 @@ async fn stop_chat_message(
     input: StopChatRequest,
     state: State<'_, AppState>,
 ) -> Result<StopChatResult, String> {
-    let api_config_id = input
-        .session
-        .api_config_id
-        .as_deref()
-        .map(str::trim)
-        .filter(|v| !v.is_empty())
-        .ok_or_else(|| "Missing session.apiConfigId".to_string())?
-        .to_string();
     let agent_id = input.session.agent_id.trim().to_string();
     if agent_id.is_empty() {
         return Err("Missing session.agentId".to_string());
     }
 
-    let chat_key = inflight_chat_key(&api_config_id, &agent_id);
+    // 与发送时相同的顺序解析 API；未指定会话时停止该 API 配置与智能体下的当前会话。
+    let (selected_api, chat_key) = {
+        let guard = state
+            .state_lock
+            .lock()
+            .map_err(|_| "Failed to lock state mutex".to_string())?;
+        let app_config = read_config(&state.config_path)?;
+        let data = read_app_data_headers(&state.data_path)?;
+        drop(guard);
+        let agent = data.agents.iter().find(|a| a.id == agent_id);
+        let selected_api = resolve_session_api_config(
+            &app_config,
+            agent,
+            input.session.api_config_id.as_deref(),
+        )?;
+        let chat_key = match input
+            .conversation_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+        {
+            Some(id) => id.to_string(),
+            None => latest_active_conversation_index(&data, &selected_api.id, &agent_id)
+                .map(|idx| data.conversations[idx].id.clone())
+                .unwrap_or_default(),
+        };
+        (selected_api, chat_key)
+    };
     let aborted = {
         let mut inflight = state
             .inflight_chat_abort_handles
@@ -662,17 +903,14 @@ async fn stop_chat_message(
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let app_config = read_config(&state.config_path)?;
-    let selected_api = app_config
-        .api_configs
-        .iter()
-        .find(|api| api.id == api_config_id)
-        .cloned()
-        .ok_or_else(|| format!("Selected API config '{api_config_id}' not found."))?;
-    let mut data = read_app_data(&state.data_path)?;
-    ensure_default_agent(&mut data);
+    let mut data = read_app_data_headers(&state.data_path)?;
 
-    let idx = latest_active_conversation_index(&data, &api_config_id, &agent_id);
+    // 发送前被自动归档时，回复落在接替它的当前会话里。
+    let idx = data
+        .conversations
+        .iter()
+        .position(|c| c.id == chat_key && c.status == "active")
+        .or_else(|| latest_active_conversation_index(&data, &selected_api.id, &agent_id));
     let Some(idx) = idx else {
         drop(guard);
         return Ok(StopChatResult {
@@ -685,6 +923,7 @@ async fn stop_chat_message(
         .conversations
         .get_mut(idx)
         .ok_or_else(|| "Active conversation index is out of bounds.".to_string())?;
+    load_conversation_messages(&state.data_path, conversation)?;
 
     // If the latest message is already an assistant message, do not append duplicate partial output.
     if conversation
@@ -713,7 +952,7 @@ async fn stop_chat_message(
     };
 
     let now = now_iso();
-    conversation.messages.push(ChatMessage {
+    push_branch_message(conversation, ChatMessage {
         id: Uuid::new_v4().to_string(),
         role: "assistant".to_string(),
         created_at: now.clone(),
@@ -724,6 +963,8 @@ async fn stop_chat_message(
         provider_meta,
         tool_call: None,
         mcp_call: None,
+        parent_id: None,
+        sibling_index: 0,
     });
     conversation.updated_at = now.clone();
     conversation.last_assistant_at = Some(now);
@@ -731,7 +972,7 @@ async fn stop_chat_message(
         compute_context_usage_ratio(conversation, selected_api.context_window_tokens);
     let conversation_id = conversation.id.clone();
 
-    write_app_data(&state.data_path, &data)?;
+    write_conversation_data(&state.data_path, &data, &conversation_id)?;
     drop(guard);
 
     Ok(StopChatResult {
@@ -741,6 +982,391 @@ async fn stop_chat_message(
     })
 }
 
+/// 编辑类命令的目标会话：指定 id 时按 id 查找，否则取会话选择对应的当前会话。
+fn resolve_edit_target_conversation(
+    app_config: &AppConfig,
+    data: &AppData,
+    session: Option<&SessionSelector>,
+    conversation_id: Option<&str>,
+) -> Result<(ApiConfig, usize), String> {
+    let requested_agent_id = session.map(|s| s.agent_id.as_str()).unwrap_or("");
+    let agent_id = resolve_session_agent_id(data, requested_agent_id)?;
+    let api_config = resolve_session_api_config(
+        app_config,
+        data.agents.iter().find(|a| a.id == agent_id),
+        session.and_then(|s| s.api_config_id.as_deref()),
+    )?;
+    let idx = match conversation_id.map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => active_conversation_index_by_id(data, id, &api_config.id, &agent_id)?,
+        None => latest_active_conversation_index(data, &api_config.id, &agent_id)
+            .ok_or_else(|| "No active conversation.".to_string())?,
+    };
+    Ok((api_config, idx))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EditChatMessageInput {
+    message_id: String,
+    payload: ChatInputPayload,
+    #[serde(default)]
+    session: Option<SessionSelector>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+}
+
+/// 修改一条用户消息并重新发送：原消息及其后的对话转为备选分支，新的一轮成为当前分支。
+#[tauri::command]
+async fn edit_chat_message(
+    input: EditChatMessageInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    on_delta: tauri::ipc::Channel<AssistantDeltaEvent>,
+) -> Result<SendChatResult, String> {
+    let message_id = input.message_id.trim().to_string();
+    if message_id.is_empty() {
+        return Err("messageId is required".to_string());
+    }
+    let request = SendChatRequest {
+        payload: input.payload,
+        session: input.session,
+        conversation_id: input.conversation_id,
+    };
+    let options = ChatSendOptions {
+        replace_from_message_id: Some(message_id),
+        ..ChatSendOptions::default()
+    };
+    run_chat_send(request, options, app, state, on_delta).await
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegenerateChatReplyInput {
+    #[serde(default)]
+    session: Option<SessionSelector>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    /// 为空时沿用会话的 API 配置。
+    #[serde(default)]
+    api_config_id: Option<String>,
+}
+
+#[tauri::command]
+async fn regenerate_chat_reply(
+    input: RegenerateChatReplyInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    on_delta: tauri::ipc::Channel<AssistantDeltaEvent>,
+) -> Result<SendChatResult, String> {
+    let (conversation_id, message_id, payload) = {
+        let guard = state
+            .state_lock
+            .lock()
+            .map_err(|_| "Failed to lock state mutex".to_string())?;
+        let app_config = read_config(&state.config_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
+        let (_, idx) = resolve_edit_target_conversation(
+            &app_config,
+            &data,
+            input.session.as_ref(),
+            input.conversation_id.as_deref(),
+        )?;
+        load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
+        drop(guard);
+        let conversation = &data.conversations[idx];
+        let mut user_message = conversation
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .cloned()
+            .ok_or_else(|| "No user message to regenerate a reply for.".to_string())?;
+        hydrate_message_blobs(
+            &blob_storage_dir(&state.data_path),
+            std::slice::from_mut(&mut user_message),
+        );
+        (
+            conversation.id.clone(),
+            user_message.id.clone(),
+            chat_payload_from_user_message(&user_message),
+        )
+    };
+    let request = SendChatRequest {
+        payload,
+        session: input.session,
+        conversation_id: Some(conversation_id),
+    };
+    let options = ChatSendOptions {
+        regenerate_message_id: Some(message_id),
+        reply_api_config_id: input
+            .api_config_id
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty()),
+        ..ChatSendOptions::default()
+    };
+    run_chat_send(request, options, app, state, on_delta).await
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteChatMessageInput {
+    message_id: String,
+    /// 为 true 时删除消息所在的整轮。
+    #[serde(default)]
+    whole_turn: bool,
+    #[serde(default)]
+    session: Option<SessionSelector>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteChatMessageResult {
+    conversation_id: String,
+    removed_count: usize,
+    message_count: usize,
+}
+
+#[tauri::command]
+fn delete_chat_message(
+    input: DeleteChatMessageInput,
+    state: State<'_, AppState>,
+) -> Result<DeleteChatMessageResult, String> {
+    let message_id = input.message_id.trim();
+    if message_id.is_empty() {
+        return Err("messageId is required".to_string());
+    }
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let (api_config, idx) = resolve_edit_target_conversation(
+        &app_config,
+        &data,
+        input.session.as_ref(),
+        input.conversation_id.as_deref(),
+    )?;
+    if chat_inflight(&state, &data.conversations[idx].id)? {
+        return Err("A reply is still being generated. Stop it before deleting messages.".to_string());
+    }
+    let conversation = &mut data.conversations[idx];
+    load_conversation_messages(&state.data_path, conversation)?;
+    let removed_count = remove_conversation_messages(conversation, message_id, input.whole_turn)?;
+    refresh_conversation_after_edit(conversation, api_config.context_window_tokens);
+    let result = DeleteChatMessageResult {
+        conversation_id: conversation.id.clone(),
+        removed_count,
+        message_count: conversation.messages.len(),
+    };
+    write_conversation_data(&state.data_path, &data, &result.conversation_id)?;
+    match gc_unreferenced_blobs(&state.data_path, &data) {
+        Ok(removed) if removed > 0 => eprintln!("[BLOB] removed {removed} unreferenced blobs"),
+        Ok(_) => {}
+        Err(err) => eprintln!("[BLOB] gc failed: {err}"),
+    }
+    drop(guard);
+    Ok(result)
+}
+
+/// 该会话是否有正在生成的回复。
+fn chat_inflight(state: &AppState, conversation_id: &str) -> Result<bool, String> {
+    Ok(state
+        .inflight_chat_abort_handles
+        .lock()
+        .map_err(|_| "Failed to lock inflight chat abort handles".to_string())?
+        .contains_key(conversation_id.trim()))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationBranchesInput {
+    #[serde(default)]
+    session: Option<SessionSelector>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+}
+
+#[tauri::command]
+fn get_conversation_branches(
+    input: ConversationBranchesInput,
+    state: State<'_, AppState>,
+) -> Result<Vec<MessageBranchInfo>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let Ok((_, idx)) = resolve_edit_target_conversation(
+        &app_config,
+        &data,
+        input.session.as_ref(),
+        input.conversation_id.as_deref(),
+    ) else {
+        return Ok(Vec::new());
+    };
+    load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
+    drop(guard);
+    Ok(conversation_branch_infos(&data.conversations[idx]))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwitchMessageBranchInput {
+    message_id: String,
+    /// -1 切到上一个兄弟分支，1 切到下一个。
+    offset: i32,
+    #[serde(default)]
+    session: Option<SessionSelector>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+}
+
+/// 在同一父消息下的备选回复 / 编辑前后的提问之间切换，返回切换后的分支信息。
+#[tauri::command]
+fn switch_message_branch(
+    input: SwitchMessageBranchInput,
+    state: State<'_, AppState>,
+) -> Result<Vec<MessageBranchInfo>, String> {
+    let message_id = input.message_id.trim();
+    if message_id.is_empty() {
+        return Err("messageId is required".to_string());
+    }
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let (api_config, idx) = resolve_edit_target_conversation(
+        &app_config,
+        &data,
+        input.session.as_ref(),
+        input.conversation_id.as_deref(),
+    )?;
+    if chat_inflight(&state, &data.conversations[idx].id)? {
+        return Err("A reply is still being generated. Stop it before switching branches.".to_string());
+    }
+    let conversation = &mut data.conversations[idx];
+    load_conversation_messages(&state.data_path, conversation)?;
+    switch_conversation_branch(conversation, message_id, input.offset)?;
+    refresh_conversation_after_edit(conversation, api_config.context_window_tokens);
+    let branches = conversation_branch_infos(conversation);
+    let conversation_id = conversation.id.clone();
+    write_conversation_data(&state.data_path, &data, &conversation_id)?;
+    drop(guard);
+    Ok(branches)
+}
+
+const CONVERSATION_TITLE_EVENT: &str = "easy-call:conversation-title";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationTitleEvent {
+    conversation_id: String,
+    title: String,
+}
+
+/// 回复落盘后在后台为会话生成标题；失败只记日志，下一轮对话后再试。
+fn spawn_conversation_title_task(app: &AppHandle, conversation_id: &str) {
+    let app = app.clone();
+    let conversation_id = conversation_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        match generate_conversation_title(&app, &conversation_id).await {
+            Ok(Some(title)) => {
+                let _ = app.emit(
+                    CONVERSATION_TITLE_EVENT,
+                    ConversationTitleEvent {
+                        conversation_id,
+                        title,
+                    },
+                );
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!(
+                "[TITLE] generate failed. conversation_id={}, err={}",
+                conversation_id, err
+            ),
+        }
+    });
+}
+
+async fn generate_conversation_title(
+    app: &AppHandle,
+    conversation_id: &str,
+) -> Result<Option<String>, String> {
+    let state = app.state::<AppState>();
+    let (model_name, resolved_api, prepared) = {
+        let guard = state
+            .state_lock
+            .lock()
+            .map_err(|_| "Failed to lock state mutex".to_string())?;
+        let app_config = read_config(&state.config_path)?;
+        let mut data = read_app_data_headers(&state.data_path)?;
+        load_conversation_messages_by_id(&state.data_path, &mut data, conversation_id)?;
+        drop(guard);
+        let Some(conversation) = data
+            .conversations
+            .iter()
+            .find(|c| c.id == conversation_id && !c.title_settled)
+        else {
+            return Ok(None);
+        };
+        let Some(prepared) = conversation_title_prompt(conversation, &app_config.ui_language)
+        else {
+            return Ok(None);
+        };
+        let api_id = app_config
+            .title_api_config_id
+            .clone()
+            .unwrap_or_else(|| conversation.api_config_id.clone());
+        let title_api = resolve_selected_api_config(&app_config, Some(&api_id))
+            .ok_or_else(|| "No API config available".to_string())?;
+        let resolved_api = resolve_api_config(&app_config, Some(&title_api.id))?;
+        (title_api.model.trim().to_string(), resolved_api, prepared)
+    };
+
+    let reply = call_with_api_key_rotation(&resolved_api, |api_config| {
+        let prepared = prepared.clone();
+        let model_name = &model_name;
+        async move { call_model_openai_rig_style(&api_config, model_name, prepared).await }
+    })
+    .await?;
+    let title = clean_generated_title(&reply.assistant_text)
+        .ok_or_else(|| "Generated title is empty".to_string())?;
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    // 生成期间会话可能已被归档或被用户重命名。
+    let conversation = data
+        .conversations
+        .iter_mut()
+        .chain(
+            data.archived_conversations
+                .iter_mut()
+                .map(|a| &mut a.source_conversation),
+        )
+        .find(|c| c.id == conversation_id && !c.title_settled);
+    let Some(conversation) = conversation else {
+        return Ok(None);
+    };
+    conversation.title = title.clone();
+    conversation.title_settled = true;
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    eprintln!(
+        "[TITLE] generated. conversation_id={}, title={}",
+        conversation_id, title
+    );
+    Ok(Some(title))
+}
+
 async fn fetch_models_openai(input: &RefreshModelsInput) -> Result<Vec<String>, String> {
     let base = input.base_url.trim().trim_end_matches('/');
     let url = format!("{base}/models");
@@ -885,21 +1511,23 @@ fn candidate_stt_urls(base_url: &str) -> Vec<String> {
     urls
 }
 
+/// `api_key` 为已解析的明文，由调用方从 key 池中选取。
 async fn call_openai_stt_transcribe(
     api_config: &ApiConfig,
+    api_key: &str,
     mime: &str,
     audio_raw: Vec<u8>,
-) -> Result<String, String> {
+) -> Result<String, ApiCallError> {
     let model = api_config.model.trim();
     if model.is_empty() {
-        return Err("STT model is empty.".to_string());
+        return Err("STT model is empty.".to_string().into());
     }
-    if api_config.api_key.trim().is_empty() {
-        return Err("STT API key is empty.".to_string());
+    if api_key.trim().is_empty() {
+        return Err("STT API key is empty.".to_string().into());
     }
     let urls = candidate_stt_urls(&api_config.base_url);
     if urls.is_empty() {
-        return Err("STT base URL is empty.".to_string());
+        return Err("STT base URL is empty.".to_string().into());
     }
 
     let client = reqwest::Client::builder()
@@ -908,6 +1536,7 @@ async fn call_openai_stt_transcribe(
         .map_err(|err| format!("Build STT HTTP client failed: {err}"))?;
 
     let mut errors = Vec::new();
+    let mut status = None;
     for url in urls {
         let file_part = reqwest::multipart::Part::bytes(audio_raw.clone())
             .file_name("speech.webm")
@@ -922,7 +1551,7 @@ async fn call_openai_stt_transcribe(
             .text("model", model.to_string());
         let resp = client
             .post(&url)
-            .bearer_auth(api_config.api_key.trim())
+            .bearer_auth(api_key)
             .multipart(form)
             .send()
             .await;
@@ -931,12 +1560,15 @@ async fn call_openai_stt_transcribe(
             continue;
         };
         if !resp.status().is_success() {
-            let status = resp.status();
+            let code = resp.status();
             let raw = resp.text().await.unwrap_or_default();
             errors.push(format!(
-                "{url} -> {status}: {}",
+                "{url} -> {code}: {}",
                 raw.chars().take(220).collect::<String>()
             ));
+            if matches!(code.as_u16(), 429 | 401) || status.is_none() {
+                status = Some(code.as_u16());
+            }
             continue;
         }
         let body = resp
@@ -952,12 +1584,16 @@ async fn call_openai_stt_transcribe(
         return Err(format!(
             "STT response does not contain text field: {}",
             body.to_string().chars().take(220).collect::<String>()
-        ));
+        )
+        .into());
     }
 
-    Err(format!(
-        "STT request failed for all candidate URLs: {}",
-        errors.join(" || ")
+    Err(ApiCallError::new(
+        status,
+        format!(
+            "STT request failed for all candidate URLs: {}",
+            errors.join(" || ")
+        ),
     ))
 }
 
@@ -1000,7 +1636,14 @@ async fn stt_transcribe(
     let audio_raw = B64
         .decode(input.bytes_base64.trim())
         .map_err(|err| format!("Decode audio base64 failed: {err}"))?;
-    let text = call_openai_stt_transcribe(&api, &input.mime, audio_raw).await?;
+    let resolved_api = resolve_api_config(&app_config, Some(api.id.as_str()))?;
+    let text = call_with_api_key_rotation(&resolved_api, |api_config| {
+        let api = &api;
+        let mime = input.mime.as_str();
+        let audio_raw = audio_raw.clone();
+        async move { call_openai_stt_transcribe(api, &api_config.api_key, mime, audio_raw).await }
+    })
+    .await?;
     Ok(SttTranscribeOutput { text })
 }
 
@@ -1118,7 +1761,33 @@ async fn fetch_models_anthropic(input: &RefreshModelsInput) -> Result<Vec<String
 }
 
 #[tauri::command]
-async fn refresh_models(input: RefreshModelsInput) -> Result<Vec<String>, String> {
+async fn refresh_models(
+    input: RefreshModelsInput,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let mut input = input;
+    if is_masked_secret(&input.api_key) {
+        let api_config_id = input
+            .api_config_id
+            .as_deref()
+            .ok_or_else(|| "apiConfigId is required for a saved API key.".to_string())?;
+        let app_config = {
+            let guard = state
+                .state_lock
+                .lock()
+                .map_err(|_| "Failed to lock state mutex".to_string())?;
+            let cfg = read_config(&state.config_path)?;
+            drop(guard);
+            cfg
+        };
+        input.api_key = app_config
+            .api_configs
+            .iter()
+            .find(|a| a.id == api_config_id)
+            .and_then(|a| unmask_secret_ref(a, &input.api_key, true))
+            .unwrap_or_default();
+    }
+    input.api_key = reveal_api_key(&input.api_key)?;
     if input.api_key.trim().is_empty() {
         return Err("API key is empty.".to_string());
     }
@@ -1206,7 +1875,7 @@ fn get_image_text_cache_stats(state: State<'_, AppState>) -> Result<ImageTextCac
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let data = read_app_data(&state.data_path)?;
+    let data = read_app_data_headers(&state.data_path)?;
     drop(guard);
 
     let entries = data.image_text_cache.len();
@@ -1234,7 +1903,7 @@ fn clear_image_text_cache(state: State<'_, AppState>) -> Result<ImageTextCacheSt
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     data.image_text_cache.clear();
     write_app_data(&state.data_path, &data)?;
     drop(guard);
@@ -102,6 +102,7 @@ fn load_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
     let mut result = read_config(&state.config_path)?;
     normalize_app_config(&mut result);
     drop(guard);
+    mask_config_secrets(&mut result);
     Ok(result)
 }
 
@@ -122,20 +123,343 @@ fn save_config(
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
 
+    let previous = read_config(&state.config_path)?;
+    seal_config_secrets(&mut config, Some(&previous))?;
     write_config(&state.config_path, &config)?;
     register_hotkey_from_config(&app, &config)?;
     drop(guard);
+    mask_config_secrets(&mut config);
     Ok(config)
 }
 
 #[tauri::command]
-fn load_agents(state: State<'_, AppState>) -> Result<Vec<AgentProfile>, String> {
+fn get_secret_store_status(state: State<'_, AppState>) -> Result<SecretStoreStatus, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let status = secret_store_status()?;
+    drop(guard);
+    Ok(status)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiKeyHealthInput {
+    /// 为空时返回全部 API 配置。
+    #[serde(default)]
+    api_config_id: Option<String>,
+}
+
+#[tauri::command]
+fn get_api_key_health(
+    input: ApiKeyHealthInput,
+    state: State<'_, AppState>,
+) -> Result<Vec<ApiKeyHealth>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let config = read_config(&state.config_path)?;
+    drop(guard);
+    let api_config_id = input
+        .api_config_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    if let Some(id) = api_config_id {
+        if !config.api_configs.iter().any(|a| a.id == id) {
+            return Err(format!("API config '{id}' not found."));
+        }
+    }
+    Ok(config
+        .api_configs
+        .iter()
+        .filter(|a| api_config_id.is_none_or(|id| a.id == id))
+        .flat_map(api_key_health_report)
+        .collect())
+}
+
+#[tauri::command]
+fn unlock_secret_store(passphrase: String, state: State<'_, AppState>) -> Result<(), String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    unlock_vault_with_passphrase(&passphrase)?;
+    // 解锁后顺带迁移口令模式下尚未加密的明文 key。
+    let _ = read_config(&state.config_path)?;
+    drop(guard);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetSecretStorePassphraseInput {
+    /// 为空时改回本机密钥文件模式。
+    passphrase: Option<String>,
+}
+
+#[tauri::command]
+fn set_secret_store_passphrase(
+    input: SetSecretStorePassphraseInput,
+    state: State<'_, AppState>,
+) -> Result<SecretStoreStatus, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    change_vault_passphrase(input.passphrase.as_deref())?;
+    let status = secret_store_status()?;
+    drop(guard);
+    Ok(status)
+}
+
+#[tauri::command]
+fn list_corrupt_data_files(state: State<'_, AppState>) -> Result<Vec<CorruptDataFile>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let dir = state
+        .data_path
+        .parent()
+        .ok_or_else(|| "Data path has no parent directory".to_string())?;
+    let files = list_corrupt_data_files_in(dir)?;
+    drop(guard);
+    Ok(files)
+}
+
+#[tauri::command]
+fn list_backups(state: State<'_, AppState>) -> Result<Vec<BackupEntry>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let backups = list_backups_in(&state.config_path, &state.data_path)?;
+    drop(guard);
+    Ok(backups)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreBackupInput {
+    backup_id: String,
+    /// config | appData；为空时恢复快照中的全部文件。
+    #[serde(default)]
+    kinds: Vec<String>,
+}
+
+#[tauri::command]
+fn restore_backup(
+    input: RestoreBackupInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let restored = restore_backup_files(
+        &state.config_path,
+        &state.data_path,
+        &input.backup_id,
+        &input.kinds,
+    )?;
+    if restored.iter().any(|k| k == "config") {
+        let config = read_config(&state.config_path)?;
+        register_hotkey_from_config(&app, &config)?;
+    }
+    drop(guard);
+    Ok(restored)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportBackupBundleInput {
+    /// 为空时弹出保存对话框。
+    #[serde(default)]
+    path: Option<String>,
+    /// 为 true 时配置中的 API key 以明文写入备份包。
+    #[serde(default)]
+    include_secrets: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportBackupBundleResult {
+    path: String,
+    manifest: BackupBundleManifest,
+}
+
+#[tauri::command]
+fn export_backup_bundle(
+    input: ExportBackupBundleInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ExportBackupBundleResult, String> {
     let guard = state
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let config = read_config(&state.config_path)?;
+    let data = read_app_data(&state.data_path)?;
+    drop(guard);
+
+    let (bytes, manifest) = build_backup_bundle(
+        &config,
+        &data,
+        &blob_storage_dir(&state.data_path),
+        input.include_secrets,
+        &reveal_api_key,
+    )?;
+    let file_path = match input.path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .dialog()
+            .file()
+            .add_filter("Zip", &["zip"])
+            .blocking_save_file()
+            .and_then(|fp| fp.as_path().map(ToOwned::to_owned))
+            .ok_or_else(|| "Export cancelled".to_string())?,
+    };
+    write_file_atomically(&file_path, &bytes)?;
+    Ok(ExportBackupBundleResult {
+        path: file_path.to_string_lossy().to_string(),
+        manifest,
+    })
+}
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportBackupBundleInput {
+    /// 为空时弹出打开对话框。
+    #[serde(default)]
+    path: Option<String>,
+    /// merge | replace
+    mode: String,
+}
+
+#[tauri::command]
+fn import_backup_bundle(
+    input: ImportBackupBundleInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ImportBundleResult, String> {
+    let replace = match input.mode.trim() {
+        "merge" => false,
+        "replace" => true,
+        other => return Err(format!("Unsupported import mode '{other}'. Use 'merge' or 'replace'.")),
+    };
+    let file_path = match input.path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .dialog()
+            .file()
+            .add_filter("Zip", &["zip"])
+            .blocking_pick_file()
+            .and_then(|fp| fp.as_path().map(ToOwned::to_owned))
+            .ok_or_else(|| "Import cancelled".to_string())?,
+    };
+    let raw = fs::read(&file_path).map_err(|err| format!("Read backup bundle failed: {err}"))?;
+    let bundle = read_backup_bundle(&raw)?;
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let previous_config = read_config(&state.config_path)?;
+    let mut config = previous_config.clone();
     let mut data = read_app_data(&state.data_path)?;
+    let avatar_dir = avatar_storage_dir(&state)?;
+    snapshot_current_files(&state.config_path, &state.data_path, PRE_IMPORT_BACKUP_SUFFIX)?;
+    let result = if replace {
+        replace_with_backup_bundle(&mut config, &mut data, bundle, &avatar_dir)?
+    } else {
+        merge_backup_bundle(&mut config, &mut data, bundle, &avatar_dir)?
+    };
+    if result.config_imported {
+        normalize_app_config(&mut config);
+        seal_config_secrets(&mut config, Some(&previous_config))?;
+        write_config(&state.config_path, &config)?;
+        register_hotkey_from_config(&app, &config)?;
+    }
+    write_app_data(&state.data_path, &data)?;
+    invalidate_memory_matcher_cache();
+    drop(guard);
+    Ok(result)
+}
+
+#[tauri::command]
+fn get_data_directory() -> Result<DataDirLocation, String> {
+    app_data_dir().cloned()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MoveDataDirectoryInput {
+    target_dir: String,
+}
+
+/// 迁移成功后应用会自动重启，以便从新目录重新加载全部数据。
+#[tauri::command]
+fn move_data_directory(
+    input: MoveDataDirectoryInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<DataDirLocation, String> {
+    let location = app_data_dir()?;
+    if location.is_fixed() {
+        return Err(format!(
+            "Data directory is set by {} and cannot be moved from the app.",
+            location.source
+        ));
+    }
+    let target = absolute_path(&input.target_dir)
+        .ok_or_else(|| "Target data directory is empty.".to_string())?;
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let inflight = state
+        .inflight_chat_abort_handles
+        .lock()
+        .map_err(|_| "Failed to lock inflight chat abort handles".to_string())?
+        .len();
+    if inflight > 0 {
+        return Err("Stop running chats before moving the data directory.".to_string());
+    }
+    move_data_dir_contents(location, &target)?;
+    if let Err(err) = rebase_avatar_paths(&target.join("app_data.db"), &location.dir, &target) {
+        eprintln!("[STORAGE] rebase avatar paths failed: {err}");
+    }
+    drop(guard);
+    eprintln!(
+        "[STORAGE] data directory moved: {} -> {}, restarting",
+        location.dir.display(),
+        target.display()
+    );
+    app.request_restart();
+    Ok(DataDirLocation {
+        source: if target == location.default_dir {
+            "default".to_string()
+        } else {
+            "redirect".to_string()
+        },
+        dir: target,
+        default_dir: location.default_dir.clone(),
+    })
+}
+
+#[tauri::command]
+fn load_agents(state: State<'_, AppState>) -> Result<Vec<AgentProfile>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+
+    let mut data = read_app_data_headers(&state.data_path)?;
     let changed = ensure_default_agent(&mut data);
     if changed {
         write_app_data(&state.data_path, &data)?;
@@ -158,13 +482,20 @@ fn save_agents(
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
 
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let existing_user_persona = data
         .agents
         .iter()
         .find(|a| a.id == USER_PERSONA_ID)
         .cloned();
     data.agents = input.agents;
+    for agent in &mut data.agents {
+        if let Some(policy) = agent.archive_policy.as_mut() {
+            normalize_archive_policy(policy);
+        }
+        normalize_agent_instruction_override(&mut agent.highest_instruction);
+        normalize_agent_model_overrides(agent);
+    }
     if !data.agents.iter().any(|a| a.id == USER_PERSONA_ID) {
         if let Some(user_persona) = existing_user_persona {
             data.agents.push(user_persona);
@@ -183,7 +514,7 @@ fn load_chat_settings(state: State<'_, AppState>) -> Result<ChatSettings, String
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
 
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let changed = ensure_default_agent(&mut data);
     if changed {
         write_app_data(&state.data_path, &data)?;
@@ -207,7 +538,7 @@ fn save_chat_settings(
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
 
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     ensure_default_agent(&mut data);
     if !data
         .agents
@@ -218,7 +549,8 @@ fn save_chat_settings(
     }
     data.selected_agent_id = input.selected_agent_id.clone();
     data.user_alias = user_persona_name(&data);
-    data.response_style_id = normalize_response_style_id(&input.response_style_id);
+    data.response_style_id =
+        normalize_response_style_id(&input.response_style_id, &data.response_styles);
     write_app_data(&state.data_path, &data)?;
     drop(guard);
 
@@ -229,6 +561,43 @@ fn save_chat_settings(
     })
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveResponseStylesInput {
+    /// 完整的自定义风格列表（顺序即显示顺序）；其中的内置风格会被忽略。
+    styles: Vec<ResponseStylePreset>,
+}
+
+#[tauri::command]
+fn load_response_styles(state: State<'_, AppState>) -> Result<Vec<ResponseStylePreset>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let data = read_app_data_headers(&state.data_path)?;
+    drop(guard);
+    Ok(all_response_styles(&data.response_styles))
+}
+
+#[tauri::command]
+fn save_response_styles(
+    input: SaveResponseStylesInput,
+    state: State<'_, AppState>,
+) -> Result<Vec<ResponseStylePreset>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+
+    let mut data = read_app_data_headers(&state.data_path)?;
+    data.response_styles = normalize_custom_response_styles(input.styles);
+    // 被删除的风格若正被全局或智能体使用，回退到默认风格。
+    ensure_default_agent(&mut data);
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    Ok(all_response_styles(&data.response_styles))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct SaveAgentAvatarInput {
@@ -326,7 +695,7 @@ fn save_agent_avatar(
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let _ = ensure_default_agent(&mut data);
 
     let idx = data
@@ -372,7 +741,7 @@ fn clear_agent_avatar(
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let _ = ensure_default_agent(&mut data);
     let idx = data
         .agents
@@ -411,6 +780,151 @@ fn read_avatar_data_url(
     })
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportAgentInput {
+    agent_id: String,
+    /// json | card（角色卡 PNG）
+    format: String,
+    /// 为空时弹出保存对话框。
+    #[serde(default)]
+    path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportAgentResult {
+    path: String,
+}
+
+#[tauri::command]
+fn export_agent(
+    input: ExportAgentInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ExportAgentResult, String> {
+    let as_card = match input.format.trim() {
+        "json" => false,
+        "card" => true,
+        other => return Err(format!("Unsupported agent export format '{other}'. Use 'json' or 'card'.")),
+    };
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let data = read_app_data_headers(&state.data_path)?;
+    drop(guard);
+    let agent = data
+        .agents
+        .iter()
+        .find(|a| a.id == input.agent_id && !a.is_built_in_user)
+        .cloned()
+        .ok_or_else(|| "Agent not found".to_string())?;
+    let avatar = agent.avatar_path.as_deref().and_then(|path| match fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            eprintln!("[AGENT-EXPORT] skip unreadable avatar: path={path}, err={err}");
+            None
+        }
+    });
+    let (bytes, extension) = if as_card {
+        (build_agent_card_png(&agent, avatar.as_deref())?, "png")
+    } else {
+        (build_agent_export_json(&agent, avatar.as_deref())?, "json")
+    };
+
+    let file_path = match input.path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .dialog()
+            .file()
+            .set_file_name(agent_export_file_name(&agent.name, extension))
+            .add_filter(extension.to_uppercase(), &[extension])
+            .blocking_save_file()
+            .and_then(|fp| fp.as_path().map(ToOwned::to_owned))
+            .ok_or_else(|| "Export cancelled".to_string())?,
+    };
+    write_file_atomically(&file_path, &bytes)?;
+    Ok(ExportAgentResult {
+        path: file_path.to_string_lossy().to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportAgentInput {
+    /// 为空时弹出打开对话框；支持本应用的 JSON 与角色卡 PNG/JSON。
+    #[serde(default)]
+    path: Option<String>,
+}
+
+/// 导入的智能体总是新建，返回保存后的档案供前端加入列表。
+#[tauri::command]
+fn import_agent(
+    input: ImportAgentInput,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<AgentProfile, String> {
+    let file_path = match input.path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => app
+            .dialog()
+            .file()
+            .add_filter("Agent", &["json", "png"])
+            .blocking_pick_file()
+            .and_then(|fp| fp.as_path().map(ToOwned::to_owned))
+            .ok_or_else(|| "Import cancelled".to_string())?,
+    };
+    let raw = fs::read(&file_path).map_err(|err| format!("Read agent file failed: {err}"))?;
+    let parsed = parse_agent_import(&raw)?;
+
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let _ = ensure_default_agent(&mut data);
+
+    let mut agent = parsed.agent;
+    agent.id = format!("persona-{}", Uuid::new_v4());
+    agent.is_built_in_user = false;
+    agent.avatar_path = None;
+    agent.avatar_updated_at = None;
+    if let Some(policy) = agent.archive_policy.as_mut() {
+        normalize_archive_policy(policy);
+    }
+    normalize_agent_instruction_override(&mut agent.highest_instruction);
+    normalize_agent_model_overrides(&mut agent);
+    // 来自其他设备的 API 与自定义风格引用在本机不存在时回退到全局设置。
+    if agent_default_api_config(&app_config, &agent).is_none() {
+        agent.api_config_id = None;
+    }
+    if let Some(style_id) = agent.response_style_id.as_deref() {
+        if find_response_style(style_id, &data.response_styles).is_none() {
+            agent.response_style_id = None;
+        }
+    }
+    if let Some(raw_avatar) = parsed.avatar {
+        match normalize_avatar_bytes_to_webp(&raw_avatar) {
+            Ok(webp) => {
+                let dir = avatar_storage_dir(&state)?;
+                fs::create_dir_all(&dir)
+                    .map_err(|err| format!("Create avatar directory failed: {err}"))?;
+                let path = dir.join(format!("agent-{}.webp", sanitize_avatar_key(&agent.id)));
+                fs::write(&path, webp).map_err(|err| format!("Write avatar file failed: {err}"))?;
+                agent.avatar_path = Some(path.to_string_lossy().to_string());
+                agent.avatar_updated_at = Some(now_iso());
+            }
+            Err(err) => eprintln!("[AGENT-IMPORT] skip avatar: {err}"),
+        }
+    }
+    data.agents.push(agent.clone());
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    Ok(agent)
+}
+
 #[tauri::command]
 fn sync_tray_icon(
     input: SyncTrayIconInput,
@@ -421,7 +935,7 @@ fn sync_tray_icon(
         .state_lock
         .lock()
         .map_err(|_| "Failed to lock state mutex".to_string())?;
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let changed = ensure_default_agent(&mut data);
     if changed {
         write_app_data(&state.data_path, &data)?;
@@ -454,6 +968,7 @@ fn save_conversation_api_settings(
     let mut config = read_config(&state.config_path)?;
     config.chat_api_config_id = input.chat_api_config_id.clone();
     config.vision_api_config_id = input.vision_api_config_id.clone();
+    config.title_api_config_id = input.title_api_config_id.clone();
     config.stt_api_config_id = input.stt_api_config_id.clone();
     config.stt_auto_send = input.stt_auto_send;
     normalize_app_config(&mut config);
@@ -463,6 +978,7 @@ fn save_conversation_api_settings(
     Ok(ConversationApiSettings {
         chat_api_config_id: config.chat_api_config_id,
         vision_api_config_id: config.vision_api_config_id,
+        title_api_config_id: config.title_api_config_id,
         stt_api_config_id: config.stt_api_config_id,
         stt_auto_send: config.stt_auto_send,
     })
@@ -482,7 +998,7 @@ fn get_chat_snapshot(
     let api_config = resolve_selected_api_config(&app_config, input.api_config_id.as_deref())
         .ok_or_else(|| "No API config available".to_string())?;
 
-    let mut data = read_app_data(&state.data_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
     let defaults_changed = ensure_default_agent(&mut data);
     let requested_agent_id = input.agent_id.trim();
     let effective_agent_id = if data
@@ -507,20 +1023,28 @@ fn get_chat_snapshot(
 
     let before_len = data.conversations.len();
     let idx = ensure_active_conversation_index(&mut data, &api_config.id, &effective_agent_id);
+    load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
     let conversation = &data.conversations[idx];
+    let archive_policy = effective_archive_policy(
+        &app_config,
+        data.agents.iter().find(|a| a.id == effective_agent_id),
+    );
 
-    let latest_user = conversation
+    let mut latest_user = conversation
         .messages
         .iter()
         .rev()
         .find(|m| m.role == "user")
         .cloned();
-    let latest_assistant = conversation
+    let mut latest_assistant = conversation
         .messages
         .iter()
         .rev()
         .find(|m| m.role == "assistant")
         .cloned();
+    let blob_dir = blob_storage_dir(&state.data_path);
+    hydrate_message_blobs(&blob_dir, latest_user.as_mut_slice());
+    hydrate_message_blobs(&blob_dir, latest_assistant.as_mut_slice());
 
     if defaults_changed || data.conversations.len() != before_len {
         write_app_data(&state.data_path, &data)?;
@@ -532,6 +1056,7 @@ fn get_chat_snapshot(
         latest_user,
         latest_assistant,
         active_message_count: conversation.messages.len(),
+        archive_policy,
     })
 }
 
@@ -549,9 +1074,22 @@ fn get_active_conversation_messages(
     let api_config = resolve_selected_api_config(&app_config, input.api_config_id.as_deref())
         .ok_or_else(|| "No API config available".to_string())?;
 
-    let mut data = read_app_data(&state.data_path)?;
-    let defaults_changed = ensure_default_agent(&mut data);
     let requested_agent_id = input.agent_id.trim();
+    let blob_dir = blob_storage_dir(&state.data_path);
+    // 常见路径：请求的智能体已有活动会话，直接按索引读取这一条会话。
+    if !requested_agent_id.is_empty() {
+        if let Some(conversation) =
+            read_latest_active_conversation(&state.data_path, &api_config.id, requested_agent_id)?
+        {
+            drop(guard);
+            let mut messages = conversation.messages;
+            hydrate_message_blobs(&blob_dir, &mut messages);
+            return Ok(messages);
+        }
+    }
+
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let defaults_changed = ensure_default_agent(&mut data);
     let effective_agent_id = if data
         .agents
         .iter()
@@ -574,7 +1112,9 @@ fn get_active_conversation_messages(
 
     let before_len = data.conversations.len();
     let idx = ensure_active_conversation_index(&mut data, &api_config.id, &effective_agent_id);
-    let messages = data.conversations[idx].messages.clone();
+    load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
+    let mut messages = data.conversations[idx].messages.clone();
+    hydrate_message_blobs(&blob_dir, &mut messages);
 
     if defaults_changed || data.conversations.len() != before_len {
         write_app_data(&state.data_path, &data)?;
@@ -583,3 +1123,213 @@ fn get_active_conversation_messages(
     Ok(messages)
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationListItem {
+    conversation_id: String,
+    title: String,
+    api_config_id: String,
+    agent_id: String,
+    created_at: String,
+    updated_at: String,
+    message_count: usize,
+    last_context_usage_ratio: f64,
+    is_current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compaction: Option<ConversationCompaction>,
+}
+
+fn conversation_list_item(conversation: &Conversation, is_current: bool) -> ConversationListItem {
+    ConversationListItem {
+        conversation_id: conversation.id.clone(),
+        title: conversation.title.clone(),
+        api_config_id: conversation.api_config_id.clone(),
+        agent_id: conversation.agent_id.clone(),
+        created_at: conversation.created_at.clone(),
+        updated_at: conversation.updated_at.clone(),
+        message_count: conversation.messages.len(),
+        last_context_usage_ratio: conversation.last_context_usage_ratio,
+        is_current,
+        compaction: conversation.compaction.clone(),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateConversationInput {
+    session: SessionSelector,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationIdInput {
+    conversation_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RenameConversationInput {
+    conversation_id: String,
+    title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeleteConversationResult {
+    deleted_id: String,
+    current_conversation_id: Option<String>,
+}
+
+fn active_conversation_position(data: &AppData, conversation_id: &str) -> Result<usize, String> {
+    let conversation_id = conversation_id.trim();
+    if conversation_id.is_empty() {
+        return Err("conversationId is required".to_string());
+    }
+    data.conversations
+        .iter()
+        .position(|c| c.id == conversation_id && c.status == "active")
+        .ok_or_else(|| format!("Conversation '{conversation_id}' not found."))
+}
+
+#[tauri::command]
+fn list_conversations(
+    input: SessionSelector,
+    state: State<'_, AppState>,
+) -> Result<Vec<ConversationListItem>, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let api_config = resolve_selected_api_config(&app_config, input.api_config_id.as_deref())
+        .ok_or_else(|| "No API config available".to_string())?;
+    let data = read_app_data_headers(&state.data_path)?;
+    let message_counts = read_conversation_message_counts(&state.data_path)?;
+    drop(guard);
+
+    let agent_id = resolve_session_agent_id(&data, &input.agent_id)?;
+    let current = latest_active_conversation_index(&data, &api_config.id, &agent_id);
+    let mut items = data
+        .conversations
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| {
+            c.status == "active" && c.agent_id == agent_id && c.api_config_id == api_config.id
+        })
+        .map(|(idx, c)| ConversationListItem {
+            message_count: message_counts.get(&c.id).copied().unwrap_or_default(),
+            ..conversation_list_item(c, Some(idx) == current)
+        })
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(items)
+}
+
+#[tauri::command]
+fn create_conversation(
+    input: CreateConversationInput,
+    state: State<'_, AppState>,
+) -> Result<ConversationListItem, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let app_config = read_config(&state.config_path)?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    ensure_default_agent(&mut data);
+    let agent_id = resolve_session_agent_id(&data, &input.session.agent_id)?;
+    let api_config = resolve_session_api_config(
+        &app_config,
+        data.agents.iter().find(|a| a.id == agent_id),
+        input.session.api_config_id.as_deref(),
+    )?;
+    let idx = push_active_conversation(&mut data, &api_config.id, &agent_id, input.title.as_deref());
+    let item = conversation_list_item(&data.conversations[idx], true);
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    Ok(item)
+}
+
+#[tauri::command]
+fn switch_conversation(
+    input: ConversationIdInput,
+    state: State<'_, AppState>,
+) -> Result<ConversationListItem, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let idx = active_conversation_position(&data, &input.conversation_id)?;
+    let idx = select_conversation_index(&mut data, idx);
+    load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
+    let item = conversation_list_item(&data.conversations[idx], true);
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    Ok(item)
+}
+
+#[tauri::command]
+fn rename_conversation(
+    input: RenameConversationInput,
+    state: State<'_, AppState>,
+) -> Result<ConversationListItem, String> {
+    let title = input.title.trim();
+    if title.is_empty() {
+        return Err("Conversation title cannot be empty.".to_string());
+    }
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut data = read_app_data_headers(&state.data_path)?;
+    let idx = active_conversation_position(&data, &input.conversation_id)?;
+    load_conversation_messages(&state.data_path, &mut data.conversations[idx])?;
+    data.conversations[idx].title = title.to_string();
+    data.conversations[idx].title_settled = true;
+    let is_current = latest_active_conversation_index(
+        &data,
+        &data.conversations[idx].api_config_id,
+        &data.conversations[idx].agent_id,
+    ) == Some(idx);
+    let item = conversation_list_item(&data.conversations[idx], is_current);
+    write_app_data(&state.data_path, &data)?;
+    drop(guard);
+    Ok(item)
+}
+
+#[tauri::command]
+fn delete_conversation(
+    input: ConversationIdInput,
+    state: State<'_, AppState>,
+) -> Result<DeleteConversationResult, String> {
+    let guard = state
+        .state_lock
+        .lock()
+        .map_err(|_| "Failed to lock state mutex".to_string())?;
+    let mut data = read_app_data(&state.data_path)?;
+    let idx = active_conversation_position(&data, &input.conversation_id)?;
+    if chat_inflight(&state, &data.conversations[idx].id)? {
+        return Err(
+            "A reply is still being generated. Stop it before deleting the conversation."
+                .to_string(),
+        );
+    }
+    let removed = data.conversations.remove(idx);
+    let current_conversation_id =
+        latest_active_conversation_index(&data, &removed.api_config_id, &removed.agent_id)
+            .map(|idx| data.conversations[idx].id.clone());
+    write_app_data(&state.data_path, &data)?;
+    match gc_unreferenced_blobs(&state.data_path, &data) {
+        Ok(removed) if removed > 0 => eprintln!("[BLOB] removed {removed} unreferenced blobs"),
+        Ok(_) => {}
+        Err(err) => eprintln!("[BLOB] gc failed: {err}"),
+    }
+    drop(guard);
+    Ok(DeleteConversationResult {
+        deleted_id: removed.id,
+        current_conversation_id,
+    })
+}
@@ -36,6 +36,7 @@
             base_url: server.base_url(),
             api_key: "test-key".to_string(),
             request_format: RequestFormat::OpenAI,
+            api_config_id: None,
         };
 
         let rt = test_runtime();
@@ -68,6 +69,7 @@
             base_url: server.base_url(),
             api_key: "test-key".to_string(),
             request_format: RequestFormat::OpenAI,
+            api_config_id: None,
         };
 
         let rt = test_runtime();
@@ -178,3 +180,136 @@
             "{\"query\":\"rust\"}".to_string()
         );
     }
+
+    #[test]
+    fn openai_pdf_turn_should_keep_tools_enabled() {
+        let server = MockServer::start();
+        let sse_body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"读完了\"}}]}\n",
+            "\n",
+            "data: [DONE]\n",
+            "\n"
+        );
+        let sse_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/chat/completions")
+                .body_contains("\"tools\"")
+                .body_contains("data:application/pdf;base64,QUJD");
+            then.status(200)
+                .header("content-type", "text/event-stream")
+                .body(sse_body);
+        });
+
+        let selected_api = ApiConfig {
+            base_url: server.base_url(),
+            api_key: "test-key".to_string(),
+            ..ApiConfig::default()
+        };
+        let resolved = ResolvedApiConfig {
+            api_config_id: selected_api.id.clone(),
+            request_format: RequestFormat::OpenAI,
+            base_url: server.base_url(),
+            api_key: "test-key".to_string(),
+            api_key_refs: Vec::new(),
+            model: "gpt-4o-mini".to_string(),
+            temperature: 1.0,
+            fixed_test_prompt: String::new(),
+        };
+        let prepared = PreparedPrompt {
+            preamble: "sys".to_string(),
+            history_messages: Vec::new(),
+            latest_user_text: "看看这个文件".to_string(),
+            latest_user_time_text: String::new(),
+            latest_user_system_text: String::new(),
+            latest_images: vec![("application/pdf".to_string(), "QUJD".to_string())],
+            latest_audios: Vec::new(),
+        };
+        let on_delta = tauri::ipc::Channel::<AssistantDeltaEvent>::new(|_| Ok(()));
+
+        let rt = test_runtime();
+        let reply = rt
+            .block_on(call_model_openai_style(
+                &resolved,
+                &selected_api,
+                "gpt-4o-mini",
+                prepared,
+                None,
+                &on_delta,
+                3,
+            ))
+            .expect("pdf turn with tools");
+
+        sse_mock.assert();
+        assert_eq!(reply.assistant_text, "读完了");
+    }
+
+    #[test]
+    fn follow_agent_session_should_target_same_conversation_for_send_regenerate_and_stop() {
+        let app_config = AppConfig {
+            chat_api_config_id: "global-api".to_string(),
+            api_configs: vec![
+                ApiConfig {
+                    id: "global-api".to_string(),
+                    ..ApiConfig::default()
+                },
+                ApiConfig {
+                    id: "agent-api".to_string(),
+                    ..ApiConfig::default()
+                },
+            ],
+            ..AppConfig::default()
+        };
+        let agent = AgentProfile {
+            api_config_id: Some("agent-api".to_string()),
+            ..default_agent()
+        };
+        let mut data = AppData {
+            agents: vec![agent.clone()],
+            ..AppData::default()
+        };
+        // 跟随智能体时前端不指定 API。
+        let session = SessionSelector {
+            api_config_id: Some(String::new()),
+            agent_id: agent.id.clone(),
+        };
+
+        // 发送
+        let send_api =
+            resolve_session_api_config(&app_config, Some(&agent), session.api_config_id.as_deref())
+                .expect("send api");
+        assert_eq!(send_api.id, "agent-api");
+        let send_idx = ensure_active_conversation_index(&mut data, &send_api.id, &agent.id);
+        let conversation_id = data.conversations[send_idx].id.clone();
+
+        // 重新生成
+        let (regen_api, regen_idx) = resolve_edit_target_conversation(
+            &app_config,
+            &data,
+            Some(&session),
+            Some(&conversation_id),
+        )
+        .expect("regenerate target");
+        assert_eq!(regen_api.id, "agent-api");
+        assert_eq!(regen_idx, send_idx);
+        let (_, current_idx) =
+            resolve_edit_target_conversation(&app_config, &data, Some(&session), None)
+                .expect("current conversation");
+        assert_eq!(current_idx, send_idx);
+
+        // 停止
+        let stop_api =
+            resolve_session_api_config(&app_config, Some(&agent), session.api_config_id.as_deref())
+                .expect("stop api");
+        assert_eq!(
+            latest_active_conversation_index(&data, &stop_api.id, &agent.id),
+            Some(send_idx)
+        );
+
+        // 会话显式指定的 API 优先于智能体默认；智能体没有默认时用全局对话 API。
+        let explicit = resolve_session_api_config(&app_config, Some(&agent), Some("global-api"))
+            .expect("explicit api");
+        assert_eq!(explicit.id, "global-api");
+        let fallback = resolve_session_api_config(&app_config, Some(&default_agent()), None)
+            .expect("fallback api");
+        assert_eq!(fallback.id, "global-api");
+    }
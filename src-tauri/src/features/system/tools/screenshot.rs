@@ -41,9 +41,10 @@ fn validate_screenshot_request(input: &ScreenshotRequest) -> DesktopToolResult<(
 }
 
 fn default_screenshot_path() -> DesktopToolResult<std::path::PathBuf> {
-    let mut dir = std::env::temp_dir();
-    dir.push("easy-call-ai");
-    dir.push("screenshots");
+    let mut dir = app_data_dir()
+        .map_err(DesktopToolError::internal_error)?
+        .dir
+        .join(SCREENSHOT_DIR_NAME);
     std::fs::create_dir_all(&dir).map_err(|err| {
         DesktopToolError::internal_error(format!("create screenshot directory failed: {err}"))
     })?;
@@ -24,6 +24,8 @@
             provider_meta: None,
             tool_call: None,
             mcp_call: None,
+            parent_id: None,
+            sibling_index: 0,
         }
     }
 
@@ -35,6 +37,7 @@
         Conversation {
             id: Uuid::new_v4().to_string(),
             title: "t".to_string(),
+            title_settled: false,
             api_config_id: "api".to_string(),
             agent_id: "agent".to_string(),
             created_at: now.clone(),
@@ -44,6 +47,10 @@
             last_context_usage_ratio: 0.0,
             status: "active".to_string(),
             messages,
+            alternate_messages: Vec::new(),
+            forked_from_archive_id: None,
+            compaction: None,
+            messages_deferred: false,
         }
     }
 
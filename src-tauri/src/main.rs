@@ -3,7 +3,7 @@
 use std::{
     fs,
     io::Cursor,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex, OnceLock},
 };
 
@@ -16,7 +16,7 @@ use rmcp::{ServiceExt, schemars};
 use rig::{
     completion::{
         message::{AudioMediaType, DocumentMediaType, ImageDetail, ImageMediaType, UserContent},
-        Message as RigMessage, Prompt, ToolDefinition,
+        CompletionError, Message as RigMessage, Prompt, PromptError, ToolDefinition,
     },
     message::{AssistantContent, ToolResultContent},
     prelude::CompletionClient,
@@ -35,7 +35,7 @@ use tauri::{
 };
 use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime, UtcOffset};
 use uuid::Uuid;
 
 
@@ -44,9 +44,19 @@ include!("features/core/domain.rs");
 
 // ==================== 配置与存储 ====================
 include!("features/config/storage_and_stt.rs");
+include!("features/config/sqlite_store.rs");
+include!("features/config/search_index.rs");
+include!("features/config/backup.rs");
+include!("features/config/secret_store.rs");
+include!("features/config/api_key_pool.rs");
+include!("features/config/data_dir.rs");
+include!("features/config/bundle.rs");
+include!("features/config/agent_card.rs");
 
 // ==================== 对话核心 ====================
 include!("features/chat/conversation.rs");
+include!("features/chat/prompt_template.rs");
+include!("features/chat/archive_import.rs");
 include!("features/chat/model_runtime.rs");
 
 // ==================== 系统窗口与命令 ====================
@@ -99,16 +109,22 @@ fn main() {
                 .state_lock
                 .lock()
                 .map_err(|_| "Failed to lock state mutex".to_string())?;
-            let mut data = read_app_data(&app_state.data_path).unwrap_or_default();
-            let changed = ensure_default_agent(&mut data);
-            if changed {
-                let _ = write_app_data(&app_state.data_path, &data);
-            }
-            let avatar_path = data
-                .agents
-                .iter()
-                .find(|a| a.id == data.selected_agent_id)
-                .and_then(|a| a.avatar_path.clone());
+            // 读取失败时不回写默认数据，避免覆盖用户的历史记录。
+            let avatar_path = match read_app_data(&app_state.data_path) {
+                Ok(mut data) => {
+                    if ensure_default_agent(&mut data) {
+                        let _ = write_app_data(&app_state.data_path, &data);
+                    }
+                    data.agents
+                        .iter()
+                        .find(|a| a.id == data.selected_agent_id)
+                        .and_then(|a| a.avatar_path.clone())
+                }
+                Err(err) => {
+                    eprintln!("[BOOT] read_app_data failed: {err}");
+                    None
+                }
+            };
             drop(guard);
             let _ = sync_tray_icon_from_avatar_path(&app_handle, avatar_path.as_deref());
             hide_on_close(&app_handle);
@@ -127,17 +143,37 @@ fn main() {
             check_github_update,
             load_config,
             save_config,
+            list_corrupt_data_files,
+            get_secret_store_status,
+            get_api_key_health,
+            unlock_secret_store,
+            set_secret_store_passphrase,
+            list_backups,
+            restore_backup,
+            export_backup_bundle,
+            import_backup_bundle,
+            get_data_directory,
+            move_data_directory,
             load_agents,
             save_agents,
             load_chat_settings,
             save_chat_settings,
+            load_response_styles,
+            save_response_styles,
             save_agent_avatar,
             clear_agent_avatar,
             read_avatar_data_url,
+            export_agent,
+            import_agent,
             sync_tray_icon,
             save_conversation_api_settings,
             get_chat_snapshot,
             get_active_conversation_messages,
+            list_conversations,
+            create_conversation,
+            switch_conversation,
+            rename_conversation,
+            delete_conversation,
             get_prompt_preview,
             get_system_prompt_preview,
             list_archives,
@@ -148,13 +184,21 @@ fn main() {
             import_memories,
             get_archive_messages,
             delete_archive,
+            search_history,
             export_archive_to_file,
+            import_archives,
             open_external_url,
             send_chat_message,
             stop_chat_message,
+            edit_chat_message,
+            regenerate_chat_reply,
+            delete_chat_message,
+            get_conversation_branches,
+            switch_message_branch,
             read_local_binary_file,
             stt_transcribe,
             force_archive_current,
+            restore_archive,
             refresh_models,
             check_tools_status,
             get_image_text_cache_stats,
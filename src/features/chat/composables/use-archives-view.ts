@@ -10,8 +10,46 @@ type ExportArchiveFileResult = {
   format: "json" | "markdown";
 };
 
+type ImportArchivesResult = {
+  path: string;
+  format: string;
+  importedCount: number;
+  skippedCount: number;
+  archives: ArchiveSummary[];
+};
+
+type RestoreArchiveResult = {
+  mode: "restored" | "forked";
+  conversationId: string;
+  apiConfigId: string;
+  agentId: string;
+  archivedCurrentId?: string | null;
+  keptTurns?: number | null;
+  messageCount: number;
+};
+
+export type SearchHit = {
+  source: "conversation" | "archive";
+  conversationId: string;
+  archiveId?: string | null;
+  messageId?: string | null;
+  role: string;
+  createdAt: string;
+  agentId: string;
+  title: string;
+  snippet: string;
+  highlights: Array<{ start: number; end: number }>;
+  score: number;
+};
+
+type SearchHistoryResult = {
+  total: number;
+  hits: SearchHit[];
+};
+
 type UseArchivesViewOptions = {
   t: TrFn;
+  getChatApiConfigId: () => string;
   setStatus: (text: string) => void;
   setStatusError: (key: string, error: unknown) => void;
 };
@@ -20,6 +58,8 @@ export function useArchivesView(options: UseArchivesViewOptions) {
   const archives = ref<ArchiveSummary[]>([]);
   const archiveMessages = ref<ChatMessage[]>([]);
   const selectedArchiveId = ref("");
+  const searchHits = ref<SearchHit[]>([]);
+  const searchTotal = ref(0);
 
   async function loadArchives() {
     try {
@@ -76,6 +116,60 @@ export function useArchivesView(options: UseArchivesViewOptions) {
     }
   }
 
+  async function restoreArchive(archiveId: string) {
+    if (!archiveId) return;
+    try {
+      const result = await invokeTauri<RestoreArchiveResult>("restore_archive", {
+        input: {
+          archiveId,
+          apiConfigId: options.getChatApiConfigId() || undefined,
+        },
+      });
+      options.setStatus(
+        result.mode === "forked"
+          ? options.t("status.archiveForked", { turns: result.keptTurns ?? 0 })
+          : options.t("status.archiveRestored"),
+      );
+      await loadArchives();
+    } catch (e) {
+      options.setStatusError("status.restoreArchiveFailed", e);
+    }
+  }
+
+  async function importArchives() {
+    try {
+      const result = await invokeTauri<ImportArchivesResult>("import_archives", { input: {} });
+      options.setStatus(
+        options.t("status.archivesImported", {
+          count: result.importedCount,
+          skipped: result.skippedCount,
+          format: result.format,
+        }),
+      );
+      if (result.archives.length > 0) {
+        selectedArchiveId.value = result.archives[0].archiveId;
+      }
+      await loadArchives();
+    } catch (e) {
+      options.setStatusError("status.importArchivesFailed", e);
+    }
+  }
+
+  async function searchHistory(query: string) {
+    if (!query.trim()) {
+      searchHits.value = [];
+      searchTotal.value = 0;
+      return;
+    }
+    try {
+      const result = await invokeTauri<SearchHistoryResult>("search_history", { input: { query } });
+      searchHits.value = result.hits;
+      searchTotal.value = result.total;
+    } catch (e) {
+      options.setStatusError("status.searchHistoryFailed", e);
+    }
+  }
+
   return {
     archives,
     archiveMessages,
@@ -84,6 +178,11 @@ export function useArchivesView(options: UseArchivesViewOptions) {
     selectArchive,
     deleteArchive,
     exportArchive,
+    importArchives,
+    restoreArchive,
+    searchHits,
+    searchTotal,
+    searchHistory,
   };
 }
 
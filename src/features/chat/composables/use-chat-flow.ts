@@ -1,6 +1,6 @@
 import { Channel } from "@tauri-apps/api/core";
 import { ref, type Ref } from "vue";
-import type { ChatMessage } from "../../../types/app";
+import type { ChatMessage, ChatTurn } from "../../../types/app";
 
 export type AssistantDeltaEvent = {
   delta?: string;
@@ -10,10 +10,20 @@ export type AssistantDeltaEvent = {
   message?: string;
 };
 
+type ChatSession = { apiConfigId: string; agentId: string; conversationId?: string };
+
+type SendChatResult = {
+  assistantText: string;
+  latestUserText: string;
+  reasoningStandard?: string;
+  reasoningInline?: string;
+  archivedBeforeSend: boolean;
+};
+
 type UseChatFlowOptions = {
   chatting: Ref<boolean>;
   forcingArchive: Ref<boolean>;
-  getSession: () => { apiConfigId: string; agentId: string } | null;
+  getSession: () => ChatSession | null;
   chatInput: Ref<string>;
   clipboardImages: Ref<Array<{ mime: string; bytesBase64: string }>>;
   latestUserText: Ref<string>;
@@ -32,17 +42,22 @@ type UseChatFlowOptions = {
   invokeSendChatMessage: (input: {
     text: string;
     images: Array<{ mime: string; bytesBase64: string }>;
-    session: { apiConfigId: string; agentId: string };
+    session: ChatSession;
+    onDelta: Channel<AssistantDeltaEvent>;
+  }) => Promise<SendChatResult>;
+  invokeEditChatMessage: (input: {
+    messageId: string;
+    text: string;
+    images: Array<{ mime: string; bytesBase64: string }>;
+    session: ChatSession;
+    onDelta: Channel<AssistantDeltaEvent>;
+  }) => Promise<SendChatResult>;
+  invokeRegenerateChatReply: (input: {
+    session: ChatSession;
     onDelta: Channel<AssistantDeltaEvent>;
-  }) => Promise<{
-    assistantText: string;
-    latestUserText: string;
-    reasoningStandard?: string;
-    reasoningInline?: string;
-    archivedBeforeSend: boolean;
-  }>;
+  }) => Promise<SendChatResult>;
   invokeStopChatMessage?: (input: {
-    session: { apiConfigId: string; agentId: string };
+    session: ChatSession;
     partialAssistantText: string;
     partialReasoningStandard: string;
     partialReasoningInline: string;
@@ -55,10 +70,13 @@ const STREAM_DRAIN_TARGET_MS = 1000;
 
 export function useChatFlow(options: UseChatFlowOptions) {
   let chatGeneration = 0;
+  // 正在生成的回复所属的会话，停止时按它定位，不受期间切换会话的影响。
+  let runningSession: ChatSession | null = null;
   let streamPendingText = "";
   let streamDrainDeadline = 0;
   let streamFlushTimer: ReturnType<typeof setInterval> | null = null;
   const reasoningStartedAtMs = ref(0);
+  const editingMessageId = ref("");
 
   function readDeltaMessage(message: unknown): string {
     if (typeof message === "string") return message;
@@ -123,10 +141,52 @@ export function useChatFlow(options: UseChatFlowOptions) {
     const text = options.chatInput.value.trim();
     if (!text && options.clipboardImages.value.length === 0) return;
     const sendSession = options.getSession();
-    if (!sendSession || !sendSession.apiConfigId || !sendSession.agentId) return;
+    if (!sendSession || !sendSession.agentId) return;
+
+    const sentImages = [...options.clipboardImages.value];
+    const editingId = editingMessageId.value;
+    options.chatInput.value = "";
+    options.clipboardImages.value = [];
+    editingMessageId.value = "";
+
+    await runChat(sendSession, text, sentImages, (onDelta) =>
+      editingId
+        ? options.invokeEditChatMessage({ messageId: editingId, text, images: sentImages, session: sendSession, onDelta })
+        : options.invokeSendChatMessage({ text, images: sentImages, session: sendSession, onDelta }),
+    );
+  }
+
+  async function regenerateReply(turn: ChatTurn) {
+    if (options.chatting.value || options.forcingArchive.value) return;
+    const sendSession = options.getSession();
+    if (!sendSession || !sendSession.agentId) return;
+    await runChat(sendSession, turn.userText, [...turn.userImages], (onDelta) =>
+      options.invokeRegenerateChatReply({ session: sendSession, onDelta }),
+    );
+  }
+
+  function startEditMessage(turn: ChatTurn) {
+    if (options.chatting.value || options.forcingArchive.value) return;
+    editingMessageId.value = turn.id;
+    options.chatInput.value = turn.userText;
+    options.clipboardImages.value = [...turn.userImages];
+  }
+
+  function cancelEditMessage() {
+    if (!editingMessageId.value) return;
+    editingMessageId.value = "";
+    options.chatInput.value = "";
+    options.clipboardImages.value = [];
+  }
 
+  async function runChat(
+    sendSession: ChatSession,
+    text: string,
+    sentImages: Array<{ mime: string; bytesBase64: string }>,
+    invoke: (onDelta: Channel<AssistantDeltaEvent>) => Promise<SendChatResult>,
+  ) {
     options.latestUserText.value = text;
-    options.latestUserImages.value = [...options.clipboardImages.value];
+    options.latestUserImages.value = sentImages;
     options.latestAssistantText.value = "";
     options.latestReasoningStandardText.value = "";
     options.latestReasoningInlineText.value = "";
@@ -134,10 +194,6 @@ export function useChatFlow(options: UseChatFlowOptions) {
     options.toolStatusState.value = "";
     options.chatErrorText.value = "";
 
-    const sentImages = [...options.clipboardImages.value];
-    options.chatInput.value = "";
-    options.clipboardImages.value = [];
-
     options.visibleTurnCount.value = 1;
 
     const gen = ++chatGeneration;
@@ -169,13 +225,9 @@ export function useChatFlow(options: UseChatFlowOptions) {
     };
 
     options.chatting.value = true;
+    runningSession = sendSession;
     try {
-      const result = await options.invokeSendChatMessage({
-        text,
-        images: sentImages,
-        session: sendSession,
-        onDelta: deltaChannel,
-      });
+      const result = await invoke(deltaChannel);
       if (gen !== chatGeneration) return;
       options.latestUserText.value = options.removeBinaryPlaceholders(result.latestUserText);
       options.latestUserImages.value = sentImages;
@@ -222,13 +274,15 @@ export function useChatFlow(options: UseChatFlowOptions) {
       if (gen === chatGeneration) {
         options.chatting.value = false;
         reasoningStartedAtMs.value = 0;
+        runningSession = null;
       }
     }
   }
 
   async function stopChat() {
     if (!options.chatting.value) return;
-    const stopSession = options.getSession();
+    const stopSession = runningSession ?? options.getSession();
+    runningSession = null;
     const gen = ++chatGeneration;
     if (streamPendingText) {
       options.latestAssistantText.value += streamPendingText;
@@ -265,6 +319,10 @@ export function useChatFlow(options: UseChatFlowOptions) {
 
   return {
     sendChat,
+    regenerateReply,
+    startEditMessage,
+    cancelEditMessage,
+    editingMessageId,
     stopChat,
     clearStreamBuffer,
     reasoningStartedAtMs,
@@ -19,7 +19,7 @@ type UseChatMediaOptions = {
   clipboardImages: Ref<Array<{ mime: string; bytesBase64: string }>>;
 };
 
-type RejectionReason = "imageUnsupported" | "pdfNeedsImage" | "pdfNeedsGemini";
+type RejectionReason = "imageUnsupported" | "pdfNeedsImage" | "pdfUnsupportedFormat";
 type LocalBinaryFileResult = { mime: string; bytesBase64: string };
 
 export function useChatMedia(options: UseChatMediaOptions) {
@@ -40,7 +40,8 @@ export function useChatMedia(options: UseChatMediaOptions) {
   }
 
   function canAcceptPdf(apiConfig: ApiConfigItem): boolean {
-    return !!apiConfig.enableImage && apiConfig.requestFormat === "gemini";
+    // gemini/anthropic/openai 原生读取 PDF，其余聊天协议由后端提取文本后发送。
+    return !!apiConfig.enableImage && apiConfig.requestFormat !== "openai_tts";
   }
 
   function classifyFileMime(
@@ -56,7 +57,7 @@ export function useChatMedia(options: UseChatMediaOptions) {
     if (normalized === "application/pdf") {
       if (!apiConfig.enableImage) return { kind: null, reason: "pdfNeedsImage" };
       if (canAcceptPdf(apiConfig)) return { kind: "pdf", reason: null };
-      return { kind: null, reason: "pdfNeedsGemini" };
+      return { kind: null, reason: "pdfUnsupportedFormat" };
     }
     return { kind: null, reason: null };
   }
@@ -81,9 +82,9 @@ export function useChatMedia(options: UseChatMediaOptions) {
   }
 
   function rejectionMessage(reasons: RejectionReason[], apiConfig: ApiConfigItem | null): string {
-    if (reasons.includes("pdfNeedsGemini")) {
+    if (reasons.includes("pdfUnsupportedFormat")) {
       const currentFormat = apiConfig?.requestFormat || "unknown";
-      return `当前接口协议为 ${currentFormat}，不是 gemini，PDF 附件暂不支持。`;
+      return `当前接口协议为 ${currentFormat}，PDF 附件暂不支持。`;
     }
     if (reasons.includes("pdfNeedsImage")) {
       return "当前接口未启用图片能力，暂不支持 PDF 附件。";
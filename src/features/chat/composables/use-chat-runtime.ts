@@ -1,6 +1,7 @@
+import { ref } from "vue";
 import type { Ref, ShallowRef } from "vue";
 import { invokeTauri } from "../../../services/tauri-api";
-import type { ChatMessage } from "../../../types/app";
+import type { ChatMessage, ConversationListItem, MessageBranchInfo } from "../../../types/app";
 
 type TrFn = (key: string, params?: Record<string, unknown>) => string;
 
@@ -26,23 +27,38 @@ type UseChatRuntimeOptions = {
   perfDebug: boolean;
 };
 
+type DeleteConversationResult = {
+  deletedId: string;
+  currentConversationId?: string | null;
+};
+
 export function useChatRuntime(options: UseChatRuntimeOptions) {
+  const conversations = ref<ConversationListItem[]>([]);
+  const messageBranches = ref<Record<string, MessageBranchInfo>>({});
+
+  function setMessageBranches(branches: MessageBranchInfo[]) {
+    messageBranches.value = Object.fromEntries(branches.map((item) => [item.messageId, item]));
+  }
+
+  function sessionInput() {
+    return {
+      apiConfigId: options.activeChatApiConfigId.value,
+      agentId: options.selectedPersonaId.value,
+    };
+  }
+
   async function forceArchiveNow() {
     if (!options.activeChatApiConfigId.value || !options.selectedPersonaId.value) return;
     if (options.chatting.value || options.forcingArchive.value) return;
     options.forcingArchive.value = true;
     try {
       const result = await invokeTauri<ForceArchiveResult>("force_archive_current", {
-        input: {
-          apiConfigId: options.activeChatApiConfigId.value,
-          agentId: options.selectedPersonaId.value,
-        },
+        input: sessionInput(),
       });
       options.setStatus(
         result.archived ? options.t("status.forceArchiveDone", { count: result.mergedMemories }) : result.summary,
       );
-      await loadAllMessages();
-      options.visibleTurnCount.value = 1;
+      await reloadAfterConversationChange();
     } catch (e) {
       options.setStatusError("status.forceArchiveFailed", e);
     } finally {
@@ -54,14 +70,17 @@ export function useChatRuntime(options: UseChatRuntimeOptions) {
     if (!options.activeChatApiConfigId.value || !options.selectedPersonaId.value) return;
     const startedAt = options.perfNow();
     try {
-      const msgs = await invokeTauri<ChatMessage[]>("get_active_conversation_messages", {
-        input: {
-          apiConfigId: options.activeChatApiConfigId.value,
-          agentId: options.selectedPersonaId.value,
-        },
-      });
+      const [msgs, branches] = await Promise.all([
+        invokeTauri<ChatMessage[]>("get_active_conversation_messages", {
+          input: sessionInput(),
+        }),
+        invokeTauri<MessageBranchInfo[]>("get_conversation_branches", {
+          input: { session: sessionInput() },
+        }),
+      ]);
       if (options.perfDebug) console.log(`[PERF] loadAllMessages count=${msgs.length}`);
       options.allMessages.value = msgs;
+      setMessageBranches(branches);
     } catch (e) {
       options.setStatusError("status.loadMessagesFailed", e);
     } finally {
@@ -70,7 +89,91 @@ export function useChatRuntime(options: UseChatRuntimeOptions) {
   }
 
   async function refreshConversationHistory() {
-    await loadAllMessages();
+    await Promise.all([loadAllMessages(), loadConversations()]);
+  }
+
+  async function loadConversations() {
+    if (!options.activeChatApiConfigId.value || !options.selectedPersonaId.value) return;
+    try {
+      conversations.value = await invokeTauri<ConversationListItem[]>("list_conversations", {
+        input: sessionInput(),
+      });
+    } catch (e) {
+      options.setStatusError("status.loadConversationsFailed", e);
+    }
+  }
+
+  async function reloadAfterConversationChange() {
+    options.visibleTurnCount.value = 1;
+    await refreshConversationHistory();
+  }
+
+  async function createConversation() {
+    if (!options.activeChatApiConfigId.value || !options.selectedPersonaId.value) return;
+    if (options.chatting.value || options.forcingArchive.value) return;
+    try {
+      await invokeTauri<ConversationListItem>("create_conversation", {
+        input: { session: sessionInput() },
+      });
+      await reloadAfterConversationChange();
+    } catch (e) {
+      options.setStatusError("status.createConversationFailed", e);
+    }
+  }
+
+  async function switchConversation(conversationId: string) {
+    if (options.chatting.value || options.forcingArchive.value) return;
+    try {
+      await invokeTauri<ConversationListItem>("switch_conversation", { input: { conversationId } });
+      await reloadAfterConversationChange();
+    } catch (e) {
+      options.setStatusError("status.switchConversationFailed", e);
+    }
+  }
+
+  async function renameConversation(payload: { conversationId: string; title: string }) {
+    if (!payload.title.trim()) return;
+    try {
+      await invokeTauri<ConversationListItem>("rename_conversation", { input: payload });
+      await loadConversations();
+    } catch (e) {
+      options.setStatusError("status.renameConversationFailed", e);
+    }
+  }
+
+  async function deleteConversation(conversationId: string) {
+    try {
+      await invokeTauri<DeleteConversationResult>("delete_conversation", { input: { conversationId } });
+      options.setStatus(options.t("status.conversationDeleted"));
+      await reloadAfterConversationChange();
+    } catch (e) {
+      options.setStatusError("status.deleteConversationFailed", e);
+    }
+  }
+
+  async function deleteChatTurn(messageId: string) {
+    if (options.chatting.value || options.forcingArchive.value) return;
+    try {
+      await invokeTauri("delete_chat_message", {
+        input: { messageId, wholeTurn: true, session: sessionInput() },
+      });
+      await refreshConversationHistory();
+    } catch (e) {
+      options.setStatusError("status.deleteMessageFailed", e);
+    }
+  }
+
+  async function switchMessageBranch(payload: { messageId: string; offset: number }) {
+    if (options.chatting.value || options.forcingArchive.value) return;
+    try {
+      const branches = await invokeTauri<MessageBranchInfo[]>("switch_message_branch", {
+        input: { ...payload, session: sessionInput() },
+      });
+      setMessageBranches(branches);
+      await loadAllMessages();
+    } catch (e) {
+      options.setStatusError("status.switchBranchFailed", e);
+    }
   }
 
   function loadMoreTurns() {
@@ -78,6 +181,15 @@ export function useChatRuntime(options: UseChatRuntimeOptions) {
   }
 
   return {
+    conversations,
+    loadConversations,
+    createConversation,
+    switchConversation,
+    renameConversation,
+    deleteConversation,
+    deleteChatTurn,
+    messageBranches,
+    switchMessageBranch,
     refreshConversationHistory,
     forceArchiveNow,
     loadAllMessages,
@@ -28,6 +28,7 @@ export function useChatTurns(options: UseChatTurnsOptions) {
         const userText = removeBinaryPlaceholders(renderMessage(msg));
         const userImages = extractMessageImages(msg);
         const userAudios = extractMessageAudios(msg);
+        let assistantId = "";
         let assistantText = "";
         let assistantReasoningStandard = "";
         let assistantReasoningInline = "";
@@ -35,6 +36,7 @@ export function useChatTurns(options: UseChatTurnsOptions) {
           const assistantMsg = msgs[i + 1];
           const parsed = parseAssistantStoredText(renderMessage(assistantMsg));
           const providerMeta = assistantMsg.providerMeta || {};
+          assistantId = assistantMsg.id;
           assistantText = parsed.assistantText;
           assistantReasoningStandard = parsed.reasoningStandard || String(providerMeta.reasoningStandard || "");
           assistantReasoningInline = parsed.reasoningInline || String(providerMeta.reasoningInline || "");
@@ -53,6 +55,7 @@ export function useChatTurns(options: UseChatTurnsOptions) {
             userText,
             userImages,
             userAudios,
+            assistantId,
             assistantText,
             assistantReasoningStandard,
             assistantReasoningInline,
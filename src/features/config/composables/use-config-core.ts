@@ -60,6 +60,8 @@ export function useConfigCore(options: UseConfigCoreOptions) {
       tools: defaultApiTools(),
       baseUrl: "https://api.openai.com/v1",
       apiKey: "",
+      extraApiKeys: [],
+      keyRotation: "roundRobin",
       model: "gpt-4o-mini",
       temperature: 1,
       contextWindowTokens: 128000,
@@ -95,7 +97,10 @@ export function useConfigCore(options: UseConfigCoreOptions) {
     if (!options.config.apiConfigs.some((a) => a.id === options.config.selectedApiConfigId)) {
       options.config.selectedApiConfigId = options.config.apiConfigs[0].id;
     }
-    if (!options.config.apiConfigs.some((a) => a.id === options.config.chatApiConfigId && a.enableText)) {
+    if (
+      options.config.chatApiConfigId
+      && !options.config.apiConfigs.some((a) => a.id === options.config.chatApiConfigId && a.enableText)
+    ) {
       options.config.chatApiConfigId =
         options.textCapableApiConfigs.value.find((a) => a.requestFormat !== "openai_tts")?.id
         ?? options.textCapableApiConfigs.value[0]?.id
@@ -107,6 +112,12 @@ export function useConfigCore(options: UseConfigCoreOptions) {
     ) {
       options.config.visionApiConfigId = undefined;
     }
+    if (
+      options.config.titleApiConfigId &&
+      !options.textCapableApiConfigs.value.some((a) => a.id === options.config.titleApiConfigId && a.requestFormat !== "openai_tts")
+    ) {
+      options.config.titleApiConfigId = undefined;
+    }
     options.config.sttAutoSend = !!options.config.sttAutoSend;
     if (
       options.config.sttApiConfigId &&
@@ -130,8 +141,14 @@ export function useConfigCore(options: UseConfigCoreOptions) {
       selectedApiConfigId: options.config.selectedApiConfigId,
       chatApiConfigId: options.config.chatApiConfigId,
       ...(options.config.visionApiConfigId ? { visionApiConfigId: options.config.visionApiConfigId } : {}),
+      ...(options.config.titleApiConfigId ? { titleApiConfigId: options.config.titleApiConfigId } : {}),
       ...(options.config.sttApiConfigId ? { sttApiConfigId: options.config.sttApiConfigId } : {}),
       ...(options.config.sttAutoSend ? { sttAutoSend: true } : {}),
+      archivePolicy: { ...options.config.archivePolicy },
+      timezone: options.config.timezone,
+      ...(options.config.highestInstruction
+        ? { highestInstruction: { ...options.config.highestInstruction, rules: [...options.config.highestInstruction.rules] } }
+        : {}),
       apiConfigs: options.config.apiConfigs.map((a) => ({
         id: a.id,
         name: a.name,
@@ -149,6 +166,8 @@ export function useConfigCore(options: UseConfigCoreOptions) {
         })),
         baseUrl: a.baseUrl,
         apiKey: a.apiKey,
+        extraApiKeys: (a.extraApiKeys || []).map((k) => k.trim()).filter(Boolean),
+        keyRotation: a.keyRotation ?? "roundRobin",
         model: a.model,
         temperature: Number(a.temperature ?? 1),
         contextWindowTokens: Math.round(Number(a.contextWindowTokens ?? 128000)),
@@ -167,8 +186,12 @@ export function useConfigCore(options: UseConfigCoreOptions) {
       selectedApiConfigId: options.config.selectedApiConfigId,
       chatApiConfigId: options.config.chatApiConfigId,
       visionApiConfigId: options.config.visionApiConfigId,
+      titleApiConfigId: options.config.titleApiConfigId,
       sttApiConfigId: options.config.sttApiConfigId,
       sttAutoSend: !!options.config.sttAutoSend,
+      archivePolicy: options.config.archivePolicy,
+      timezone: options.config.timezone,
+      highestInstruction: options.config.highestInstruction,
       apiConfigs: options.config.apiConfigs.map((a) => ({
         id: a.id,
         name: a.name,
@@ -186,6 +209,8 @@ export function useConfigCore(options: UseConfigCoreOptions) {
         })),
         baseUrl: a.baseUrl,
         apiKey: a.apiKey,
+        extraApiKeys: a.extraApiKeys ?? [],
+        keyRotation: a.keyRotation ?? "roundRobin",
         model: a.model,
         temperature: a.temperature,
         contextWindowTokens: a.contextWindowTokens,
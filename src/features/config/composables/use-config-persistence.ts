@@ -1,6 +1,6 @@
 import type { ComputedRef, Ref } from "vue";
 import { invokeTauri } from "../../../services/tauri-api";
-import type { AppConfig, PersonaProfile } from "../../../types/app";
+import type { AppConfig, PersonaProfile, ResponseStyleOption } from "../../../types/app";
 import type { SupportedLocale } from "../../../i18n";
 
 type TrFn = (key: string, params?: Record<string, unknown>) => string;
@@ -22,6 +22,7 @@ type UseConfigPersistenceOptions = {
   userAlias: Ref<string>;
   selectedResponseStyleId: Ref<string>;
   responseStyleIds: ComputedRef<string[]>;
+  responseStyleOptions: Ref<ResponseStyleOption[]>;
   createApiConfig: (name?: string) => AppConfig["apiConfigs"][number];
   normalizeApiBindingsLocal: () => void;
   buildConfigPayload: () => AppConfig;
@@ -49,8 +50,12 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
       options.config.selectedApiConfigId = cfg.selectedApiConfigId;
       options.config.chatApiConfigId = cfg.chatApiConfigId;
       options.config.visionApiConfigId = cfg.visionApiConfigId ?? undefined;
+      options.config.titleApiConfigId = cfg.titleApiConfigId ?? undefined;
       options.config.sttApiConfigId = cfg.sttApiConfigId ?? undefined;
       options.config.sttAutoSend = !!cfg.sttAutoSend;
+      options.config.archivePolicy = { ...options.config.archivePolicy, ...cfg.archivePolicy };
+      options.config.timezone = cfg.timezone ?? "";
+      options.config.highestInstruction = cfg.highestInstruction ?? undefined;
       options.config.apiConfigs.splice(
         0,
         options.config.apiConfigs.length,
@@ -84,8 +89,12 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
       options.config.selectedApiConfigId = saved.selectedApiConfigId;
       options.config.chatApiConfigId = saved.chatApiConfigId;
       options.config.visionApiConfigId = saved.visionApiConfigId ?? undefined;
+      options.config.titleApiConfigId = saved.titleApiConfigId ?? undefined;
       options.config.sttApiConfigId = saved.sttApiConfigId ?? undefined;
       options.config.sttAutoSend = !!saved.sttAutoSend;
+      options.config.archivePolicy = { ...options.config.archivePolicy, ...saved.archivePolicy };
+      options.config.timezone = saved.timezone ?? "";
+      options.config.highestInstruction = saved.highestInstruction ?? undefined;
       options.config.apiConfigs.splice(0, options.config.apiConfigs.length, ...saved.apiConfigs);
       options.normalizeApiBindingsLocal();
       options.lastSavedConfigJson.value = options.buildConfigSnapshotJson();
@@ -135,6 +144,7 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
   async function loadChatSettings() {
     options.suppressAutosave.value = true;
     try {
+      options.responseStyleOptions.value = await invokeTauri<ResponseStyleOption[]>("load_response_styles");
       const settings = await invokeTauri<{ selectedAgentId: string; userAlias: string; responseStyleId: string }>(
         "load_chat_settings",
       );
@@ -171,6 +181,44 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
     }
   }
 
+  async function saveResponseStyles(styles: ResponseStyleOption[]) {
+    try {
+      options.responseStyleOptions.value = await invokeTauri<ResponseStyleOption[]>("save_response_styles", {
+        input: { styles },
+      });
+      if (!options.responseStyleIds.value.includes(options.selectedResponseStyleId.value)) {
+        options.selectedResponseStyleId.value = "concise";
+      }
+      await loadPersonas();
+      options.setStatus(options.t("status.responseStylesSaved"));
+    } catch (e) {
+      options.setStatusError("status.saveResponseStylesFailed", e);
+    }
+  }
+
+  async function exportPersona(format: "json" | "card") {
+    const agentId = options.personaEditorId.value;
+    try {
+      await savePersonas();
+      const result = await invokeTauri<{ path: string }>("export_agent", { input: { agentId, format } });
+      options.setStatus(options.t("status.personaExported", { path: result.path }));
+    } catch (e) {
+      options.setStatusError("status.exportPersonaFailed", e);
+    }
+  }
+
+  async function importPersona() {
+    try {
+      await savePersonas();
+      const agent = await invokeTauri<PersonaProfile>("import_agent", { input: {} });
+      await loadPersonas();
+      options.personaEditorId.value = agent.id;
+      options.setStatus(options.t("status.personaImported", { name: agent.name }));
+    } catch (e) {
+      options.setStatusError("status.importPersonaFailed", e);
+    }
+  }
+
   async function saveChatPreferences() {
     options.saving.value = true;
     options.setStatus(options.t("status.savingChatSettings"));
@@ -201,18 +249,21 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
       const saved = await invokeTauri<{
         chatApiConfigId: string;
         visionApiConfigId?: string;
+        titleApiConfigId?: string;
         sttApiConfigId?: string;
         sttAutoSend?: boolean;
       }>("save_conversation_api_settings", {
         input: {
           chatApiConfigId: options.config.chatApiConfigId,
           visionApiConfigId: options.config.visionApiConfigId || null,
+          titleApiConfigId: options.config.titleApiConfigId || null,
           sttApiConfigId: options.config.sttApiConfigId || null,
           sttAutoSend: !!options.config.sttAutoSend,
         },
       });
       options.config.chatApiConfigId = saved.chatApiConfigId;
       options.config.visionApiConfigId = saved.visionApiConfigId ?? undefined;
+      options.config.titleApiConfigId = saved.titleApiConfigId ?? undefined;
       options.config.sttApiConfigId = saved.sttApiConfigId ?? undefined;
       options.config.sttAutoSend = !!saved.sttAutoSend;
       options.lastSavedConfigJson.value = options.buildConfigSnapshotJson();
@@ -230,6 +281,9 @@ export function useConfigPersistence(options: UseConfigPersistenceOptions) {
     loadPersonas,
     loadChatSettings,
     savePersonas,
+    saveResponseStyles,
+    exportPersona,
+    importPersona,
     saveChatPreferences,
     saveConversationApiSettings,
   };
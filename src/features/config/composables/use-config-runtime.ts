@@ -106,6 +106,7 @@ export function useConfigRuntime(options: UseConfigRuntimeOptions) {
           baseUrl: options.selectedApiConfig.value.baseUrl,
           apiKey: options.selectedApiConfig.value.apiKey,
           requestFormat: options.selectedApiConfig.value.requestFormat,
+          apiConfigId: apiId,
         },
       });
       const normalizedModels = models.map((m) => m.trim()).filter(Boolean);
@@ -8,6 +8,7 @@ type AppBootstrapOptions = {
   onThemeChanged: (theme: string) => void;
   onLocaleChanged: (locale: string) => void;
   onRefreshSignal: () => Promise<void>;
+  onConversationTitleChanged: () => void;
 };
 
 export function useAppBootstrap(options: AppBootstrapOptions) {
@@ -32,6 +33,11 @@ export function useAppBootstrap(options: AppBootstrapOptions) {
         await options.onRefreshSignal();
       }),
     );
+    unlisteners.push(
+      await listen("easy-call:conversation-title", () => {
+        options.onConversationTitleChanged();
+      }),
+    );
   }
 
   function unmount() {
@@ -59,6 +59,9 @@ export function useAppWatchers(options: UseAppWatchersOptions) {
       avatarPath: p.avatarPath,
       avatarUpdatedAt: p.avatarUpdatedAt,
       isBuiltInUser: p.isBuiltInUser,
+      archivePolicy: p.archivePolicy,
+      responseStyleId: p.responseStyleId,
+      highestInstruction: p.highestInstruction,
     })),
     () => options.schedulePersonasAutosave(),
     { deep: true },
@@ -112,6 +115,7 @@ export function useAppWatchers(options: UseAppWatchersOptions) {
     () => ({
       chatApiConfigId: options.config.chatApiConfigId,
       visionApiConfigId: options.config.visionApiConfigId,
+      titleApiConfigId: options.config.titleApiConfigId,
       sttApiConfigId: options.config.sttApiConfigId,
       sttAutoSend: options.config.sttAutoSend,
     }),
@@ -24,11 +24,15 @@ export type ApiConfigItem = {
   tools: ApiToolItem[];
   baseUrl: string;
   apiKey: string;
+  extraApiKeys?: string[];
+  keyRotation?: ApiKeyRotation;
   model: string;
   temperature: number;
   contextWindowTokens: number;
 };
 
+export type ApiKeyRotation = "roundRobin" | "leastRateLimited";
+
 export type AppConfig = {
   hotkey: string;
   uiLanguage: "zh-CN" | "en-US" | "ja-JP" | "ko-KR";
@@ -39,11 +43,39 @@ export type AppConfig = {
   selectedApiConfigId: string;
   chatApiConfigId: string;
   visionApiConfigId?: string;
+  titleApiConfigId?: string;
   sttApiConfigId?: string;
   sttAutoSend?: boolean;
+  archivePolicy: ArchivePolicy;
+  timezone: string;
+  highestInstruction?: HighestInstruction;
   apiConfigs: ApiConfigItem[];
 };
 
+export type HighestInstruction = {
+  title: string;
+  rules: string[];
+};
+
+export type AgentInstructionOverride = {
+  disabled: boolean;
+  extraRules: string[];
+};
+
+export type ArchiveMode = "idle" | "daily" | "never";
+
+export type ContextOverflowAction = "archive" | "compact";
+
+export type ArchivePolicy = {
+  mode: ArchiveMode;
+  forceUsageRatio: number;
+  overflowAction: ContextOverflowAction;
+  idleMinutes: number;
+  idleUsageRatio: number;
+  dailyTime: string;
+  fallbackRecentTurns: number;
+};
+
 export type PersonaProfile = {
   id: string;
   name: string;
@@ -53,6 +85,13 @@ export type PersonaProfile = {
   avatarPath?: string;
   avatarUpdatedAt?: string;
   isBuiltInUser?: boolean;
+  archivePolicy?: ArchivePolicy;
+  responseStyleId?: string;
+  highestInstruction?: AgentInstructionOverride;
+  apiConfigId?: string;
+  temperature?: number;
+  allowedTools?: string[];
+  deniedTools?: string[];
 };
 
 export type MessagePart =
@@ -88,6 +127,14 @@ export type ChatMessage = {
     [key: string]: unknown;
   };
   toolCall?: ToolCallMessage[];
+  parentId?: string;
+  siblingIndex?: number;
+};
+
+export type MessageBranchInfo = {
+  messageId: string;
+  siblingPosition: number;
+  siblingCount: number;
 };
 
 export type ChatSnapshot = {
@@ -95,6 +142,7 @@ export type ChatSnapshot = {
   latestUser?: ChatMessage;
   latestAssistant?: ChatMessage;
   activeMessageCount: number;
+  archivePolicy: ArchivePolicy;
 };
 
 export type ChatTurn = {
@@ -102,11 +150,31 @@ export type ChatTurn = {
   userText: string;
   userImages: Array<{ mime: string; bytesBase64: string }>;
   userAudios: Array<{ mime: string; bytesBase64: string }>;
+  assistantId: string;
   assistantText: string;
   assistantReasoningStandard: string;
   assistantReasoningInline: string;
 };
 
+export type ConversationListItem = {
+  conversationId: string;
+  title: string;
+  apiConfigId: string;
+  agentId: string;
+  createdAt: string;
+  updatedAt: string;
+  messageCount: number;
+  lastContextUsageRatio: number;
+  isCurrent: boolean;
+  compaction?: ConversationCompaction;
+};
+
+export type ConversationCompaction = {
+  summary: string;
+  untilMessageId: string;
+  createdAt: string;
+};
+
 export type ArchiveSummary = {
   archiveId: string;
   archivedAt: string;
@@ -118,6 +186,7 @@ export type ResponseStyleOption = {
   id: string;
   name: string;
   prompt: string;
+  builtIn?: boolean;
 };
 
 export type ChatSettings = { selectedPersonaId: string; userAlias: string; responseStyleId: string };